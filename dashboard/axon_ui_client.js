@@ -97,6 +97,8 @@ class AxonEngineClient {
       case 'AlertCreated': this.handleAlertCreated(payload); break;
       case 'WorkerStatusUpdate': this.handleWorkerStatus(payload); break;
       case 'TelegramMessage': this.handleTelegramMessage(payload); break;
+      case 'CommandRejected': this.handleCommandRejected(payload); break;
+      case 'PatchReview': this.handlePatchReview(payload); break;
     }
   }
 
@@ -150,6 +152,37 @@ class AxonEngineClient {
     this.addChatMessage('ai', response.text, response.model);
   }
 
+  handleCommandRejected(rejection) {
+    const details = (rejection.errors || [])
+      .map(e => `${e.path || '/'}: ${e.message}`)
+      .join('\n');
+    console.warn(`[AXON] Command rejected (schema v${rejection.schema_version}):\n${details}`);
+  }
+
+  // Shows the diff in the chat; answer with reviewPatch(id, ...)
+  handlePatchReview(review) {
+    const { preview } = review;
+    const diff = preview.files
+      .map(f => f.error ? `${f.file_path}: ${f.error}` : f.diff)
+      .join('\n');
+    const actions = preview.actions
+      .map((a, i) => `${i + 1}. ${a.change_type} ${a.file_path}: ${a.description}`)
+      .join('\n');
+
+    this.addChatMessage(
+      'ai',
+      `Patch review [${review.id}]: agent '${review.agent}' proposes ${preview.description}\n` +
+      `${actions}\n\`\`\`diff\n${diff}\n\`\`\`\n` +
+      `Decide within ${review.timeout_seconds}s.`
+    );
+  }
+
+  // decision: { decision: 'approve', actions: [0, 2] } | { decision: 'reject' }
+  //         | { decision: 'edit', action: 0, content: '...' }; actions are 0-based
+  reviewPatch(id, decision) {
+    this.send('ReviewPatch', { id, decision });
+  }
+
   addChatMessage(role, text, model = null) {
     const container = document.getElementById('chat-messages');
     if (!container) return;
@@ -203,6 +236,11 @@ class AxonEngineClient {
     this.send('Chat', { message });
     this.addChatMessage('user', message);
   }
+
+  // action: 'changes' | 'merge' | 'discard'; the answer arrives as ChatResponse
+  workspace(action) {
+    this.send('Workspace', { action });
+  }
 }
 
 const axon = new AxonEngineClient();
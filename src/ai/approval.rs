@@ -0,0 +1,264 @@
+//! Human approval for dangerous tool calls.
+//!
+//! A call that needs approval is suspended under a short id and announced
+//! with `ApprovalRequested`: the orchestrator relays it to the CLI and the
+//! Telegram admin chat, the dashboard receives it over WS. `FixApproved`
+//! resumes the call; `FixDenied` or the timeout rejects it. Only approvers
+//! (see `is_approver`) may decide. Every decision is appended to the audit
+//! trail with the ingress that made it.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::config::schema::{ApprovalConfig, AxonConfig};
+use crate::event::bus::EventSender;
+use crate::event::event::{AxonEvent, Ingress};
+
+pub const AUDIT_FILE: &str = "axon_state/audit/approvals.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalOutcome {
+    Approved,
+    Denied,
+    TimedOut,
+}
+
+/// One line of the audit trail
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    pub id: String,
+    pub agent: String,
+    pub tool: String,
+    pub args: Value,
+    pub reason: String,
+    pub requested_at: DateTime<Utc>,
+    pub decided_at: DateTime<Utc>,
+    pub outcome: ApprovalOutcome,
+    /// Who decided; absent when the request timed out
+    #[serde(default)]
+    pub decided_by: Option<Ingress>,
+}
+
+/// Whether `origin` may approve, deny or merge: the CLI, the Telegram
+/// admin chat (`telegram.admin_id`), and the dashboard when
+/// `approval.dashboard` is on
+pub fn is_approver(config: &AxonConfig, origin: &Ingress) -> bool {
+    match origin {
+        Ingress::Cli => true,
+        Ingress::WebSocket => config.approval.dashboard,
+        Ingress::Telegram { chat_id } => telegram_admin(config) == Some(*chat_id),
+    }
+}
+
+/// Chat that receives approval requests, when the Telegram bot is set up
+pub fn telegram_admin(config: &AxonConfig) -> Option<i64> {
+    let telegram = &config.telegram;
+    (!telegram.bot_token.trim().is_empty() && telegram.admin_id != 0).then_some(telegram.admin_id)
+}
+
+type Decision = (ApprovalOutcome, Option<Ingress>);
+type Waiters = Arc<Mutex<HashMap<String, oneshot::Sender<Decision>>>>;
+
+/// Suspends tool calls until a human approves or denies them
+pub struct ApprovalGate {
+    tx: EventSender,
+    timeout: Duration,
+    audit_file: PathBuf,
+    waiting: Waiters,
+}
+
+/// Forgets a request whose caller gave up (e.g. the tool loop timed out)
+struct Pending {
+    id: String,
+    waiting: Waiters,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.waiting.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl ApprovalGate {
+    pub fn new(tx: EventSender, timeout: Duration) -> Self {
+        Self {
+            tx,
+            timeout,
+            audit_file: PathBuf::from(AUDIT_FILE),
+            waiting: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn from_config(tx: EventSender, config: &ApprovalConfig) -> Self {
+        Self::new(tx, Duration::from_secs(config.timeout_seconds))
+    }
+
+    pub fn with_audit_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audit_file = path.into();
+        self
+    }
+
+    /// Ask for approval of `tool` with `args` and wait for the decision
+    pub async fn request(&self, agent: &str, tool: &str, args: &Value, reason: &str) -> ApprovalOutcome {
+        let id = Uuid::new_v4().simple().to_string()[..8].to_string();
+        let (decide, decision) = oneshot::channel();
+
+        self.waiting.lock().unwrap().insert(id.clone(), decide);
+        let _pending = Pending { id: id.clone(), waiting: self.waiting.clone() };
+
+        let requested_at = Utc::now();
+        tracing::info!("Approval {} requested: {} wants {} ({})", id, agent, tool, reason);
+        let _ = self.tx.send(AxonEvent::ApprovalRequested {
+            id: id.clone(),
+            agent: agent.to_string(),
+            tool: tool.to_string(),
+            args: args.clone(),
+            reason: reason.to_string(),
+            timeout_seconds: self.timeout.as_secs(),
+        });
+
+        let (outcome, decided_by) = match tokio::time::timeout(self.timeout, decision).await {
+            Ok(Ok(decision)) => decision,
+            _ => (ApprovalOutcome::TimedOut, None),
+        };
+
+        tracing::info!("Approval {}: {:?}", id, outcome);
+        let _ = self.tx.send(AxonEvent::ApprovalResolved { id: id.clone(), outcome });
+
+        let record = ApprovalRecord {
+            id,
+            agent: agent.to_string(),
+            tool: tool.to_string(),
+            args: args.clone(),
+            reason: reason.to_string(),
+            requested_at,
+            decided_at: Utc::now(),
+            outcome,
+            decided_by,
+        };
+        if let Err(e) = append_audit(&self.audit_file, &record) {
+            tracing::warn!("Approval audit not written: {:#}", e);
+        }
+
+        outcome
+    }
+
+    /// Settle request `id` on behalf of `by`; false when it is unknown or
+    /// already settled
+    pub fn resolve(&self, id: &str, approved: bool, by: Option<Ingress>) -> bool {
+        let Some(decide) = self.waiting.lock().unwrap().remove(id) else {
+            return false;
+        };
+
+        let outcome = if approved { ApprovalOutcome::Approved } else { ApprovalOutcome::Denied };
+        decide.send((outcome, by)).is_ok()
+    }
+
+    /// Ids of the requests still waiting
+    pub fn pending(&self) -> Vec<String> {
+        self.waiting.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Settle requests from `FixApproved` / `FixDenied` on the bus
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let mut rx = self.tx.subscribe();
+
+        loop {
+            let (id, approved, by) = match rx.recv().await {
+                Ok(AxonEvent::FixApproved { alert_id, by }) => (alert_id, true, by),
+                Ok(AxonEvent::FixDenied { alert_id, by }) => (alert_id, false, by),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Approval gate lagged, skipped {} events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            };
+
+            if !self.resolve(&id, approved, by) {
+                tracing::warn!("No pending approval {}", id);
+            }
+        }
+    }
+}
+
+/// Append one decision to the audit trail at `path`
+pub(crate) fn append_audit(path: &Path, record: &ApprovalRecord) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_approve_deny_and_timeout_are_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, mut rx) = tokio::sync::broadcast::channel(16);
+        let gate = Arc::new(
+            ApprovalGate::new(tx.clone(), Duration::from_millis(200)).with_audit_file(dir.path().join("audit.jsonl"))
+        );
+        tokio::spawn(gate.clone().run());
+
+        // Answer each request from the bus, like a user on the CLI would
+        tokio::spawn(async move {
+            let mut answers = [true, false].into_iter();
+            while let Ok(event) = rx.recv().await {
+                if let AxonEvent::ApprovalRequested { id, .. } = event {
+                    match answers.next() {
+                        Some(true) => { let _ = tx.send(AxonEvent::FixApproved { alert_id: id, by: Some(Ingress::Cli) }); }
+                        Some(false) => { let _ = tx.send(AxonEvent::FixDenied { alert_id: id, by: Some(Ingress::Telegram { chat_id: 7 }) }); }
+                        None => {}
+                    }
+                }
+            }
+        });
+
+        let args = json!({"command": "rm -rf target"});
+        assert_eq!(gate.request("coder", "run_shell", &args, "rm").await, ApprovalOutcome::Approved);
+        assert_eq!(gate.request("coder", "run_shell", &args, "rm").await, ApprovalOutcome::Denied);
+        assert_eq!(gate.request("coder", "run_shell", &args, "rm").await, ApprovalOutcome::TimedOut);
+        assert!(gate.pending().is_empty());
+
+        let audit = std::fs::read_to_string(dir.path().join("audit.jsonl")).unwrap();
+        let records: Vec<ApprovalRecord> = audit.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let outcomes: Vec<ApprovalOutcome> = records.iter().map(|r| r.outcome).collect();
+        assert_eq!(outcomes, [ApprovalOutcome::Approved, ApprovalOutcome::Denied, ApprovalOutcome::TimedOut]);
+        let deciders: Vec<Option<Ingress>> = records.into_iter().map(|r| r.decided_by).collect();
+        assert_eq!(deciders, [Some(Ingress::Cli), Some(Ingress::Telegram { chat_id: 7 }), None]);
+    }
+
+    #[test]
+    fn test_only_the_telegram_admin_approves() {
+        let mut config = AxonConfig::default();
+        config.telegram.admin_id = 42;
+        assert!(!is_approver(&config, &Ingress::Telegram { chat_id: 42 }), "no bot token");
+
+        config.telegram.bot_token = "123:abc".into();
+        assert!(is_approver(&config, &Ingress::Telegram { chat_id: 42 }));
+        assert!(!is_approver(&config, &Ingress::Telegram { chat_id: 43 }));
+        assert!(is_approver(&config, &Ingress::Cli));
+        assert!(!is_approver(&config, &Ingress::WebSocket));
+    }
+}
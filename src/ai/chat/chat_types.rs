@@ -18,6 +18,8 @@ impl ChatSession {
     }
 }
 
+impl Default for ChatSession { fn default() -> Self { Self::new() } }
+
 // === AXON_COMPAT: ChatSession::push ===
 impl ChatSession {
     /// Compatibility: old code expects ChatSession::push(role, msg)
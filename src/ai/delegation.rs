@@ -0,0 +1,542 @@
+//! The agent loop: tool calls and agent-to-agent delegation.
+//!
+//! An agent whose `tools` include `delegate` may answer with
+//! `{"tool":"delegate","args":{"agent":"analyst","question":"..."}}`.
+//! The sub-question runs on the named agent and the answer is fed back into
+//! the caller's context. Depth, per-request count and cycles are limited, and
+//! every hop is recorded in a `TraceNode` tree. Other tools named in `tools`
+//! run through the router's `ToolRouter` and feed back the same way, until
+//! the agent answers or the `[tool_loop]` budget is spent. A reply may hold
+//! several calls; read-only ones next to each other run in parallel.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::time::{timeout_at, Instant};
+use uuid::Uuid;
+
+use crate::ai::chat::ChatSession;
+use crate::ai::provider::{ChatMessage, ToolSpec};
+use crate::ai::structured::schema_of;
+use crate::ai::tool_json_detector::{response_tool_calls, ToolCall};
+use crate::ai::provider::LlmProvider;
+use crate::ai::self_reflection::{AiAgent, MultiAgentRouter};
+use crate::ai::tool_router::tool_prompt;
+use crate::event::event::AxonEvent;
+
+pub const DELEGATE_TOOL: &str = "delegate";
+
+/// Answers kept per trace node; full answers still reach the caller
+const TRACE_ANSWER_CHARS: usize = 400;
+
+/// Tool output published in `ToolStep` events
+const STEP_OUTPUT_CHARS: usize = 2000;
+
+/// One agent run in a request; children are the delegations it made
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TraceNode {
+    pub agent: String,
+    pub question: String,
+    pub answer: String,
+    /// Set when the run failed or a delegation was refused
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub children: Vec<TraceNode>,
+}
+
+impl TraceNode {
+    fn new(agent: &str, question: &str) -> Self {
+        Self {
+            agent: agent.to_string(),
+            question: question.to_string(),
+            answer: String::new(),
+            error: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Number of delegations below this node
+    pub fn delegations(&self) -> usize {
+        self.children.iter().map(|c| 1 + c.delegations()).sum()
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct DelegateArgs {
+    agent: String,
+    question: String,
+}
+
+/// Mutable state shared by every hop of one request
+struct DelegationRun {
+    request_id: Uuid,
+    max_depth: usize,
+    remaining: usize,
+    /// Tool calls left, delegations included
+    iterations_left: usize,
+    deadline: Instant,
+    timeout: Duration,
+    steps: u32,
+}
+
+impl DelegationRun {
+    fn timed_out(&self) -> anyhow::Error {
+        anyhow!("time budget of {}s exceeded", self.timeout.as_secs())
+    }
+}
+
+type BoxedRun<'a> = Pin<Box<dyn Future<Output = (Result<String>, TraceNode)> + Send + 'a>>;
+
+impl<P: LlmProvider> MultiAgentRouter<P> {
+
+    /// Run `agent` on `input`, serving its tool calls and `delegate` calls
+    /// until it answers or the `[tool_loop]` budget runs out. Each call is
+    /// published as a `ToolStep` for `request_id`. Returns the final answer
+    /// and the delegation tree.
+    pub async fn execute_traced(
+        &self,
+        request_id: Uuid,
+        agent: Arc<AiAgent<P>>,
+        input: &str,
+        session: &ChatSession,
+        rag_context: &[String],
+    ) -> (Result<String>, TraceNode) {
+        let routing = self.routing();
+        let timeout = Duration::from_secs(self.tool_loop().timeout_seconds);
+        let mut run = DelegationRun {
+            request_id,
+            max_depth: routing.max_delegation_depth,
+            remaining: routing.max_delegations,
+            iterations_left: self.tool_loop().max_iterations,
+            deadline: Instant::now() + timeout,
+            timeout,
+            steps: 0,
+        };
+
+        self.run_node(agent, input.to_string(), session, rag_context, vec![], &mut run).await
+    }
+
+    fn run_node<'a>(
+        &'a self,
+        agent: Arc<AiAgent<P>>,
+        question: String,
+        session: &'a ChatSession,
+        rag_context: &'a [String],
+        path: Vec<String>,
+        run: &'a mut DelegationRun,
+    ) -> BoxedRun<'a> {
+        Box::pin(async move {
+            let mut node = TraceNode::new(&agent.name, &question);
+
+            let mut path = path;
+            path.push(agent.name.clone());
+
+            let can_delegate = agent.allowed_tools.iter().any(|t| t == DELEGATE_TOOL);
+            let specs = self.tool_specs(&agent, can_delegate);
+            let agent = match self.tool_instructions(&agent, can_delegate).await {
+                Some(instructions) => Arc::new(agent.with_system_suffix(&instructions)),
+                None => agent,
+            };
+
+            let mut turns: Vec<ChatMessage> = Vec::new();
+            let mut exhausted = false;
+
+            loop {
+                // No tools offered once the budget is spent
+                let offered = if exhausted { &[][..] } else { &specs[..] };
+                let generated = timeout_at(
+                    run.deadline,
+                    agent.execute_with_tools(&question, session, rag_context, &turns, offered),
+                ).await;
+                let response = match generated.unwrap_or_else(|_| Err(run.timed_out())) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        node.error = Some(format!("{:#}", e));
+                        return (Err(e), node);
+                    }
+                };
+
+                let calls: Vec<ToolCall> = response_tool_calls(&response)
+                    .into_iter()
+                    .filter(|c| agent.allowed_tools.contains(&c.tool))
+                    .collect();
+                let output = response.output;
+
+                if calls.is_empty() {
+                    node.answer = output.chars().take(TRACE_ANSWER_CHARS).collect();
+                    return (Ok(output), node);
+                }
+
+                if exhausted {
+                    node.answer = output.chars().take(TRACE_ANSWER_CHARS).collect();
+                    node.error = Some("tool budget exhausted".into());
+                    return (Ok(output), node);
+                }
+
+                if run.iterations_left == 0 {
+                    // One last turn to answer from what was gathered so far
+                    exhausted = true;
+                    turns.push(ChatMessage::assistant(output, calls));
+                    turns.push(ChatMessage::user(
+                        "The tool budget for this request is used up. \
+                         Give your final answer now without calling tools.",
+                    ));
+                    continue;
+                }
+
+                let mut calls = calls;
+                let skipped = calls.len().saturating_sub(run.iterations_left);
+                calls.truncate(run.iterations_left);
+                run.iterations_left -= calls.len();
+                turns.push(ChatMessage::assistant(output, calls.clone()));
+
+                for batch in self.batches(calls) {
+                    let first_step = run.steps + 1;
+                    run.steps += batch.len() as u32;
+
+                    let results = if batch.len() > 1 {
+                        // Read-only tools: no ordering between them to keep
+                        let shared: &DelegationRun = run;
+                        join_all(batch.iter().map(|call| self.call_tool(&agent.name, call, shared))).await
+                    } else if batch[0].tool == DELEGATE_TOOL {
+                        let started = Instant::now();
+                        let (reply, success) = self
+                            .delegate(&agent, batch[0].args.clone(), session, rag_context, &path, run, &mut node)
+                            .await;
+                        vec![(reply, success, started.elapsed())]
+                    } else {
+                        vec![self.call_tool(&agent.name, &batch[0], run).await]
+                    };
+
+                    for ((call, (reply, success, elapsed)), step) in batch.into_iter().zip(results).zip(first_step..) {
+                        tracing::info!("Agent '{}' step {}: {} ({})", agent.name, step, call.tool, if success { "ok" } else { "failed" });
+                        self.publish(AxonEvent::ToolStep {
+                            request_id: run.request_id,
+                            agent: agent.name.clone(),
+                            step,
+                            tool: call.tool.clone(),
+                            args: call.args,
+                            output: reply.chars().take(STEP_OUTPUT_CHARS).collect(),
+                            success,
+                            duration_ms: elapsed.as_millis() as u64,
+                        });
+                        turns.push(ChatMessage::tool(&call.tool, reply));
+                    }
+                }
+
+                if skipped > 0 {
+                    turns.push(ChatMessage::user(format!(
+                        "{} more tool calls skipped: the tool budget is used up.",
+                        skipped
+                    )));
+                }
+            }
+        })
+    }
+
+    /// Consecutive read-only calls form one parallel batch; every other
+    /// call runs alone, in order
+    fn batches(&self, calls: Vec<ToolCall>) -> Vec<Vec<ToolCall>> {
+        let read_only = |call: &ToolCall| self.tools().is_some_and(|t| t.is_read_only(&call.tool));
+
+        let mut batches: Vec<Vec<ToolCall>> = Vec::new();
+        for call in calls {
+            match batches.last_mut() {
+                Some(batch) if read_only(&call) && read_only(&batch[0]) => batch.push(call),
+                _ => batches.push(vec![call]),
+            }
+        }
+        batches
+    }
+
+    /// Run one registered tool within the request deadline; returns the text
+    /// fed back, whether it succeeded and how long it took
+    async fn call_tool(&self, agent: &str, call: &ToolCall, run: &DelegationRun) -> (String, bool, Duration) {
+        let started = Instant::now();
+
+        let (reply, success) = match self.tools() {
+            None => (format!("Tool '{}' is not available", call.tool), false),
+            Some(tools) => match timeout_at(run.deadline, tools.call(agent, call)).await {
+                Ok(Ok(result)) => (format!("Result of {}:\n{}", call.tool, result), true),
+                Ok(Err(e)) => (format!("Tool {} failed: {:#}", call.tool, e), false),
+                Err(_) => (format!("Tool {} failed: {:#}", call.tool, run.timed_out()), false),
+            },
+        };
+
+        (reply, success, started.elapsed())
+    }
+
+    /// Serve one `delegate` call; returns the text fed back and whether it succeeded
+    #[allow(clippy::too_many_arguments)]
+    async fn delegate(
+        &self,
+        agent: &AiAgent<P>,
+        args: serde_json::Value,
+        session: &ChatSession,
+        rag_context: &[String],
+        path: &[String],
+        run: &mut DelegationRun,
+        node: &mut TraceNode,
+    ) -> (String, bool) {
+        let args = match serde_json::from_value::<DelegateArgs>(args) {
+            Ok(args) => args,
+            Err(e) => return (format!("Delegation refused: invalid arguments ({})", e), false),
+        };
+
+        let target = match self.check_delegation(&args.agent, path, run).await {
+            Ok(target) => target,
+            Err(reason) => {
+                node.children.push(TraceNode {
+                    error: Some(reason.clone()),
+                    ..TraceNode::new(&args.agent, &args.question)
+                });
+                return (format!("Delegation to '{}' refused: {}", args.agent, reason), false);
+            }
+        };
+
+        run.remaining -= 1;
+        tracing::info!("Agent '{}' delegates to '{}'", agent.name, target.name);
+
+        let (result, child) = self
+            .run_node(target, args.question.clone(), session, rag_context, path.to_vec(), run)
+            .await;
+        node.children.push(child);
+
+        match result {
+            Ok(answer) => (format!("Answer from '{}' to \"{}\":\n{}", args.agent, args.question, answer), true),
+            Err(e) => (format!("Delegation to '{}' failed: {:#}", args.agent, e), false),
+        }
+    }
+
+    async fn check_delegation(
+        &self,
+        target: &str,
+        path: &[String],
+        run: &DelegationRun,
+    ) -> std::result::Result<Arc<AiAgent<P>>, String> {
+        if path.len() > run.max_depth {
+            return Err(format!("depth limit of {} reached", run.max_depth));
+        }
+        if run.remaining == 0 {
+            return Err("delegation limit for this request reached".into());
+        }
+        if path.iter().any(|p| p == target) {
+            return Err(format!("loop: {} -> {}", path.join(" -> "), target));
+        }
+
+        self.get(target).await.ok_or_else(|| format!("unknown agent '{}'", target))
+    }
+
+    /// Native definitions of the tools `agent` may call
+    fn tool_specs(&self, agent: &AiAgent<P>, can_delegate: bool) -> Vec<ToolSpec> {
+        let mut specs = self.tools().map(|t| t.specs(&agent.allowed_tools)).unwrap_or_default();
+
+        if can_delegate {
+            specs.push(ToolSpec {
+                name: DELEGATE_TOOL.into(),
+                description: "Ask another agent a sub-question; the answer is added to your context".into(),
+                parameters: schema_of::<DelegateArgs>(),
+            });
+        }
+
+        specs
+    }
+
+    /// System prompt section for the tools `agent` may call, if any
+    async fn tool_instructions(&self, agent: &AiAgent<P>, can_delegate: bool) -> Option<String> {
+        let mut lines = self.tools().map(|t| t.describe(&agent.allowed_tools)).unwrap_or_default();
+
+        if can_delegate {
+            let others = self
+                .agents()
+                .await
+                .iter()
+                .filter(|a| a.name != agent.name)
+                .map(|a| format!("{} ({})", a.name, a.description))
+                .collect::<Vec<_>>()
+                .join("; ");
+
+            lines.push(format!(
+                "- {}(agent: string, question: string): Ask another agent a sub-question. Agents: {}",
+                DELEGATE_TOOL, others
+            ));
+        }
+
+        (!lines.is_empty()).then(|| tool_prompt(&lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::ModelRegistry;
+    use crate::ai::provider::LlmResponse;
+    use crate::ai::test_provider::{text, ScriptedProvider};
+    use crate::ai::tool_router::{Tool, ToolRouter};
+    use crate::config::schema::{AgentConfig, AgentRoutingConfig, AiConfig, ToolLoopConfig};
+    use std::path::Path;
+
+    /// Coder delegates once to the analyst, who tries to delegate back
+    fn delegating_provider() -> ScriptedProvider {
+        ScriptedProvider::new(|prompt| {
+            if prompt.contains("You write code") {
+                if prompt.contains("Answer from 'analyst'") {
+                    "fixed the off-by-one".to_string()
+                } else {
+                    r#"{"tool":"delegate","args":{"agent":"analyst","question":"why does it panic?"}}"#.to_string()
+                }
+            } else if prompt.contains("refused") {
+                "index out of bounds at line 3".to_string()
+            } else {
+                r#"{"tool":"delegate","args":{"agent":"coder","question":"loop back"}}"#.to_string()
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_delegation_tree_and_loop_guard() {
+        let agent = |name: &str, prompt: &str| AgentConfig {
+            name: name.into(),
+            system_prompt: Some(prompt.into()),
+            tools: vec![DELEGATE_TOOL.into()],
+            ..Default::default()
+        };
+
+        let router = MultiAgentRouter::from_config(
+            Arc::new(delegating_provider()),
+            ModelRegistry::from_ai_config(&AiConfig::default()),
+            &[agent("coder", "You write code."), agent("analyst", "You analyze logs.")],
+            Path::new("."),
+        ).unwrap().with_routing(AgentRoutingConfig::default());
+
+        let coder = router.get("coder").await.unwrap();
+        let (answer, trace) = router.execute_traced(Uuid::new_v4(), coder, "fix the panic", &ChatSession::new(), &[]).await;
+
+        assert_eq!(answer.unwrap(), "fixed the off-by-one");
+        assert_eq!(trace.agent, "coder");
+        assert_eq!(trace.delegations(), 2);
+
+        let analyst = &trace.children[0];
+        assert_eq!(analyst.question, "why does it panic?");
+        assert_eq!(analyst.answer, "index out of bounds at line 3");
+        assert!(analyst.children[0].error.as_deref().unwrap().starts_with("loop: coder -> analyst"));
+    }
+
+    struct Echo;
+
+    #[async_trait::async_trait]
+    impl Tool for Echo {
+        fn name(&self) -> &str { "echo" }
+
+        fn description(&self) -> &str { "Echo text" }
+
+        fn schema(&self) -> serde_json::Value { serde_json::json!({ "type": "object" }) }
+
+        fn read_only(&self) -> bool { true }
+
+        async fn execute(&self, args: serde_json::Value) -> Result<String> {
+            Ok(args["text"].as_str().unwrap_or_default().to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_loop_budget_and_step_events() {
+        let (tx, mut rx) = tokio::sync::broadcast::channel(16);
+        let config = AgentConfig { name: "coder".into(), tools: vec!["echo".into()], ..Default::default() };
+
+        let router = MultiAgentRouter::from_config(
+            Arc::new(ScriptedProvider::new(|prompt| {
+                // Keeps calling `echo` until told the budget is spent
+                if prompt.contains("budget for this request is used up") {
+                    "done".to_string()
+                } else {
+                    r#"{"tool":"echo","args":{"text":"again"}}"#.to_string()
+                }
+            })),
+            ModelRegistry::from_ai_config(&AiConfig::default()),
+            &[config],
+            Path::new("."),
+        ).unwrap()
+        .with_tools(Arc::new(ToolRouter::new().with_tool(Echo)))
+        .with_tool_loop(ToolLoopConfig { max_iterations: 2, ..Default::default() })
+        .with_events(tx);
+
+        let id = Uuid::new_v4();
+        let coder = router.get("coder").await.unwrap();
+        let (answer, _) = router.execute_traced(id, coder, "go", &ChatSession::new(), &[]).await;
+        assert_eq!(answer.unwrap(), "done");
+
+        for expected in 1..=2 {
+            match rx.try_recv().unwrap() {
+                AxonEvent::ToolStep { request_id, step, tool, output, success, .. } => {
+                    assert_eq!((request_id, step, tool.as_str(), success), (id, expected, "echo", true));
+                    assert_eq!(output, "Result of echo:\nagain");
+                }
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_multiple_calls_run_and_feed_back_in_order() {
+        let config = AgentConfig { name: "coder".into(), tools: vec!["echo".into()], ..Default::default() };
+
+        let router = MultiAgentRouter::from_config(
+            Arc::new(ScriptedProvider::new(|prompt| {
+                // Two echoes in one fenced array, then what came back
+                match prompt.find("Result of echo") {
+                    Some(at) => prompt[at..].lines().filter(|l| l.starts_with("echo ")).collect::<Vec<_>>().join(","),
+                    None => "```json\n[{\"tool\":\"echo\",\"args\":{\"text\":\"echo a\"}},\
+                             {\"tool\":\"echo\",\"args\":{\"text\":\"echo b\"}}]\n```".to_string(),
+                }
+            })),
+            ModelRegistry::from_ai_config(&AiConfig::default()),
+            &[config],
+            Path::new("."),
+        ).unwrap()
+        .with_tools(Arc::new(ToolRouter::new().with_tool(Echo)));
+
+        let coder = router.get("coder").await.unwrap();
+        let (answer, _) = router.execute_traced(Uuid::new_v4(), coder, "go", &ChatSession::new(), &[]).await;
+        assert_eq!(answer.unwrap(), "echo a,echo b");
+    }
+
+    #[tokio::test]
+    async fn test_native_tool_results_come_back_as_tool_messages() {
+        let provider = Arc::new(ScriptedProvider::chat(|messages| match messages.last() {
+            Some(last) if last.role == "tool" => text(format!("echoed: {}", last.content)),
+            _ => LlmResponse {
+                tool_calls: vec![ToolCall { tool: "echo".into(), args: serde_json::json!({ "text": "hi" }) }],
+                ..text("")
+            },
+        }));
+        let config = AgentConfig { name: "coder".into(), tools: vec!["echo".into()], ..Default::default() };
+
+        let router = MultiAgentRouter::from_config(
+            provider.clone(),
+            ModelRegistry::from_ai_config(&AiConfig::default()),
+            &[config],
+            Path::new("."),
+        ).unwrap()
+        .with_tools(Arc::new(ToolRouter::new().with_tool(Echo)));
+
+        let coder = router.get("coder").await.unwrap();
+        let (answer, _) = router.execute_traced(Uuid::new_v4(), coder, "go", &ChatSession::new(), &[]).await;
+        assert_eq!(answer.unwrap(), "echoed: Result of echo:\nhi");
+
+        let conversation = provider.conversations().pop().unwrap();
+        let roles: Vec<&str> = conversation.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "tool"]);
+        assert_eq!(conversation[1].content, "go");
+        assert_eq!(conversation[2].tool_calls[0].tool, "echo");
+        assert_eq!(conversation[3].tool_name.as_deref(), Some("echo"));
+    }
+}
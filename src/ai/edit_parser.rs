@@ -0,0 +1,599 @@
+//! Turns the edit formats models answer with into `PatchAction`s.
+//!
+//! A reply may hold unified diffs, SEARCH/REPLACE blocks or whole files in
+//! code fences, in any mix. Edits are resolved against the current tree
+//! into full-file actions; hunks whose context drifted in whitespace still
+//! apply, and those that don't are reported one by one so the model can
+//! be asked again with the exact mismatch.
+
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::ai::multi_agent_router::{PatchAction, PatchType};
+use crate::ai::patch_executor::{normalize, replace_block, PatchExecutor};
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HunkLine {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+/// One `@@` section of a unified diff
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub header: String,
+    /// First old line (1-based) from the header, when it has one
+    pub old_start: Option<usize>,
+    pub lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    /// Lines the file must contain for the hunk to apply
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(t) | HunkLine::Removed(t) => Some(t.as_str()),
+                HunkLine::Added(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Diff { file_path: String, hunks: Vec<Hunk>, created: bool, deleted: bool },
+    SearchReplace { file_path: String, search: String, replace: String },
+    /// `named` when the fence info string gave the path; otherwise it came
+    /// from the line above and the body must look like the whole file
+    WholeFile { file_path: String, content: String, named: bool },
+}
+
+impl Edit {
+    pub fn file_path(&self) -> &str {
+        match self {
+            Edit::Diff { file_path, .. } | Edit::SearchReplace { file_path, .. } | Edit::WholeFile { file_path, .. } => file_path,
+        }
+    }
+
+    fn format(&self) -> &'static str {
+        match self {
+            Edit::Diff { .. } => "unified diff",
+            Edit::SearchReplace { .. } => "search/replace",
+            Edit::WholeFile { .. } => "whole file",
+        }
+    }
+}
+
+/// An edit that could not be used, with enough detail to re-prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditError {
+    pub file_path: String,
+    /// 1-based hunk (or SEARCH/REPLACE block) within the file
+    pub hunk: Option<usize>,
+    pub header: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", if self.file_path.is_empty() { "<no file>" } else { &self.file_path })?;
+        if let Some(hunk) = self.hunk {
+            write!(f, " hunk {}", hunk)?;
+        }
+        if let Some(header) = &self.header {
+            write!(f, " `{}`", header)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// Actions for every file whose edits all applied, and what went wrong
+/// with the others. A file with any failed edit gets no action at all.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedEdits {
+    pub actions: Vec<PatchAction>,
+    pub errors: Vec<EditError>,
+}
+
+impl ParsedEdits {
+    /// Error list to send back to the model
+    pub fn feedback(&self) -> String {
+        let mut out = String::from("These edits did not apply:\n");
+        for error in &self.errors {
+            out.push_str(&format!("- {}\n", error));
+        }
+        out.push_str("Copy context and SEARCH lines exactly from the current file.");
+        out
+    }
+}
+
+/// Parse `text` and resolve its edits against the files of `executor`
+pub fn parse_actions(text: &str, executor: &PatchExecutor) -> Result<ParsedEdits> {
+    let (edits, mut errors) = parse(text);
+    if edits.is_empty() && errors.is_empty() {
+        bail!("no edits found in the reply");
+    }
+
+    let mut parsed = resolve(&edits, executor);
+    errors.append(&mut parsed.errors);
+    parsed.errors = errors;
+    Ok(parsed)
+}
+
+/// Every edit in `text`, in order, plus blocks too malformed to use
+pub fn parse(text: &str) -> (Vec<Edit>, Vec<EditError>) {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut edits = Vec::new();
+    let mut errors = Vec::new();
+    // Path named on the line just above; any other line clears it
+    let mut path_hint: Option<String> = None;
+    // Inside a fence whose diffs or SEARCH/REPLACE blocks are read line by
+    // line, with the path the fence was opened for
+    let mut in_fence = false;
+    let mut fence_path: Option<String> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i].trim();
+
+        if line.starts_with(SEARCH_MARKER) {
+            let path = path_hint.take().or_else(|| fence_path.clone());
+            let (edit, next) = parse_search_replace(&lines, i, path.as_deref());
+            match edit {
+                Ok(edit) => {
+                    // The next block may follow without repeating the path
+                    path_hint = Some(edit.file_path().to_string());
+                    edits.push(edit);
+                }
+                Err(e) => errors.push(e),
+            }
+            i = next;
+        } else if is_diff_start(&lines, i) {
+            let (edit, next) = parse_diff(&lines, i);
+            edits.push(edit);
+            path_hint = None;
+            i = next;
+        } else if in_fence && line == "```" {
+            in_fence = false;
+            fence_path = None;
+            path_hint = None;
+            i += 1;
+        } else if let Some(info) = line.strip_prefix("```") {
+            let end = (i + 1..lines.len()).find(|&j| lines[j].trim() == "```").unwrap_or(lines.len());
+            let body = &lines[i + 1..end];
+            let named = info.split_whitespace().find_map(as_path);
+            let above = path_hint.take();
+
+            let nested = body.iter().enumerate().any(|(j, l)| l.trim().starts_with(SEARCH_MARKER) || is_diff_start(body, j));
+            if nested {
+                fence_path = named.or(above);
+                in_fence = true;
+                i += 1;
+                continue;
+            }
+
+            // A command block under "edit `src/lib.rs`, then run:" is not the file
+            let above = above.filter(|_| !is_shell(info));
+            if let Some((file_path, named)) = named.map(|p| (p, true)).or(above.map(|p| (p, false))) {
+                let mut content = body.join("\n");
+                content.push('\n');
+                edits.push(Edit::WholeFile { file_path, content, named });
+            }
+            i = end + 1;
+        } else {
+            path_hint = find_path(line);
+            i += 1;
+        }
+    }
+
+    (edits, errors)
+}
+
+/// Apply `edits` to the current files, one full-file action per changed file
+pub fn resolve(edits: &[Edit], executor: &PatchExecutor) -> ParsedEdits {
+    let mut files: Vec<&str> = Vec::new();
+    for edit in edits {
+        if !files.contains(&edit.file_path()) {
+            files.push(edit.file_path());
+        }
+    }
+
+    let mut parsed = ParsedEdits::default();
+    for file_path in files {
+        let original = match executor.current(file_path) {
+            Ok(original) => original,
+            Err(e) => {
+                parsed.errors.push(EditError { file_path: file_path.to_string(), hunk: None, header: None, reason: format!("{:#}", e) });
+                continue;
+            }
+        };
+
+        let mut content = original.clone();
+        let mut failed = false;
+        let mut block = 0;
+        let mut formats: Vec<&str> = Vec::new();
+
+        for edit in edits.iter().filter(|e| e.file_path() == file_path) {
+            if !formats.contains(&edit.format()) {
+                formats.push(edit.format());
+            }
+
+            match edit {
+                Edit::WholeFile { content: new, named, .. } => {
+                    if let (false, Some(current)) = (named, &content) {
+                        if !is_complete(new, current) {
+                            failed = true;
+                            parsed.errors.push(EditError {
+                                file_path: file_path.to_string(),
+                                hunk: None,
+                                header: None,
+                                reason: "code block looks like a snippet, not the whole file; use SEARCH/REPLACE for partial edits".into(),
+                            });
+                            continue;
+                        }
+                    }
+                    content = Some(new.clone());
+                }
+                Edit::SearchReplace { search, replace, .. } => {
+                    block += 1;
+                    let header = search.lines().find(|l| !l.trim().is_empty()).map(|l| l.trim().to_string());
+                    let result = match &content {
+                        None if search.trim().is_empty() => Ok(replace.clone()),
+                        None => Err("the file does not exist".to_string()),
+                        Some(text) => replace_block(text, search, replace).map_err(|e| e.to_string()),
+                    };
+                    match result {
+                        Ok(new) => content = Some(new),
+                        Err(reason) => {
+                            failed = true;
+                            parsed.errors.push(EditError { file_path: file_path.to_string(), hunk: Some(block), header, reason });
+                        }
+                    }
+                }
+                Edit::Diff { deleted: true, .. } => content = None,
+                Edit::Diff { hunks, created, .. } => {
+                    let base = if *created { String::new() } else if let Some(text) = &content {
+                        text.clone()
+                    } else {
+                        failed = true;
+                        parsed.errors.push(EditError { file_path: file_path.to_string(), hunk: None, header: None, reason: "the file does not exist".into() });
+                        continue;
+                    };
+
+                    match apply_hunks(&base, hunks) {
+                        Ok(new) => content = Some(new),
+                        Err(errors) => {
+                            failed = true;
+                            parsed.errors.extend(errors.into_iter().map(|mut e| {
+                                e.file_path = file_path.to_string();
+                                e
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+
+        if failed || content == original {
+            continue;
+        }
+
+        let (change_type, content) = match (original.is_some(), content) {
+            (_, None) => (PatchType::DeleteFile, None),
+            (false, Some(text)) => (PatchType::CreateFile, Some(text)),
+            (true, Some(text)) => (PatchType::ModifyFile, Some(text)),
+        };
+        parsed.actions.push(PatchAction {
+            file_path: file_path.to_string(),
+            description: format!("Edit from {}", formats.join(", ")),
+            change_type,
+            content,
+            search: None,
+        });
+    }
+
+    parsed
+}
+
+/// Apply `hunks` in order; every hunk that doesn't apply is reported
+pub fn apply_hunks(text: &str, hunks: &[Hunk]) -> Result<String, Vec<EditError>> {
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let mut errors = Vec::new();
+    // Lines added minus lines removed so far, to shift later headers
+    let mut offset: isize = 0;
+    let mut cursor = 0;
+
+    for (n, hunk) in hunks.iter().enumerate() {
+        let fail = |reason: String| EditError { file_path: String::new(), hunk: Some(n + 1), header: Some(hunk.header.clone()), reason };
+        let old = hunk.old_lines();
+        let expected = hunk.old_start.map(|s| (s.saturating_sub(1) as isize + offset).max(0) as usize);
+
+        let start = if old.is_empty() {
+            // Pure insertion: only the header says where
+            let at = expected.unwrap_or(lines.len()).min(lines.len());
+            if hunk.old_start.is_some_and(|s| s > 0) { at + 1 } else { at }.min(lines.len())
+        } else {
+            match locate(&lines, &old, cursor, expected) {
+                Ok(start) => start,
+                Err(reason) => {
+                    errors.push(fail(reason));
+                    continue;
+                }
+            }
+        };
+
+        // Context keeps the file's own spelling of each line
+        let mut replacement = Vec::new();
+        let mut at = start;
+        for line in &hunk.lines {
+            match line {
+                HunkLine::Context(_) => {
+                    replacement.push(lines[at].clone());
+                    at += 1;
+                }
+                HunkLine::Removed(_) => at += 1,
+                HunkLine::Added(t) => replacement.push(t.clone()),
+            }
+        }
+
+        offset += replacement.len() as isize - old.len() as isize;
+        cursor = start + replacement.len();
+        lines.splice(start..start + old.len(), replacement);
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut out = lines.join("\n");
+    if !out.is_empty() && (text.ends_with('\n') || text.is_empty()) {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Where `old` starts in `lines` at or after `from`: exact matches first,
+/// then whitespace-insensitive ones, closest to `expected` when given
+fn locate(lines: &[String], old: &[&str], from: usize, expected: Option<usize>) -> Result<usize, String> {
+    let fits = |start: usize, eq: &dyn Fn(&str, &str) -> bool| {
+        start + old.len() <= lines.len() && old.iter().enumerate().all(|(k, o)| eq(&lines[start + k], o))
+    };
+    let exact = |a: &str, b: &str| a == b;
+    let loose = |a: &str, b: &str| normalize(a) == normalize(b);
+
+    for eq in [&exact as &dyn Fn(&str, &str) -> bool, &loose] {
+        let found: Vec<usize> = (from..lines.len().max(from)).filter(|&s| fits(s, eq)).collect();
+        match (found.as_slice(), expected) {
+            ([], _) => continue,
+            ([one], _) => return Ok(*one),
+            (many, Some(want)) => return Ok(*many.iter().min_by_key(|&&s| s.abs_diff(want)).unwrap()),
+            (many, None) => return Err(format!("context matches {} places; include more lines", many.len())),
+        }
+    }
+
+    // Report where the best partial match stopped agreeing with the file
+    let matched = |start: usize| {
+        old.iter().enumerate().take_while(|(k, o)| lines.get(start + k).is_some_and(|l| normalize(l) == normalize(o))).count()
+    };
+    let best = (from..lines.len())
+        .map(|s| (matched(s), s))
+        .filter(|(count, _)| *count > 0)
+        .max_by_key(|&(count, s)| (count, std::cmp::Reverse(expected.map_or(0, |e| s.abs_diff(e)))));
+
+    Err(match best {
+        None => format!("context not found; `{}` is not in the file", old[0].trim()),
+        Some((count, start)) => match lines.get(start + count) {
+            Some(found) => format!("line {}: expected `{}`, found `{}`", start + count + 1, old[count].trim(), found.trim()),
+            None => format!("expected `{}` after line {}, found end of file", old[count].trim(), start + count),
+        },
+    })
+}
+
+fn is_diff_start(lines: &[&str], i: usize) -> bool {
+    lines[i].starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+}
+
+/// The file section of a unified diff starting at its `---` line
+fn parse_diff(lines: &[&str], start: usize) -> (Edit, usize) {
+    let old = diff_path(&lines[start][4..]);
+    let new = diff_path(&lines[start + 1][4..]);
+    let mut hunks = Vec::new();
+    let mut i = start + 2;
+
+    while i < lines.len() && lines[i].starts_with("@@") {
+        let header = lines[i].trim().to_string();
+        let old_start = header
+            .split_whitespace()
+            .find_map(|t| t.strip_prefix('-'))
+            .and_then(|t| t.split(',').next()?.parse().ok());
+
+        let mut body = Vec::new();
+        i += 1;
+        while i < lines.len() && !lines[i].starts_with("@@") && !is_diff_start(lines, i) && !lines[i].starts_with("```") {
+            let line = lines[i];
+            match line.chars().next() {
+                Some('+') => body.push(HunkLine::Added(line[1..].to_string())),
+                Some('-') => body.push(HunkLine::Removed(line[1..].to_string())),
+                Some(' ') => body.push(HunkLine::Context(line[1..].to_string())),
+                Some('\\') => {}
+                // Blank lines often lose their leading space
+                None => body.push(HunkLine::Context(String::new())),
+                Some(_) => break,
+            }
+            i += 1;
+        }
+
+        while body.last() == Some(&HunkLine::Context(String::new())) {
+            body.pop();
+        }
+        hunks.push(Hunk { header, old_start, lines: body });
+    }
+
+    let edit = Edit::Diff {
+        file_path: new.clone().or_else(|| old.clone()).unwrap_or_default(),
+        hunks,
+        created: old.is_none(),
+        deleted: new.is_none(),
+    };
+    (edit, i)
+}
+
+/// Path of a `---`/`+++` line; `None` for `/dev/null`
+fn diff_path(raw: &str) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or(raw).trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path).to_string())
+}
+
+/// A SEARCH/REPLACE block starting at its `<<<<<<< SEARCH` line
+fn parse_search_replace(lines: &[&str], start: usize, path: Option<&str>) -> (Result<Edit, EditError>, usize) {
+    let error = |reason: &str| EditError {
+        file_path: path.unwrap_or_default().to_string(),
+        hunk: None,
+        header: None,
+        reason: reason.to_string(),
+    };
+
+    let Some(divider) = (start + 1..lines.len()).find(|&j| lines[j].trim() == DIVIDER) else {
+        return (Err(error("SEARCH block has no ======= line")), lines.len());
+    };
+    let Some(end) = (divider + 1..lines.len()).find(|&j| lines[j].trim().starts_with(REPLACE_MARKER)) else {
+        return (Err(error("SEARCH block has no >>>>>>> REPLACE line")), lines.len());
+    };
+    let Some(file_path) = path else {
+        return (Err(error("SEARCH/REPLACE block without a file path before it")), end + 1);
+    };
+
+    let block = |from: usize, to: usize| {
+        let mut text = lines[from..to].join("\n");
+        if to > from {
+            text.push('\n');
+        }
+        text
+    };
+    let edit = Edit::SearchReplace {
+        file_path: file_path.to_string(),
+        search: block(start + 1, divider),
+        replace: block(divider + 1, end),
+    };
+    (Ok(edit), end + 1)
+}
+
+/// Whether `body` can replace all of `current`: it keeps at least half of
+/// the file's non-blank lines, so a snippet never overwrites a file
+fn is_complete(body: &str, current: &str) -> bool {
+    let count = |text: &str| text.lines().filter(|l| !l.trim().is_empty()).count();
+    count(body) * 2 >= count(current)
+}
+
+/// Fence languages for commands and their output
+fn is_shell(info: &str) -> bool {
+    let language = info.split_whitespace().next().unwrap_or("").to_ascii_lowercase();
+    ["bash", "sh", "shell", "zsh", "console", "terminal", "powershell", "ps1", "cmd", "text", "output"].contains(&language.as_str())
+}
+
+/// A file path named on a line of prose: the line itself, or a quoted
+/// token in it (`src/main.rs`, **src/main.rs**, File: src/main.rs)
+fn find_path(line: &str) -> Option<String> {
+    let stripped = line.trim_start_matches('#').trim();
+    let stripped = ["File:", "file:", "Path:", "path:"]
+        .iter()
+        .find_map(|p| stripped.strip_prefix(p))
+        .unwrap_or(stripped);
+
+    as_path(stripped).or_else(|| line.split('`').skip(1).step_by(2).filter_map(as_path).last())
+}
+
+fn as_path(token: &str) -> Option<String> {
+    let token = token.trim().trim_end_matches(':').trim_matches(|c| c == '*' || c == '`' || c == '"' || c == '\'');
+    if token.is_empty() || !token.chars().all(|c| c.is_ascii_alphanumeric() || "._-/\\".contains(c)) {
+        return None;
+    }
+
+    let name = token.rsplit(['/', '\\']).next().unwrap_or(token);
+    let has_extension = name
+        .rsplit_once('.')
+        .is_some_and(|(stem, ext)| !stem.is_empty() && (1..=5).contains(&ext.len()) && ext.chars().all(|c| c.is_ascii_alphanumeric()));
+    (has_extension || (token.contains('/') && !token.ends_with('/'))).then(|| token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_parse_mixed_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "fn a() {\n    one();\n}\n\nfn b() {\n    two();\n}\n").unwrap();
+        fs::write(dir.path().join("src/util.rs"), "pub fn x() -> u32 {\n    1\n}\n").unwrap();
+        let executor = PatchExecutor::new(dir.path());
+
+        // Context indented with tabs instead of spaces, header off by two lines
+        let reply = "Here is the fix.\n\n```diff\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -7,3 +7,3 @@\n fn b() {\n-\ttwo();\n+    three();\n }\n```\n\n\
+                     `src/util.rs`:\n```rust\n<<<<<<< SEARCH\n    1\n=======\n    2\n>>>>>>> REPLACE\n```\n\n\
+                     src/new.rs\n```rust\npub const N: u8 = 1;\n```\n";
+
+        let parsed = parse_actions(reply, &executor).unwrap();
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+
+        let content = |path: &str| parsed.actions.iter().find(|a| a.file_path == path).and_then(|a| a.content.clone()).unwrap();
+        assert_eq!(content("src/lib.rs"), "fn a() {\n    one();\n}\n\nfn b() {\n    three();\n}\n");
+        assert_eq!(content("src/util.rs"), "pub fn x() -> u32 {\n    2\n}\n");
+        assert_eq!(content("src/new.rs"), "pub const N: u8 = 1;\n");
+        assert_eq!(parsed.actions.iter().find(|a| a.file_path == "src/new.rs").unwrap().change_type, PatchType::CreateFile);
+    }
+
+    #[test]
+    fn test_commands_and_snippets_are_not_files() {
+        let (edits, errors) = parse("Edit src/lib.rs to add the check, then run the tests:\n\n```bash\ncargo test\n```\n");
+        assert!(edits.is_empty() && errors.is_empty(), "{:?}", edits);
+
+        let (edits, _) = parse("Edit `src/lib.rs` and run:\n```bash\ncargo test\n```\n");
+        assert!(edits.is_empty(), "{:?}", edits);
+
+        // A path mentioned earlier does not carry over to a later block
+        let (edits, _) = parse("In `src/lib.rs` the loop is wrong.\n\nFor example:\n```rust\nfor i in 0..n {}\n```\n");
+        assert!(edits.is_empty(), "{:?}", edits);
+
+        // A snippet under the path does not replace an existing file
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\nfn e() {}\n").unwrap();
+        let parsed = parse_actions("`src/lib.rs`:\n```rust\nfn b() { todo!() }\n```\n", &PatchExecutor::new(dir.path())).unwrap();
+        assert!(parsed.actions.is_empty());
+        assert!(parsed.errors[0].reason.contains("snippet"), "{:?}", parsed.errors);
+    }
+
+    #[test]
+    fn test_failed_hunk_is_reported() {
+        let text = "a\nb\nc\nd\n";
+        let hunks = |first: &str| {
+            let (edits, _) = parse(&format!("--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n-b\n+B\n@@ -3,2 +3,2 @@\n {}\n-d\n+D\n", first));
+            match edits.into_iter().next() {
+                Some(Edit::Diff { hunks, .. }) => hunks,
+                other => panic!("{:?}", other),
+            }
+        };
+
+        assert_eq!(apply_hunks(text, &hunks("c")).unwrap(), "a\nB\nc\nD\n");
+
+        let errors = apply_hunks(text, &hunks("x")).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].hunk, Some(2));
+        assert_eq!(errors[0].header.as_deref(), Some("@@ -3,2 +3,2 @@"));
+        assert!(errors[0].reason.contains("`x` is not in the file"), "{}", errors[0].reason);
+    }
+}
@@ -1,8 +1,8 @@
-﻿use std::collections::HashMap;
-use std::path::PathBuf;
+﻿use std::path::PathBuf;
 use std::fs;
 
 use anyhow::{Result, Context};
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
@@ -10,7 +10,7 @@ use crate::ai::memory::persistent_store::{save_to_file, load_from_file, exists};
 
 const JOB_DIR: &str = "axon_state/jobs";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum JobStatus {
     Pending,
     Running,
@@ -18,6 +18,24 @@ pub enum JobStatus {
     Failed,
 }
 
+/// One step of a multi-step job (e.g. a plan produced by the Planner)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobStep {
+    pub description: String,
+    /// Agent that should run the step
+    #[serde(default)]
+    pub agent: Option<String>,
+    /// Tool to call instead of an agent
+    #[serde(default)]
+    pub tool: Option<String>,
+    /// Tool input
+    #[serde(default)]
+    pub input: Option<String>,
+    pub status: JobStatus,
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JobRecord {
     pub id: Uuid,
@@ -25,49 +43,80 @@ pub struct JobRecord {
     pub status: JobStatus,
     pub last_step: Option<String>,
     pub payload: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<JobStep>,
 }
 
-pub struct JobStore;
+impl JobRecord {
+    /// Index of the first step that still has to run
+    pub fn next_step(&self) -> Option<usize> {
+        self.steps.iter().position(|s| s.status != JobStatus::Completed)
+    }
+}
+
+/// File-backed job persistence, one JSON file per job
+#[derive(Debug, Clone)]
+pub struct JobStore {
+    dir: PathBuf,
+}
 
 impl JobStore {
 
-    fn ensure_dir() -> Result<()> {
-        if !PathBuf::from(JOB_DIR).exists() {
-            fs::create_dir_all(JOB_DIR)?;
+    pub fn new() -> Self {
+        Self::in_dir(JOB_DIR)
+    }
+
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn ensure_dir(&self) -> Result<()> {
+        if !self.dir.exists() {
+            fs::create_dir_all(&self.dir)?;
         }
         Ok(())
     }
 
-    fn path(id: Uuid) -> PathBuf {
-        PathBuf::from(JOB_DIR)
+    fn path(&self, id: Uuid) -> PathBuf {
+        self.dir
             .join(format!("{}.json", id))
     }
 
     /// Create new job and persist immediately
-    pub fn create(job_type: &str, payload: Option<String>) -> Result<JobRecord> {
-        Self::ensure_dir()?;
+    pub fn create(&self, job_type: &str, payload: Option<String>) -> Result<JobRecord> {
+        self.create_with_id(Uuid::new_v4(), job_type, payload, Vec::new())
+    }
 
+    /// Create a job with a caller-chosen id (e.g. the request id) and steps
+    pub fn create_with_id(
+        &self,
+        id: Uuid,
+        job_type: &str,
+        payload: Option<String>,
+        steps: Vec<JobStep>,
+    ) -> Result<JobRecord> {
         let record = JobRecord {
-            id: Uuid::new_v4(),
+            id,
             job_type: job_type.to_string(),
             status: JobStatus::Pending,
             last_step: None,
             payload,
+            steps,
         };
 
-        Self::save(&record)?;
+        self.save(&record)?;
         Ok(record)
     }
 
-    pub fn save(job: &JobRecord) -> Result<()> {
-        Self::ensure_dir()?;
-        let path = Self::path(job.id);
+    pub fn save(&self, job: &JobRecord) -> Result<()> {
+        self.ensure_dir()?;
+        let path = self.path(job.id);
         save_to_file(&path, job)
             .context("Failed saving job")
     }
 
-    pub fn load(id: Uuid) -> Result<JobRecord> {
-        let path = Self::path(id);
+    pub fn load(&self, id: Uuid) -> Result<JobRecord> {
+        let path = self.path(id);
 
         if !exists(&path) {
             anyhow::bail!("Job not found");
@@ -78,15 +127,19 @@ impl JobStore {
     }
 
     /// Return all incomplete jobs (for resume)
-    pub fn load_incomplete() -> Result<Vec<JobRecord>> {
-        Self::ensure_dir()?;
+    pub fn load_incomplete(&self) -> Result<Vec<JobRecord>> {
+        self.ensure_dir()?;
 
         let mut jobs = Vec::new();
 
-        for entry in fs::read_dir(JOB_DIR)? {
+        for entry in fs::read_dir(&self.dir)? {
             let entry = entry?;
             let path = entry.path();
 
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
             if let Ok(job) = load_from_file::<JobRecord>(&path) {
                 match job.status {
                     JobStatus::Pending | JobStatus::Running => {
@@ -100,16 +153,27 @@ impl JobStore {
         Ok(jobs)
     }
 
-    pub fn update_status(job: &mut JobRecord, status: JobStatus) -> Result<()> {
+    pub fn update_status(&self, job: &mut JobRecord, status: JobStatus) -> Result<()> {
         job.status = status;
-        Self::save(job)
+        self.save(job)
     }
 
-    pub fn update_step(job: &mut JobRecord, step: &str) -> Result<()> {
+    pub fn update_step(&self, job: &mut JobRecord, step: &str) -> Result<()> {
         job.last_step = Some(step.to_string());
-        Self::save(job)
+        self.save(job)
     }
-}
-
 
+    /// Record a step's outcome and checkpoint `last_step` in one write
+    pub fn complete_step(&self, job: &mut JobRecord, index: usize, status: JobStatus, output: String) -> Result<()> {
+        if let Some(step) = job.steps.get_mut(index) {
+            step.status = status;
+            step.output = Some(output);
+        }
+        if status == JobStatus::Completed {
+            job.last_step = Some(index.to_string());
+        }
+        self.save(job)
+    }
+}
 
+impl Default for JobStore { fn default() -> Self { Self::new() } }
@@ -0,0 +1,233 @@
+//! Evaluation records for offline comparison of models, agents and prompts.
+//!
+//! Every answered AI request is stored as one `EvalRecord` in
+//! `axon_state/evals`; user feedback is attached later by request id.
+//! `axon evals export` writes the records as JSONL.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::ai::memory::persistent_store::{exists, load_from_file, save_to_file};
+use crate::ai::model_router::AiTaskType;
+
+const EVAL_DIR: &str = "axon_state/evals";
+
+/// Thumbs up / down from the user, with an optional comment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Feedback {
+    pub positive: bool,
+    #[serde(default)]
+    pub comment: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalRecord {
+    /// Id of the `AiRequest`
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub agent: Option<String>,
+    pub model: String,
+    pub task: AiTaskType,
+    pub prompt: String,
+    pub response: String,
+    /// Final reflection score, when reflection ran
+    pub confidence: Option<f32>,
+    /// Reflection score of each graded attempt
+    #[serde(default)]
+    pub scores: Vec<f32>,
+    pub attempts: u32,
+    pub error: bool,
+    #[serde(default)]
+    pub feedback: Vec<Feedback>,
+}
+
+/// Which records to export. Score bounds are inclusive and exclude
+/// records that were never scored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvalFilter {
+    pub agent: Option<String>,
+    pub model: Option<String>,
+    pub min_score: Option<f32>,
+    pub max_score: Option<f32>,
+}
+
+impl EvalFilter {
+    pub fn matches(&self, record: &EvalRecord) -> bool {
+        let agent_ok = self.agent.as_ref().is_none_or(|a| record.agent.as_ref() == Some(a));
+        let model_ok = self.model.as_ref().is_none_or(|m| &record.model == m);
+
+        let scored = self.min_score.is_some() || self.max_score.is_some();
+        let score_ok = match record.confidence {
+            None => !scored,
+            Some(score) => {
+                self.min_score.is_none_or(|min| score >= min) && self.max_score.is_none_or(|max| score <= max)
+            }
+        };
+
+        agent_ok && model_ok && score_ok
+    }
+}
+
+/// Arguments of `axon evals export`: the filter plus an optional output file
+pub fn parse_export_args(args: &[String]) -> Result<(EvalFilter, Option<PathBuf>)> {
+    let mut filter = EvalFilter::default();
+    let mut out = None;
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let mut value = || iter.next().with_context(|| format!("{} needs a value", flag)).cloned();
+
+        match flag.as_str() {
+            "--agent" => filter.agent = Some(value()?),
+            "--model" => filter.model = Some(value()?),
+            "--min-score" => filter.min_score = Some(value()?.parse().context("--min-score must be a number")?),
+            "--max-score" => filter.max_score = Some(value()?.parse().context("--max-score must be a number")?),
+            "--out" | "-o" => out = Some(PathBuf::from(value()?)),
+            other => bail!(
+                "Unknown option {}\nUsage: axon evals export [--agent <name>] [--model <name>] \
+                 [--min-score <0..1>] [--max-score <0..1>] [--out <file>]",
+                other
+            ),
+        }
+    }
+
+    Ok((filter, out))
+}
+
+/// File-backed evaluation records, one JSON file per request
+#[derive(Debug, Clone)]
+pub struct EvalStore {
+    dir: PathBuf,
+}
+
+impl EvalStore {
+
+    pub fn new() -> Self {
+        Self::in_dir(EVAL_DIR)
+    }
+
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn save(&self, record: &EvalRecord) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        save_to_file(&self.path(record.id), record).context("Failed saving eval record")
+    }
+
+    pub fn load(&self, id: Uuid) -> Result<EvalRecord> {
+        let path = self.path(id);
+
+        if !exists(&path) {
+            bail!("No evaluation record for request {}", id);
+        }
+
+        load_from_file(&path).context("Failed loading eval record")
+    }
+
+    /// Attach a feedback signal to the record of request `id`
+    pub fn add_feedback(&self, id: Uuid, positive: bool, comment: Option<String>) -> Result<EvalRecord> {
+        let mut record = self.load(id)?;
+        record.feedback.push(Feedback { positive, comment, at: Utc::now() });
+        self.save(&record)?;
+        Ok(record)
+    }
+
+    /// All records, oldest first
+    pub fn all(&self) -> Result<Vec<EvalRecord>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                match load_from_file::<EvalRecord>(&path) {
+                    Ok(record) => records.push(record),
+                    Err(e) => tracing::warn!("Skipping eval record {}: {:#}", path.display(), e),
+                }
+            }
+        }
+
+        records.sort_by_key(|r| r.created_at);
+        Ok(records)
+    }
+
+    /// Write matching records as JSON lines; returns how many were written
+    pub fn export_jsonl(&self, filter: &EvalFilter, out: &mut impl Write) -> Result<usize> {
+        let mut count = 0;
+
+        for record in self.all()?.iter().filter(|r| filter.matches(r)) {
+            serde_json::to_writer(&mut *out, record)?;
+            out.write_all(b"\n")?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+impl Default for EvalStore { fn default() -> Self { Self::new() } }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(agent: &str, model: &str, confidence: Option<f32>) -> EvalRecord {
+        EvalRecord {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            agent: Some(agent.into()),
+            model: model.into(),
+            task: AiTaskType::General,
+            prompt: "p".into(),
+            response: "r".into(),
+            confidence,
+            scores: confidence.into_iter().collect(),
+            attempts: 1,
+            error: false,
+            feedback: vec![],
+        }
+    }
+
+    #[test]
+    fn test_feedback_and_filtered_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EvalStore::in_dir(dir.path());
+
+        let good = record("coder", "qwen2.5-coder:7b", Some(0.9));
+        store.save(&good).unwrap();
+        store.save(&record("coder", "llama3:8b", Some(0.4))).unwrap();
+        store.save(&record("general", "qwen2.5-coder:7b", None)).unwrap();
+
+        store.add_feedback(good.id, true, Some("spot on".into())).unwrap();
+
+        let args: Vec<String> = ["--agent", "coder", "--min-score", "0.5"].iter().map(|s| s.to_string()).collect();
+        let (filter, out) = parse_export_args(&args).unwrap();
+        assert!(out.is_none());
+
+        let mut buf = Vec::new();
+        assert_eq!(store.export_jsonl(&filter, &mut buf).unwrap(), 1);
+
+        let exported: EvalRecord = serde_json::from_slice(buf.split(|b| *b == b'\n').next().unwrap()).unwrap();
+        assert_eq!(exported.id, good.id);
+        assert_eq!(exported.feedback[0].comment.as_deref(), Some("spot on"));
+
+        let by_model = EvalFilter { model: Some("qwen2.5-coder:7b".into()), ..Default::default() };
+        assert_eq!(store.export_jsonl(&by_model, &mut Vec::new()).unwrap(), 2);
+
+        assert!(parse_export_args(&["--min-score".to_string(), "high".to_string()]).is_err());
+    }
+}
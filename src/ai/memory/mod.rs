@@ -1,8 +1,4 @@
 ﻿pub mod persistent_store;
-pub mod chat_store;
+pub mod conversation_embeddings;
 pub mod job_store;
-
-
-
-
-
+pub mod eval_store;
@@ -1,3 +1,6 @@
-﻿pub mod model_router; pub mod chat; pub mod models; pub mod prompt_builder; pub mod provider; pub mod streaming_ollama; pub mod self_reflection; pub mod multi_agent_router; pub mod tool_json_detector; pub mod tool_router; pub mod patch_tree;
+﻿pub mod model_router; pub mod chat; pub mod models; pub mod prompt_builder; pub mod provider; pub mod streaming_ollama; pub mod self_reflection; pub mod multi_agent_router; pub mod tool_json_detector; pub mod tool_router; pub mod patch_tree; pub mod memory; pub mod planner; pub mod delegation; pub mod structured; pub mod approval; pub mod patch_executor; pub mod patch_review; pub mod repair_loop; pub mod edit_parser; pub mod workspace;
 
 pub mod ollama;
+
+#[cfg(test)]
+pub mod test_provider;
@@ -1,13 +1,66 @@
-﻿use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use crate::ai::models::ModelRegistry;
+use crate::ai::provider::{LlmProvider, OllamaProvider};
+use crate::config::schema::AiConfig;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub enum AiTaskType { General, Coding, Analysis }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ToolCall {
-    pub command: String,
-    pub args: Vec<String>,
+impl AiTaskType {
+    /// Best-effort task detection for requests that were not tagged upstream
+    pub fn infer(prompt: &str) -> Self {
+        let lower = prompt.to_lowercase();
+
+        const CODING: [&str; 13] = [
+            "```", "fn ", "impl ", "refactor", "refactoring", "implement", "implementation",
+            "write a", "compile", "compiler", "rust code", "patch", "patches",
+        ];
+        const ANALYSIS: [&str; 9] = ["analyze", "analyse", "analysis", "root cause", "stack trace", "panicked", "log", "logs", "why does"];
+
+        if CODING.iter().any(|k| mentions(&lower, k)) {
+            AiTaskType::Coding
+        } else if ANALYSIS.iter().any(|k| mentions(&lower, k)) {
+            AiTaskType::Analysis
+        } else {
+            AiTaskType::General
+        }
+    }
+}
+
+/// Whether `keyword` occurs in `text` as whole words, so "log" does not
+/// match "catalog" or "login"
+pub(crate) fn mentions(text: &str, keyword: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let starts_word = keyword.starts_with(is_word);
+    let ends_word = keyword.ends_with(is_word);
+
+    text.match_indices(keyword).any(|(at, _)| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + keyword.len()..].chars().next();
+
+        let clear_before = !starts_word || before.is_none_or(|c| !is_word(c));
+        let clear_after = !ends_word || after.is_none_or(|c| !is_word(c));
+
+        clear_before && clear_after
+    })
+}
+
+/// Which model served a request and why; attached to `AiResponse`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RoutingDecision {
+    pub task: AiTaskType,
+    pub model: String,
+    pub max_tokens: u32,
+    pub reason: String,
+    /// Agent that handled the request, if any
+    #[serde(default)]
+    pub agent: Option<String>,
+    /// Why that agent was chosen
+    #[serde(default)]
+    pub agent_reason: Option<String>,
+    #[serde(default)]
+    pub agent_confidence: Option<f32>,
 }
 
 pub struct ModelRouter {
@@ -18,8 +71,105 @@ impl ModelRouter {
     pub fn new(registry: ModelRegistry) -> Self {
         Self { registry }
     }
+
+    pub fn from_config(ai: &AiConfig) -> Self {
+        Self::new(ModelRegistry::from_ai_config(ai))
+    }
+
+    /// Pick the model for a task. An explicitly requested model wins.
+    pub fn route(&self, task: AiTaskType, requested: Option<&str>) -> RoutingDecision {
+        if let Some(name) = requested.filter(|n| !n.trim().is_empty()) {
+            let max_tokens = self.registry
+                .find(name)
+                .unwrap_or_else(|| self.registry.model_for(task))
+                .max_tokens;
+
+            return RoutingDecision {
+                task,
+                model: name.to_string(),
+                max_tokens,
+                reason: "model requested explicitly".into(),
+                agent: None,
+                agent_reason: None,
+                agent_confidence: None,
+            };
+        }
+
+        let model = self.registry.model_for(task);
+        let slot = match task {
+            AiTaskType::Coding => "coder_model",
+            AiTaskType::General | AiTaskType::Analysis => "default_model",
+        };
+
+        RoutingDecision {
+            task,
+            model: model.name,
+            max_tokens: model.max_tokens,
+            reason: format!("{:?} task routed to {}", task, slot),
+            agent: None,
+            agent_reason: None,
+            agent_confidence: None,
+        }
+    }
+}
+
+/// Route a prompt for an explicit task and query the configured provider
+pub async fn query_for_task(task: AiTaskType, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let config = crate::config::loader::get_config();
+    let router = ModelRouter::from_config(&config.ai);
+    let decision = router.route(task, None);
+
+    tracing::info!("Model route: {} ({})", decision.model, decision.reason);
+
+    let provider = OllamaProvider::from_config(&config.ai);
+    let response = provider.generate(prompt, &decision.model, decision.max_tokens).await?;
+
+    Ok(response.output)
 }
 
 pub async fn route_and_query(prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-    Ok(format!("DeepSeek Response to: {}", prompt))
+    query_for_task(AiTaskType::infer(prompt), prompt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::ModelInfo;
+
+    fn router() -> ModelRouter {
+        let ai = AiConfig {
+            default_model: ModelInfo { name: "qwen2.5:7b".into(), max_tokens: 4096 },
+            coder_model: ModelInfo { name: "qwen2.5-coder:7b".into(), max_tokens: 8192 },
+            ..AiConfig::default()
+        };
+        ModelRouter::from_config(&ai)
+    }
+
+    #[test]
+    fn test_task_models() {
+        let router = router();
+
+        let coding = router.route(AiTaskType::Coding, None);
+        assert_eq!(coding.model, "qwen2.5-coder:7b");
+        assert_eq!(coding.max_tokens, 8192);
+
+        assert_eq!(router.route(AiTaskType::General, None).model, "qwen2.5:7b");
+        assert_eq!(router.route(AiTaskType::Analysis, None).model, "qwen2.5:7b");
+    }
+
+    #[test]
+    fn test_explicit_model_wins() {
+        let decision = router().route(AiTaskType::Coding, Some("llama3:8b"));
+        assert_eq!(decision.model, "llama3:8b");
+        assert_eq!(decision.reason, "model requested explicitly");
+    }
+
+    #[test]
+    fn test_infer_task() {
+        assert_eq!(AiTaskType::infer("refactor this function"), AiTaskType::Coding);
+        assert_eq!(AiTaskType::infer("analyze the crash log"), AiTaskType::Analysis);
+        assert_eq!(AiTaskType::infer("hello there"), AiTaskType::General);
+        assert_eq!(AiTaskType::infer("show the product catalog in a dialog after login"), AiTaskType::General);
+        assert_eq!(AiTaskType::infer("why are these logs empty?"), AiTaskType::Analysis);
+    }
 }
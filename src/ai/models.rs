@@ -1,29 +1,61 @@
 use serde::{Deserialize, Serialize};
-use crate::config::schema::ModelInfo;
+use crate::ai::model_router::AiTaskType;
+use crate::config::schema::{AiConfig, ModelInfo};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelRegistry {
     pub models: Vec<ModelInfo>,
     pub default_model_name: String,
+    pub coder_model_name: String,
 }
 
 impl ModelRegistry {
     pub fn from_config(default: String, coder: String, embed: String, max_tokens: u32) -> Self {
-        let mut models = Vec::new();
-        models.push(ModelInfo { name: default.clone(), max_tokens });
-        models.push(ModelInfo { name: coder, max_tokens });
-        models.push(ModelInfo { name: embed, max_tokens });
+        let models = vec![
+            ModelInfo { name: default.clone(), max_tokens },
+            ModelInfo { name: coder.clone(), max_tokens },
+            ModelInfo { name: embed, max_tokens },
+        ];
 
         Self {
             models,
             default_model_name: default,
+            coder_model_name: coder,
         }
     }
 
+    /// Registry from `[ai]`, keeping each model's own token budget
+    pub fn from_ai_config(ai: &AiConfig) -> Self {
+        Self {
+            models: vec![
+                ai.default_model.clone(),
+                ai.coder_model.clone(),
+                ai.embed_model.clone(),
+            ],
+            default_model_name: ai.default_model.name.clone(),
+            coder_model_name: ai.coder_model.name.clone(),
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<ModelInfo> {
+        self.models.iter().find(|m| m.name == name).cloned()
+    }
+
     pub fn default_model(&self) -> ModelInfo {
-        self.models.iter()
-            .find(|m| m.name == self.default_model_name)
-            .cloned()
+        self.find(&self.default_model_name)
             .unwrap_or_else(|| ModelInfo { name: "default".into(), max_tokens: 4096 })
     }
+
+    pub fn coder_model(&self) -> ModelInfo {
+        self.find(&self.coder_model_name)
+            .unwrap_or_else(|| self.default_model())
+    }
+
+    /// Configured model for a task type
+    pub fn model_for(&self, task: AiTaskType) -> ModelInfo {
+        match task {
+            AiTaskType::Coding => self.coder_model(),
+            AiTaskType::General | AiTaskType::Analysis => self.default_model(),
+        }
+    }
 }
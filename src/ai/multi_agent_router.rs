@@ -1,15 +1,19 @@
-﻿use serde::{Serialize, Deserialize};
+﻿use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
 
 /// Represents one atomic change in a patch plan
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PatchAction {
     pub file_path: String,
     pub description: String,
     pub change_type: PatchType,
     pub content: Option<String>,
+    /// Block to replace, for `ReplaceBlock`
+    #[serde(default)]
+    pub search: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum PatchType {
     CreateFile,
     ModifyFile,
@@ -18,7 +22,7 @@ pub enum PatchType {
 }
 
 /// Tree structure for complex patch planning
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PatchNode {
     pub id: String,
     pub description: String,
@@ -70,3 +74,5 @@ impl MultiAgentRouter {
     pub fn new() -> Self { MultiAgentRouter }
 }
 
+impl Default for MultiAgentRouter { fn default() -> Self { Self::new() } }
+
@@ -4,6 +4,8 @@ use std::time::Duration;
 
 pub struct OllamaProvider;
 
+impl Default for OllamaProvider { fn default() -> Self { Self::new() } }
+
 #[derive(Serialize)]
 struct OllamaRequest {
     model: String,
@@ -0,0 +1,386 @@
+//! Applies `PatchNode` trees to a project.
+//!
+//! Every path is resolved inside the project root; originals are
+//! snapshotted before the first write to them. If one action fails, all
+//! applied ones are rolled back. The `Backup` of a successful run can
+//! still revert it later (e.g. when the build breaks).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::ai::multi_agent_router::{PatchAction, PatchNode, PatchType};
+use crate::util::path::confine;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionStatus {
+    Applied,
+    Failed,
+    /// Applied, then undone because a later action failed
+    RolledBack,
+    /// Not attempted because an earlier action failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ActionReport {
+    pub file_path: String,
+    pub change_type: PatchType,
+    pub description: String,
+    pub status: ActionStatus,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PatchReport {
+    pub success: bool,
+    pub actions: Vec<ActionReport>,
+}
+
+impl PatchReport {
+    /// One line per action, e.g. `Applied ModifyFile src/main.rs`
+    pub fn summary(&self) -> String {
+        self.actions
+            .iter()
+            .map(|a| {
+                let mut line = format!("{:?} {:?} {}", a.status, a.change_type, a.file_path);
+                if let Some(error) = &a.error {
+                    line.push_str(&format!(": {}", error));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// File contents from before a patch; `None` marks files that did not exist
+#[derive(Debug, Clone, Default)]
+pub struct Backup {
+    originals: Vec<(PathBuf, Option<Vec<u8>>)>,
+    created_dirs: Vec<PathBuf>,
+}
+
+impl Backup {
+    fn remember(&mut self, path: &Path) -> Result<()> {
+        if self.originals.iter().any(|(p, _)| p == path) {
+            return Ok(());
+        }
+
+        let original = if path.exists() {
+            Some(fs::read(path).with_context(|| format!("Cannot snapshot {}", path.display()))?)
+        } else {
+            None
+        };
+        self.originals.push((path.to_path_buf(), original));
+        Ok(())
+    }
+
+    /// Files this backup covers
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.originals.iter().map(|(p, _)| p.as_path())
+    }
+
+    /// Put every file back as it was; directories the patch created are
+    /// removed when empty
+    pub fn restore(&self) -> Result<()> {
+        let mut failures = Vec::new();
+
+        for (path, original) in self.originals.iter().rev() {
+            let restored = match original {
+                Some(bytes) => fs::write(path, bytes),
+                None if path.exists() => fs::remove_file(path),
+                None => Ok(()),
+            };
+            if let Err(e) = restored {
+                failures.push(format!("{}: {}", path.display(), e));
+            }
+        }
+
+        for dir in self.created_dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+
+        if !failures.is_empty() {
+            bail!("Restore incomplete: {}", failures.join("; "));
+        }
+        Ok(())
+    }
+}
+
+/// Report of one run plus what is needed to revert it
+#[derive(Debug, Clone)]
+pub struct PatchOutcome {
+    pub report: PatchReport,
+    pub backup: Backup,
+}
+
+pub struct PatchExecutor {
+    root: PathBuf,
+}
+
+impl PatchExecutor {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Apply every action of `node` and its children, all or nothing
+    pub fn apply(&self, node: &PatchNode) -> PatchOutcome {
+        self.apply_actions(&node.flatten())
+    }
+
+    pub fn apply_actions(&self, actions: &[PatchAction]) -> PatchOutcome {
+        let mut backup = Backup::default();
+        let mut reports: Vec<ActionReport> = actions
+            .iter()
+            .map(|a| ActionReport {
+                file_path: a.file_path.clone(),
+                change_type: a.change_type.clone(),
+                description: a.description.clone(),
+                status: ActionStatus::Skipped,
+                error: None,
+            })
+            .collect();
+
+        for (action, report) in actions.iter().zip(reports.iter_mut()) {
+            match self.apply_one(action, &mut backup) {
+                Ok(()) => report.status = ActionStatus::Applied,
+                Err(e) => {
+                    report.status = ActionStatus::Failed;
+                    report.error = Some(format!("{:#}", e));
+                    break;
+                }
+            }
+        }
+
+        let success = reports.iter().all(|r| r.status == ActionStatus::Applied);
+        if !success {
+            if let Err(e) = backup.restore() {
+                tracing::error!("Patch rollback failed: {:#}", e);
+            }
+            for report in reports.iter_mut().filter(|r| r.status == ActionStatus::Applied) {
+                report.status = ActionStatus::RolledBack;
+            }
+            backup = Backup::default();
+        }
+
+        PatchOutcome { report: PatchReport { success, actions: reports }, backup }
+    }
+
+    fn apply_one(&self, action: &PatchAction, backup: &mut Backup) -> Result<()> {
+        let path = self.resolve(&action.file_path)?;
+        let current = self.read(&path)?;
+
+        match transform(action, current.as_deref())? {
+            Some(content) => {
+                if current.is_none() {
+                    self.create_parents(&path, backup)?;
+                }
+                backup.remember(&path)?;
+                fs::write(&path, content)?;
+            }
+            None => {
+                backup.remember(&path)?;
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Current text of a file under the root, `None` when it does not exist
+    pub fn current(&self, file_path: &str) -> Result<Option<String>> {
+        let path = self.resolve(file_path)?;
+        self.read(&path)
+    }
+
+    fn read(&self, path: &Path) -> Result<Option<String>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        if !path.is_file() {
+            bail!("{} is not a file", path.display());
+        }
+
+        let bytes = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    /// `file_path` inside the root, also through symlinked directories
+    fn resolve(&self, file_path: &str) -> Result<PathBuf> {
+        let path = confine(&self.root, file_path)?;
+
+        let root = self.root.canonicalize()
+            .with_context(|| format!("Project root {} not found", self.root.display()))?;
+        let existing = path.ancestors().find(|p| p.exists()).unwrap_or(&self.root);
+        if !existing.canonicalize()?.starts_with(&root) {
+            bail!("Path escapes the project root: {}", file_path);
+        }
+
+        Ok(path)
+    }
+
+    fn create_parents(&self, path: &Path, backup: &mut Backup) -> Result<()> {
+        let Some(parent) = path.parent() else {
+            return Ok(());
+        };
+
+        let missing: Vec<PathBuf> = parent.ancestors().take_while(|p| !p.exists()).map(Path::to_path_buf).collect();
+        fs::create_dir_all(parent)?;
+        backup.created_dirs.extend(missing.into_iter().rev());
+        Ok(())
+    }
+}
+
+/// Content of `action.file_path` after `action`, given its `current`
+/// content; `None` when the action deletes the file
+pub fn transform(action: &PatchAction, current: Option<&str>) -> Result<Option<String>> {
+    let content = action.content.as_deref();
+
+    match (&action.change_type, current) {
+        (PatchType::CreateFile, Some(_)) => bail!("{} already exists", action.file_path),
+        (PatchType::CreateFile, None) => Ok(Some(content.unwrap_or_default().to_string())),
+
+        (_, None) => bail!("{} does not exist", action.file_path),
+
+        (PatchType::ModifyFile, Some(_)) => {
+            Ok(Some(content.context("ModifyFile needs the new content")?.to_string()))
+        }
+
+        (PatchType::DeleteFile, Some(_)) => Ok(None),
+
+        (PatchType::ReplaceBlock, Some(text)) => {
+            let search = action.search.as_deref().context("ReplaceBlock needs the block to replace")?;
+            replace_block(text, search, content.unwrap_or_default())
+                .with_context(|| format!("{} in {}", action.description, action.file_path))
+                .map(Some)
+        }
+    }
+}
+
+/// Replace the one occurrence of `search` in `text`. Lines are compared
+/// exactly first, then ignoring differences in whitespace.
+pub fn replace_block(text: &str, search: &str, replace: &str) -> Result<String> {
+    match text.matches(search).count() {
+        1 => return Ok(text.replacen(search, replace, 1)),
+        n if n > 1 => bail!("block to replace occurs {} times; include more context", n),
+        _ => {}
+    }
+
+    let lines: Vec<&str> = text.lines().collect();
+    let wanted: Vec<String> = search.lines().map(normalize).filter(|l| !l.is_empty()).collect();
+    if wanted.is_empty() {
+        bail!("block to replace is empty");
+    }
+
+    let starts: Vec<(usize, usize)> = (0..lines.len())
+        .filter_map(|start| match_at(&lines, start, &wanted).map(|end| (start, end)))
+        .collect();
+
+    let (start, end) = match starts.as_slice() {
+        [one] => *one,
+        [] => bail!("block to replace not found"),
+        many => bail!("block to replace occurs {} times; include more context", many.len()),
+    };
+
+    let mut out: Vec<&str> = lines[..start].to_vec();
+    out.extend(replace.lines());
+    out.extend(&lines[end..]);
+
+    let mut result = out.join("\n");
+    if text.ends_with('\n') {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+/// End (exclusive) of the lines from `start` that match `wanted`,
+/// skipping blank lines on both sides
+fn match_at(lines: &[&str], start: usize, wanted: &[String]) -> Option<usize> {
+    if normalize(lines[start]).is_empty() {
+        return None;
+    }
+
+    let mut i = start;
+    for want in wanted {
+        while i < lines.len() && normalize(lines[i]).is_empty() {
+            i += 1;
+        }
+        if i >= lines.len() || normalize(lines[i]) != *want {
+            return None;
+        }
+        i += 1;
+    }
+    Some(i)
+}
+
+/// Line with runs of whitespace collapsed and the ends trimmed
+pub(crate) fn normalize(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(path: &str, change_type: PatchType, content: Option<&str>, search: Option<&str>) -> PatchAction {
+        PatchAction {
+            file_path: path.into(),
+            description: format!("{:?} {}", change_type, path),
+            change_type,
+            content: content.map(String::from),
+            search: search.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_apply_and_revert() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("lib.rs"), "fn a() {\n    1\n}\n").unwrap();
+        fs::write(dir.path().join("old.rs"), "old").unwrap();
+
+        let mut node = PatchNode::new("fix", "fix a");
+        node.add_action(action("lib.rs", PatchType::ReplaceBlock, Some("fn a() {\n    2\n}"), Some("fn a()  {\n  1\n}")));
+        node.add_action(action("src/new.rs", PatchType::CreateFile, Some("new"), None));
+        node.add_action(action("old.rs", PatchType::DeleteFile, None, None));
+
+        let outcome = PatchExecutor::new(dir.path()).apply(&node);
+        assert!(outcome.report.success, "{}", outcome.report.summary());
+        assert_eq!(fs::read_to_string(dir.path().join("lib.rs")).unwrap(), "fn a() {\n    2\n}\n");
+        assert!(!dir.path().join("old.rs").exists());
+
+        outcome.backup.restore().unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("lib.rs")).unwrap(), "fn a() {\n    1\n}\n");
+        assert_eq!(fs::read_to_string(dir.path().join("old.rs")).unwrap(), "old");
+        assert!(!dir.path().join("src").exists());
+    }
+
+    #[test]
+    fn test_failure_rolls_back_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "a").unwrap();
+
+        let actions = [
+            action("a.rs", PatchType::ModifyFile, Some("changed"), None),
+            action("missing.rs", PatchType::ModifyFile, Some("x"), None),
+            action("b.rs", PatchType::CreateFile, Some("b"), None),
+        ];
+        let outcome = PatchExecutor::new(dir.path()).apply_actions(&actions);
+
+        let statuses: Vec<ActionStatus> = outcome.report.actions.iter().map(|a| a.status).collect();
+        assert_eq!(statuses, [ActionStatus::RolledBack, ActionStatus::Failed, ActionStatus::Skipped]);
+        assert_eq!(fs::read_to_string(dir.path().join("a.rs")).unwrap(), "a");
+
+        let escape = PatchExecutor::new(dir.path()).apply_actions(&[action("../x.rs", PatchType::CreateFile, Some(""), None)]);
+        assert!(escape.report.actions[0].error.as_deref().unwrap().contains("escapes"));
+    }
+}
@@ -0,0 +1,387 @@
+//! Human review of AI patches before they touch the tree.
+//!
+//! `PatchReviewer::review` renders a unified diff per file of a `PatchNode`
+//! and announces it with `PatchReviewRequested`: the orchestrator relays it
+//! to the CLI and the Telegram admin chat (as a document when the diff is
+//! large), the dashboard receives it over WS as `PatchReview`.
+//! `PatchReviewDecided` approves all or some actions, rejects the patch, or
+//! edits one action, which re-renders the preview and asks again. Only the
+//! approved actions reach the `PatchExecutor`; the decision is appended to
+//! the approval audit trail.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::ai::approval::{append_audit, ApprovalOutcome, ApprovalRecord, AUDIT_FILE};
+use crate::ai::multi_agent_router::{PatchAction, PatchNode};
+use crate::ai::patch_executor::{transform, PatchExecutor, PatchOutcome};
+use crate::ai::workspace::GitWorkspace;
+use crate::config::schema::ApprovalConfig;
+use crate::event::bus::EventSender;
+use crate::event::event::{AxonEvent, Ingress};
+use crate::util::diff::unified_diff;
+
+/// Tool name recorded in the audit trail for patch reviews
+pub const PATCH_TOOL: &str = "apply_patch";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FileDiff {
+    pub file_path: String,
+    /// Indices into `PatchPreview::actions` that touch this file
+    pub actions: Vec<usize>,
+    /// Unified diff of all those actions; empty when one cannot be applied
+    pub diff: String,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// What a patch would change, computed without writing anything
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PatchPreview {
+    pub node_id: String,
+    pub description: String,
+    pub actions: Vec<PatchAction>,
+    pub files: Vec<FileDiff>,
+}
+
+impl PatchPreview {
+    pub fn new(executor: &PatchExecutor, node: &PatchNode) -> Self {
+        Self::of_actions(executor, &node.id, &node.description, node.flatten())
+    }
+
+    pub fn of_actions(executor: &PatchExecutor, node_id: &str, description: &str, actions: Vec<PatchAction>) -> Self {
+        // Per file: its diff entry, the content on disk and the content so far
+        let mut files: Vec<(FileDiff, Option<String>, Option<String>)> = Vec::new();
+
+        for (index, action) in actions.iter().enumerate() {
+            let pos = match files.iter().position(|(f, ..)| f.file_path == action.file_path) {
+                Some(pos) => pos,
+                None => {
+                    let (original, error) = match executor.current(&action.file_path) {
+                        Ok(content) => (content, None),
+                        Err(e) => (None, Some(format!("{:#}", e))),
+                    };
+                    let file = FileDiff { file_path: action.file_path.clone(), actions: vec![], diff: String::new(), error };
+                    files.push((file, original.clone(), original));
+                    files.len() - 1
+                }
+            };
+
+            let (file, _, current) = &mut files[pos];
+            file.actions.push(index);
+            if file.error.is_none() {
+                match transform(action, current.as_deref()) {
+                    Ok(next) => *current = next,
+                    Err(e) => file.error = Some(format!("action {}: {:#}", index + 1, e)),
+                }
+            }
+        }
+
+        let files = files
+            .into_iter()
+            .map(|(mut file, original, current)| {
+                if file.error.is_none() {
+                    file.diff = unified_diff(&file.file_path, original.as_deref(), current.as_deref());
+                }
+                file
+            })
+            .collect();
+
+        Self { node_id: node_id.to_string(), description: description.to_string(), actions, files }
+    }
+
+    /// Numbered action list, the numbers `/approve <id> 1,3` refers to
+    pub fn summary(&self) -> String {
+        let mut text = format!("Patch {}: {}", self.node_id, self.description);
+        for (i, action) in self.actions.iter().enumerate() {
+            text.push_str(&format!("\n  {}. {:?} {}: {}", i + 1, action.change_type, action.file_path, action.description));
+        }
+        text
+    }
+
+    /// All file diffs, with the reason for files that cannot be patched
+    pub fn diff(&self) -> String {
+        self.files
+            .iter()
+            .map(|f| match &f.error {
+                Some(error) => format!("### {}: cannot apply: {}\n", f.file_path, error),
+                None => f.diff.clone(),
+            })
+            .collect()
+    }
+
+    pub fn render(&self) -> String {
+        format!("{}\n\n{}", self.summary(), self.diff())
+    }
+}
+
+/// Answer to a `PatchReviewRequested`; action indices are 0-based
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ReviewDecision {
+    /// Apply the listed actions, or all of them when `actions` is absent
+    Approve {
+        #[serde(default)]
+        actions: Option<Vec<usize>>,
+    },
+    Reject,
+    /// Replace the content of one action and review again
+    Edit { action: usize, content: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct ReviewResult {
+    pub outcome: ApprovalOutcome,
+    /// The actions as approved, edits included
+    pub actions: Vec<PatchAction>,
+    /// Set when the approved actions were handed to the executor
+    pub applied: Option<PatchOutcome>,
+}
+
+type Waiters = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<(ReviewDecision, Option<Ingress>)>>>>;
+
+/// Forgets a review whose caller gave up
+struct Pending {
+    id: String,
+    waiting: Waiters,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.waiting.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Shows patches to a human and applies what they approve
+pub struct PatchReviewer {
+    tx: EventSender,
+    executor: PatchExecutor,
+    timeout: Duration,
+    audit_file: PathBuf,
+    waiting: Waiters,
+    workspace: Option<Arc<GitWorkspace>>,
+}
+
+impl PatchReviewer {
+    pub fn new(tx: EventSender, executor: PatchExecutor, timeout: Duration) -> Self {
+        Self {
+            tx,
+            executor,
+            timeout,
+            audit_file: PathBuf::from(AUDIT_FILE),
+            waiting: Arc::new(Mutex::new(HashMap::new())),
+            workspace: None,
+        }
+    }
+
+    pub fn from_config(tx: EventSender, root: impl Into<PathBuf>, config: &ApprovalConfig) -> Self {
+        Self::new(tx, PatchExecutor::new(root), Duration::from_secs(config.timeout_seconds))
+    }
+
+    pub fn with_audit_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audit_file = path.into();
+        self
+    }
+
+    /// Commit every patch applied by `review` to the AI branch of `workspace`
+    pub fn with_workspace(mut self, workspace: Arc<GitWorkspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    pub fn executor(&self) -> &PatchExecutor {
+        &self.executor
+    }
+
+    /// Show `node` for review on behalf of `agent`, then apply the
+    /// approved actions. Each edit restarts the timeout.
+    pub async fn review(&self, agent: &str, node: &PatchNode) -> ReviewResult {
+        let result = self.review_with(&self.executor, agent, node).await;
+
+        if let (Some(workspace), Some(applied)) = (&self.workspace, &result.applied) {
+            if applied.report.success {
+                if let Err(e) = workspace.commit(node, &applied.report).await {
+                    tracing::warn!("Patch {} applied but not committed: {:#}", node.id, e);
+                }
+            }
+        }
+        result
+    }
+
+    /// `review` for a patch to files under `executor`'s root
+    pub async fn review_with(&self, executor: &PatchExecutor, agent: &str, node: &PatchNode) -> ReviewResult {
+        let id = Uuid::new_v4().simple().to_string()[..8].to_string();
+        let (decide, mut decisions) = mpsc::unbounded_channel();
+
+        self.waiting.lock().unwrap().insert(id.clone(), decide);
+        let _pending = Pending { id: id.clone(), waiting: self.waiting.clone() };
+
+        let requested_at = Utc::now();
+        let mut actions = node.flatten();
+
+        let (outcome, selected, decided_by) = loop {
+            let preview = PatchPreview::of_actions(executor, &node.id, &node.description, actions.clone());
+            tracing::info!("Patch review {} requested: {} wants {}", id, agent, node.id);
+            let _ = self.tx.send(AxonEvent::PatchReviewRequested {
+                id: id.clone(),
+                agent: agent.to_string(),
+                preview,
+                timeout_seconds: self.timeout.as_secs(),
+            });
+
+            match tokio::time::timeout(self.timeout, decisions.recv()).await {
+                Ok(Some((ReviewDecision::Approve { actions: selected }, by))) => {
+                    let mut selected = selected.unwrap_or_else(|| (0..actions.len()).collect());
+                    selected.retain(|&i| i < actions.len());
+                    selected.sort_unstable();
+                    selected.dedup();
+                    break (ApprovalOutcome::Approved, selected, by);
+                }
+                Ok(Some((ReviewDecision::Reject, by))) => break (ApprovalOutcome::Denied, vec![], by),
+                Ok(Some((ReviewDecision::Edit { action, content }, _))) => match actions.get_mut(action) {
+                    Some(edited) => edited.content = Some(content),
+                    None => tracing::warn!("Patch review {} has no action {}", id, action + 1),
+                },
+                _ => break (ApprovalOutcome::TimedOut, vec![], None),
+            }
+        };
+
+        tracing::info!("Patch review {}: {:?} ({} of {} actions)", id, outcome, selected.len(), actions.len());
+        let _ = self.tx.send(AxonEvent::ApprovalResolved { id: id.clone(), outcome });
+
+        let record = ApprovalRecord {
+            id,
+            agent: agent.to_string(),
+            tool: PATCH_TOOL.to_string(),
+            args: json!({ "node_id": node.id, "actions": selected }),
+            reason: node.description.clone(),
+            requested_at,
+            decided_at: Utc::now(),
+            outcome,
+            decided_by,
+        };
+        if let Err(e) = append_audit(&self.audit_file, &record) {
+            tracing::warn!("Patch review audit not written: {:#}", e);
+        }
+
+        let actions: Vec<PatchAction> = selected.iter().map(|&i| actions[i].clone()).collect();
+        let applied = (outcome == ApprovalOutcome::Approved).then(|| executor.apply_actions(&actions));
+
+        ReviewResult { outcome, actions, applied }
+    }
+
+    /// Pass `decision` made by `by` to review `id`; false when it is
+    /// unknown or settled
+    pub fn resolve(&self, id: &str, decision: ReviewDecision, by: Option<Ingress>) -> bool {
+        let mut waiting = self.waiting.lock().unwrap();
+
+        let delivered = waiting.get(id).is_some_and(|decide| decide.send((decision.clone(), by)).is_ok());
+        if !matches!(decision, ReviewDecision::Edit { .. }) {
+            waiting.remove(id);
+        }
+        delivered
+    }
+
+    /// Ids of the reviews still waiting
+    pub fn pending(&self) -> Vec<String> {
+        self.waiting.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Settle reviews from `PatchReviewDecided` on the bus
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let mut rx = self.tx.subscribe();
+
+        loop {
+            match rx.recv().await {
+                Ok(AxonEvent::PatchReviewDecided { id, decision, by }) => {
+                    if !self.resolve(&id, decision, by) {
+                        tracing::warn!("No pending patch review {}", id);
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Patch reviewer lagged, skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::multi_agent_router::PatchType;
+
+    fn modify(path: &str, content: &str) -> PatchAction {
+        PatchAction {
+            file_path: path.into(),
+            description: format!("rewrite {}", path),
+            change_type: PatchType::ModifyFile,
+            content: Some(content.into()),
+            search: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_edit_then_partial_approval() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "fn b() {}\n").unwrap();
+
+        let (tx, mut rx) = tokio::sync::broadcast::channel(16);
+        let reviewer = Arc::new(
+            PatchReviewer::new(tx.clone(), PatchExecutor::new(dir.path()), Duration::from_secs(5))
+                .with_audit_file(dir.path().join("audit.jsonl")),
+        );
+        tokio::spawn(reviewer.clone().run());
+
+        // Edit the first action, then approve only it once the new diff shows
+        let reviewed = tokio::spawn(async move {
+            let mut previews = Vec::new();
+            while let Ok(event) = rx.recv().await {
+                if let AxonEvent::PatchReviewRequested { id, preview, .. } = event {
+                    let decision = if previews.is_empty() {
+                        ReviewDecision::Edit { action: 0, content: "fn a() { edited() }\n".into() }
+                    } else {
+                        ReviewDecision::Approve { actions: Some(vec![0]) }
+                    };
+                    previews.push(preview);
+                    let _ = tx.send(AxonEvent::PatchReviewDecided { id, decision, by: Some(Ingress::Cli) });
+                    if previews.len() == 2 {
+                        return previews;
+                    }
+                }
+            }
+            previews
+        });
+
+        let mut node = PatchNode::new("n1", "touch both");
+        node.add_action(modify("a.rs", "fn a() { 1 }\n"));
+        node.add_action(modify("b.rs", "fn b() { 2 }\n"));
+
+        let result = reviewer.review("coder", &node).await;
+        assert_eq!(result.outcome, ApprovalOutcome::Approved);
+        assert!(result.applied.unwrap().report.success);
+
+        let previews = reviewed.await.unwrap();
+        assert!(previews[0].files[0].diff.contains("+fn a() { 1 }"));
+        assert!(previews[1].files[0].diff.contains("+fn a() { edited() }"));
+        assert_eq!(previews[1].files[1].actions, [1]);
+
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.rs")).unwrap(), "fn a() { edited() }\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("b.rs")).unwrap(), "fn b() {}\n");
+        assert!(reviewer.pending().is_empty());
+    }
+}
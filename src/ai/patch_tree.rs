@@ -6,46 +6,147 @@ use tokio::sync::mpsc;
 use crate::core::state::AppState;
 use crate::event::bus::EventSender;
 use crate::event::event::{AxonEvent, WorkerHealth};
-use crate::ai::ollama::OllamaProvider;
+use chrono::Utc;
+
+use crate::ai::chat::ChatSession;
+use crate::ai::memory::eval_store::{EvalRecord, EvalStore};
+use crate::ai::model_router::{AiTaskType, ModelRouter, RoutingDecision};
+use crate::ai::provider::OllamaProvider;
+use crate::ai::planner::PlanExecutor;
+use crate::ai::repair_loop::RepairLoop;
+use crate::ai::self_reflection::MultiAgentRouter;
 
 pub async fn run(
     tx: EventSender,
     state: Arc<AppState>,
+    agents: Arc<MultiAgentRouter<OllamaProvider>>,
+    plans: Arc<PlanExecutor<OllamaProvider>>,
+    repairs: Arc<RepairLoop<OllamaProvider>>,
     mut rx: mpsc::Receiver<AxonEvent>,
 ) -> Result<()> {
 
     // Register worker
     state.update_worker("ai_bridge", WorkerHealth::Running).await;
 
-    let provider = OllamaProvider::new();
+    let router = ModelRouter::from_config(&state.config.ai);
+    let evals = EvalStore::new();
+
+    info!("AI Bridge ACTIVE ({} agents)", agents.agents().await.len());
 
-    info!("AI Bridge ACTIVE (Ollama)");
+    // Pick up plans interrupted by a restart
+    tokio::spawn({
+        let plans = plans.clone();
+        async move {
+            match plans.resume_incomplete().await {
+                Ok(0) => {}
+                Ok(n) => info!("Resumed {} unfinished plans", n),
+                Err(e) => warn!("Plan resume failed: {:#}", e),
+            }
+        }
+    });
 
     while let Some(event) = rx.recv().await {
 
-        if let AxonEvent::AiRequest { prompt, id, model, context } = event {
+        if let AxonEvent::PlanRequested { id, goal } = event {
+            let plans = plans.clone();
+            tokio::spawn(async move {
+                if let Err(e) = plans.run(id, &goal).await {
+                    warn!("Plan [{}] failed: {:#}", id, e);
+                }
+            });
+            continue;
+        }
+
+        if let AxonEvent::RepairRequested { id, project } = event {
+            let repairs = repairs.clone();
+            tokio::spawn(async move {
+                if let Err(e) = repairs.run(id, &project).await {
+                    warn!("Repair [{}] failed: {:#}", id, e);
+                }
+            });
+            continue;
+        }
 
-            info!("Processing AI request [{}]", id);
+        if let AxonEvent::AiRequest { prompt, id, model, context, task, reflect } = event {
 
-            let response = match provider.generate(&prompt).await {
-                Ok(res) => res,
+            let task = task.unwrap_or_else(|| AiTaskType::infer(&prompt));
+            let selection = agents.choose(&prompt, Some(task)).await;
+            let agent = selection.agent;
+
+            // An explicitly requested model overrides the agent's own
+            let (agent, decision) = match model.as_deref().filter(|m| !m.trim().is_empty()) {
+                Some(requested) => {
+                    let decision = router.route(task, Some(requested));
+                    (Arc::new(agent.with_model(&decision.model, decision.max_tokens)), decision)
+                }
+                None => {
+                    let decision = RoutingDecision {
+                        task,
+                        model: agent.model_name.clone(),
+                        max_tokens: agent.max_tokens,
+                        reason: format!("{:?} task handled by agent '{}'", task, agent.name),
+                        agent: None,
+                        agent_reason: None,
+                        agent_confidence: None,
+                    };
+                    (agent, decision)
+                }
+            };
+            let decision = RoutingDecision {
+                agent: Some(agent.name.clone()),
+                agent_reason: Some(selection.reason),
+                agent_confidence: Some(selection.confidence),
+                ..decision
+            };
+
+            info!(
+                "Processing AI request [{}] -> {} ({})",
+                id, decision.model, decision.reason
+            );
+
+            let rag_context = agents.retrieve(&prompt).await;
+            let outcome = agents
+                .execute_reflective(id, agent, &prompt, &ChatSession::new(), &rag_context, reflect)
+                .await;
+            let (response, failed) = match outcome.result {
+                Ok(output) => (output, false),
                 Err(e) => {
                     warn!("Ollama error: {}", e);
-                    format!("AI error: {}", e)
+                    (format!("AI error: {}", e), true)
                 }
             };
 
+            let record = EvalRecord {
+                id,
+                created_at: Utc::now(),
+                agent: decision.agent.clone(),
+                model: decision.model.clone(),
+                task,
+                prompt: prompt.clone(),
+                response: response.clone(),
+                confidence: outcome.confidence,
+                scores: outcome.scores.clone(),
+                attempts: outcome.attempts,
+                error: failed,
+                feedback: vec![],
+            };
+            if let Err(e) = evals.save(&record) {
+                warn!("Eval record not saved: {:#}", e);
+            }
+
             let _ = tx.send(AxonEvent::AiResponse {
     request_id: id,
     output: response.clone(),
-    model: "qwen2.5:7b".to_string(),
-    context: None,
-    response: response,
+    model: decision.model.clone(),
+    context,
+    response,
+    routing: Some(decision),
+    trace: Some(Box::new(outcome.trace)),
+    confidence: outcome.confidence,
+    attempts: Some(outcome.attempts),
 });
         }
     }
 
     Ok(())
 }
-
-
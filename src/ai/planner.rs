@@ -0,0 +1,424 @@
+//! Planner-to-executor pipeline.
+//!
+//! The Planner agent turns a goal into a structured step list, which is
+//! persisted as a `JobRecord`. The executor runs each step through an agent
+//! or a registered tool, checkpoints after every step and can pick up
+//! unfinished plans after a restart.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::ai::memory::conversation_embeddings::{JobRecord, JobStatus, JobStep, JobStore};
+use crate::ai::provider::LlmProvider;
+use crate::ai::self_reflection::{AiAgent, MultiAgentRouter};
+use crate::ai::structured::parse_as;
+use crate::event::bus::EventSender;
+use crate::event::event::AxonEvent;
+
+/// `JobRecord::job_type` for plans
+pub const PLAN_JOB: &str = "plan";
+
+/// Name of the agent asked to produce plans
+const PLANNER_AGENT: &str = "planner";
+
+/// How much of each earlier step's output is fed to later steps
+const PRIOR_OUTPUT_CHARS: usize = 600;
+
+/// Async tool callable from a plan step; receives the step input
+pub type ToolHandler = Arc<dyn Fn(String) -> BoxFuture<'static, Result<String>> + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PlanStep {
+    pub description: String,
+    #[serde(default)]
+    pub agent: Option<String>,
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub input: Option<String>,
+}
+
+impl From<PlanStep> for JobStep {
+    fn from(step: PlanStep) -> Self {
+        JobStep {
+            description: step.description,
+            agent: step.agent,
+            tool: step.tool,
+            input: step.input,
+            status: JobStatus::Pending,
+            output: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct PlanDocument {
+    steps: Vec<PlanStep>,
+}
+
+/// Steps from a planner answer: a JSON `{"steps": [...]}` object or array,
+/// or failing that a numbered / bulleted list.
+pub fn parse_steps(text: &str) -> Result<Vec<PlanStep>> {
+    let json_steps = parse_as::<PlanDocument>(text)
+        .map(|doc| doc.steps)
+        .or_else(|_| parse_as::<Vec<PlanStep>>(text))
+        .ok();
+
+    let steps = match json_steps {
+        Some(steps) => steps,
+        None => text
+            .lines()
+            .filter_map(list_item)
+            .map(|description| PlanStep { description, agent: None, tool: None, input: None })
+            .collect(),
+    };
+
+    let steps: Vec<PlanStep> = steps
+        .into_iter()
+        .filter(|s| !s.description.trim().is_empty())
+        .collect();
+
+    if steps.is_empty() {
+        bail!("Planner returned no steps");
+    }
+
+    Ok(steps)
+}
+
+/// "1. do x", "2) do y", "- do z" -> the item text
+fn list_item(line: &str) -> Option<String> {
+    let line = line.trim();
+
+    let rest = if let Some(rest) = line.strip_prefix(['-', '*']) {
+        rest
+    } else {
+        let digits = line.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        line[digits..].strip_prefix(['.', ')'])?
+    };
+
+    let item = rest.trim();
+    (!item.is_empty()).then(|| item.to_string())
+}
+
+/// Plans goals with the Planner agent and executes them step by step
+pub struct PlanExecutor<P: LlmProvider> {
+    agents: Arc<MultiAgentRouter<P>>,
+    store: JobStore,
+    tx: EventSender,
+    tools: HashMap<String, ToolHandler>,
+}
+
+impl<P: LlmProvider> PlanExecutor<P> {
+    pub fn new(agents: Arc<MultiAgentRouter<P>>, store: JobStore, tx: EventSender) -> Self {
+        Self {
+            agents,
+            store,
+            tx,
+            tools: HashMap::new(),
+        }
+    }
+
+    /// Make a tool available to plan steps
+    pub fn with_tool(mut self, name: &str, handler: ToolHandler) -> Self {
+        self.tools.insert(name.to_string(), handler);
+        self
+    }
+
+    /// Ask the Planner for steps and persist them as a pending job
+    pub async fn plan(&self, id: Uuid, goal: &str) -> Result<JobRecord> {
+        let planner = match self.agents.get(PLANNER_AGENT).await {
+            Some(agent) => agent,
+            None => self.agents.select(goal, None).await,
+        };
+
+        let output = planner.execute(&self.planning_prompt(goal).await).await?;
+        let steps = parse_steps(&output).context("Could not read the plan")?;
+
+        info!("Plan [{}] has {} steps", id, steps.len());
+
+        self.store.create_with_id(
+            id,
+            PLAN_JOB,
+            Some(goal.to_string()),
+            steps.into_iter().map(JobStep::from).collect(),
+        )
+    }
+
+    async fn planning_prompt(&self, goal: &str) -> String {
+        let agents = self
+            .agents
+            .agents()
+            .await
+            .iter()
+            .map(|a| format!("- {}: {}", a.name, a.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut tools: Vec<&str> = self.tools.keys().map(String::as_str).collect();
+        tools.sort();
+        let tools = if tools.is_empty() { "none".to_string() } else { tools.join(", ") };
+
+        format!(
+            "Break the goal into a short list of concrete steps.\n\n\
+             Goal:\n{}\n\n\
+             Agents:\n{}\n\n\
+             Tools: {}\n\n\
+             Each step is handled by one agent, or by one tool with an input.\n\
+             Respond strictly in JSON:\n\
+             {{\"steps\":[{{\"description\":\"...\",\"agent\":\"coder\"}},{{\"description\":\"...\",\"tool\":\"shell\",\"input\":\"cargo check\"}}]}}",
+            goal, agents, tools
+        )
+    }
+
+    /// Plan and execute; always ends with a `JobFinished` event
+    pub async fn run(&self, id: Uuid, goal: &str) -> Result<JobRecord> {
+        match self.plan(id, goal).await {
+            Ok(job) => self.execute(job).await,
+            Err(e) => {
+                self.finish(id, false, format!("Planning failed: {:#}", e));
+                Err(e)
+            }
+        }
+    }
+
+    /// Run every step that has not completed yet, checkpointing after each
+    pub async fn execute(&self, mut job: JobRecord) -> Result<JobRecord> {
+        let goal = job.payload.clone().unwrap_or_default();
+        let total = job.steps.len();
+
+        self.store.update_status(&mut job, JobStatus::Running)?;
+
+        while let Some(index) = job.next_step() {
+            let step = job.steps[index].clone();
+
+            job.steps[index].status = JobStatus::Running;
+            self.store.save(&job)?;
+            self.progress(&job, index, JobStatus::Running);
+
+            match self.run_step(&goal, &job, &step).await {
+                Ok(output) => {
+                    self.store.complete_step(&mut job, index, JobStatus::Completed, output)?;
+                    self.progress(&job, index, JobStatus::Completed);
+                }
+                Err(e) => {
+                    warn!("Plan [{}] step {}/{} failed: {:#}", job.id, index + 1, total, e);
+
+                    self.store.complete_step(&mut job, index, JobStatus::Failed, format!("{:#}", e))?;
+                    self.store.update_status(&mut job, JobStatus::Failed)?;
+                    self.progress(&job, index, JobStatus::Failed);
+                    self.finish(job.id, false, summary(&job));
+                    return Ok(job);
+                }
+            }
+        }
+
+        self.store.update_status(&mut job, JobStatus::Completed)?;
+        self.finish(job.id, true, summary(&job));
+
+        Ok(job)
+    }
+
+    /// Continue plans that were pending or running when the engine stopped;
+    /// a plan that fails is logged and the others still run
+    pub async fn resume_incomplete(&self) -> Result<usize> {
+        let jobs: Vec<JobRecord> = self
+            .store
+            .load_incomplete()?
+            .into_iter()
+            .filter(|j| j.job_type == PLAN_JOB)
+            .collect();
+
+        let count = jobs.len();
+
+        for job in jobs {
+            info!(
+                "Resuming plan [{}] after step {}",
+                job.id,
+                job.last_step.as_deref().unwrap_or("none")
+            );
+            let id = job.id;
+            if let Err(e) = self.execute(job).await {
+                warn!("Resumed plan [{}] failed: {:#}", id, e);
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn run_step(&self, goal: &str, job: &JobRecord, step: &JobStep) -> Result<String> {
+        if let Some(tool) = &step.tool {
+            let handler = self
+                .tools
+                .get(tool)
+                .with_context(|| format!("Unknown tool '{}'", tool))?;
+
+            let input = step.input.clone().unwrap_or_else(|| step.description.clone());
+            return handler(input).await;
+        }
+
+        let agent: Arc<AiAgent<P>> = match step.agent.as_deref() {
+            Some(name) => match self.agents.get(name).await {
+                Some(agent) => agent,
+                None => self.agents.select(&step.description, None).await,
+            },
+            None => self.agents.select(&step.description, None).await,
+        };
+
+        agent.execute(&step_prompt(goal, job, step)).await
+    }
+
+    fn progress(&self, job: &JobRecord, index: usize, status: JobStatus) {
+        let _ = self.tx.send(AxonEvent::JobProgress {
+            job_id: job.id,
+            step: index + 1,
+            total: job.steps.len(),
+            description: job.steps[index].description.clone(),
+            status,
+        });
+    }
+
+    fn finish(&self, job_id: Uuid, success: bool, summary: String) {
+        let _ = self.tx.send(AxonEvent::JobFinished { job_id, success, summary });
+    }
+}
+
+/// Prompt for one step, with the goal and what earlier steps produced
+fn step_prompt(goal: &str, job: &JobRecord, step: &JobStep) -> String {
+    let done = job
+        .steps
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.status == JobStatus::Completed)
+        .map(|(i, s)| {
+            let output: String = s.output.as_deref().unwrap_or("").chars().take(PRIOR_OUTPUT_CHARS).collect();
+            format!("{}. {}\n{}", i + 1, s.description, output.trim())
+        })
+        .collect::<Vec<_>>();
+
+    let mut prompt = format!("Overall goal: {}\n", goal);
+    if !done.is_empty() {
+        prompt.push_str(&format!("\nCompleted steps:\n{}\n", done.join("\n\n")));
+    }
+    prompt.push_str(&format!("\nCurrent step: {}", step.description));
+    prompt
+}
+
+/// Human-readable result of a finished or failed plan
+fn summary(job: &JobRecord) -> String {
+    let done = job.steps.iter().filter(|s| s.status == JobStatus::Completed).count();
+    let mut text = format!(
+        "Plan {:?}: {}/{} steps completed",
+        job.status,
+        done,
+        job.steps.len()
+    );
+
+    for (i, step) in job.steps.iter().enumerate() {
+        text.push_str(&format!("\n{}. [{:?}] {}", i + 1, step.status, step.description));
+    }
+
+    let last = job
+        .steps
+        .iter()
+        .rev()
+        .find(|s| matches!(s.status, JobStatus::Completed | JobStatus::Failed));
+    if let Some(output) = last.and_then(|s| s.output.as_deref()) {
+        text.push_str(&format!("\n\n{}", output.trim()));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::ModelRegistry;
+    use crate::ai::test_provider::ScriptedProvider;
+    use crate::config::schema::AiConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::broadcast;
+
+    /// Answers the planning prompt with a fixed plan and every step with "done N"
+    fn planner(plan: &str) -> ScriptedProvider {
+        let plan = plan.to_string();
+        let steps = AtomicUsize::new(0);
+
+        ScriptedProvider::new(move |prompt| {
+            if prompt.contains("Break the goal") {
+                plan.clone()
+            } else {
+                format!("done {}", steps.fetch_add(1, Ordering::SeqCst))
+            }
+        })
+    }
+
+    fn executor(dir: &std::path::Path, plan: &str) -> (PlanExecutor<ScriptedProvider>, Arc<ScriptedProvider>) {
+        let provider = Arc::new(planner(plan));
+        let agents = MultiAgentRouter::new(provider.clone(), ModelRegistry::from_ai_config(&AiConfig::default()));
+        let (tx, _) = broadcast::channel(64);
+
+        let echo: ToolHandler = Arc::new(|input| Box::pin(async move { Ok(format!("ran {}", input)) }));
+        let executor = PlanExecutor::new(Arc::new(agents), JobStore::in_dir(dir), tx).with_tool("shell", echo);
+
+        (executor, provider)
+    }
+
+    #[test]
+    fn test_parse_json_and_list_plans() {
+        let json = "Sure:\n```json\n{\"steps\":[{\"description\":\"write it\",\"agent\":\"coder\"},{\"description\":\"check\",\"tool\":\"shell\",\"input\":\"cargo check\"}]}\n```";
+        let steps = parse_steps(json).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].agent.as_deref(), Some("coder"));
+        assert_eq!(steps[1].input.as_deref(), Some("cargo check"));
+
+        let list = parse_steps("Plan:\n1. Read the logs\n2) Fix the bug\n- Rebuild").unwrap();
+        let descriptions: Vec<&str> = list.iter().map(|s| s.description.as_str()).collect();
+        assert_eq!(descriptions, ["Read the logs", "Fix the bug", "Rebuild"]);
+
+        assert!(parse_steps("no idea").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_plan_runs_agents_and_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = r#"{"steps":[{"description":"write the fix","agent":"coder"},{"description":"verify","tool":"shell","input":"cargo check"}]}"#;
+        let (executor, _) = executor(dir.path(), plan);
+
+        let job = executor.run(Uuid::new_v4(), "fix the build").await.unwrap();
+
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.last_step.as_deref(), Some("1"));
+        assert_eq!(job.steps[1].output.as_deref(), Some("ran cargo check"));
+        assert_eq!(executor.store.load(job.id).unwrap().status, JobStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_resume_skips_completed_steps() {
+        let dir = tempfile::tempdir().unwrap();
+        let (executor, provider) = executor(dir.path(), "1. first\n2. second\n3. third");
+
+        // Simulate a crash after the first step
+        let mut job = executor.plan(Uuid::new_v4(), "three things").await.unwrap();
+        executor.store.complete_step(&mut job, 0, JobStatus::Completed, "earlier run".into()).unwrap();
+        job.steps[1].status = JobStatus::Running;
+        executor.store.update_status(&mut job, JobStatus::Running).unwrap();
+
+        assert_eq!(executor.resume_incomplete().await.unwrap(), 1);
+
+        let job = executor.store.load(job.id).unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.steps[0].output.as_deref(), Some("earlier run"));
+        assert_eq!(provider.prompts().iter().filter(|p| !p.contains("Break the goal")).count(), 2);
+        assert!(executor.store.load_incomplete().unwrap().is_empty());
+    }
+}
@@ -1,66 +1,220 @@
-﻿use serde::{Deserialize};
-use serde_json::Value;
+﻿use crate::ai::chat::ChatRole;
 
-/// Format standard pentru tool call generat de AI
-///
-/// Modelul trebuie sÄƒ genereze ceva de genul:
+/// Rough token estimate (~4 characters per token for English and code).
+/// Good enough for budgeting; the model enforces the real limit.
+pub fn estimate_tokens(text: &str) -> u32 {
+    (text.chars().count() as u32).div_ceil(4)
+}
+
+/// Assembles the final prompt from system prompt, retrieved context,
+/// conversation history and the user turn, within a token budget.
 ///
-/// ```json
-/// {
-///   "tool": "run_shell",
-///   "args": {
-///       "command": "cargo build"
-///   }
-/// }
-/// ```
-#[derive(Debug, Clone, Deserialize)]
-pub struct ToolCall {
-    pub tool: String,
-    pub args: Value,
+/// System prompt and user turn are always kept. Remaining budget goes to
+/// RAG context (in ranked order), then history (most recent first).
+#[derive(Debug, Clone, Default)]
+pub struct PromptBuilder {
+    buf: String,
+    system: Option<String>,
+    context: Vec<String>,
+    history: Vec<(ChatRole, String)>,
+    user: Option<String>,
+    budget: Option<u32>,
 }
 
-/// Detect JSON tool call inside AI output.
-/// Returns Some(ToolCall) if valid JSON tool detected.
-pub fn detect_tool_call(output: &str) -> Option<ToolCall> {
+impl PromptBuilder {
+    pub fn new() -> Self { Self::default() }
 
-    // 1ï¸âƒ£ CautÄƒ primul bloc JSON Ã®n text
-    let start = output.find('{')?;
-    let end = output.rfind('}')?;
+    /// Free-form line, emitted before the structured sections
+    pub fn push_line(mut self, s: impl AsRef<str>) -> Self {
+        self.buf.push_str(s.as_ref());
+        self.buf.push('\n');
+        self
+    }
 
-    if end <= start {
-        return None;
+    pub fn system(mut self, prompt: impl Into<String>) -> Self {
+        let prompt = prompt.into();
+        if !prompt.trim().is_empty() {
+            self.system = Some(prompt);
+        }
+        self
     }
 
-    let json_str = &output[start..=end];
+    /// Retrieved chunks, best match first
+    pub fn context(mut self, chunks: impl IntoIterator<Item = String>) -> Self {
+        self.context.extend(chunks);
+        self
+    }
 
-    // 2ï¸âƒ£ ÃŽncearcÄƒ sÄƒ parseze JSON
-    let parsed: Value = serde_json::from_str(json_str).ok()?;
+    /// Earlier turns, oldest first
+    pub fn history(mut self, turns: &[(ChatRole, String)]) -> Self {
+        self.history.extend(turns.iter().cloned());
+        self
+    }
+
+    pub fn user(mut self, input: impl Into<String>) -> Self {
+        self.user = Some(input.into());
+        self
+    }
 
-    // 3ï¸âƒ£ VerificÄƒ dacÄƒ are structura tool call
-    if parsed.get("tool").is_some() && parsed.get("args").is_some() {
-        serde_json::from_value(parsed).ok()
-    } else {
-        None
+    /// Maximum prompt size in estimated tokens
+    pub fn token_budget(mut self, tokens: u32) -> Self {
+        self.budget = Some(tokens);
+        self
+    }
+
+    /// The sections that fit the budget, for chat APIs that take them as
+    /// separate messages
+    pub fn fit(self) -> PromptParts {
+        let system = self.system.map(|s| s.trim().to_string());
+        let user = self.user.map(|u| u.trim().to_string());
+
+        let fixed = estimate_tokens(&self.buf)
+            + system.as_deref().map_or(0, |s| estimate_tokens(&format!("### System\n{}\n\n", s)))
+            + user.as_deref().map_or(0, |u| estimate_tokens(&format!("### User\n{}\n\n### Assistant\n", u)));
+        let mut remaining = self.budget.map(|b| b.saturating_sub(fixed));
+
+        let mut take = |text: &str| match remaining.as_mut() {
+            None => true,
+            Some(left) => {
+                let cost = estimate_tokens(text);
+                if cost <= *left {
+                    *left -= cost;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        let context: Vec<String> = self.context
+            .iter()
+            .enumerate()
+            .take_while(|(i, chunk)| take(&context_entry(*i, chunk)))
+            .map(|(_, chunk)| chunk.trim().to_string())
+            .collect();
+
+        let mut history: Vec<(ChatRole, String)> = self.history
+            .into_iter()
+            .rev()
+            .take_while(|(role, text)| take(&history_entry(role, text)))
+            .map(|(role, text)| (role, text.trim().to_string()))
+            .collect();
+        history.reverse();
+
+        PromptParts { lines: self.buf, system, context, history, user }
+    }
+
+    pub fn build(self) -> String {
+        self.fit().render()
     }
 }
 
-// === AXON_COMPAT: PromptBuilder stub ===
-/// Minimal compatibility PromptBuilder.
-/// Replace with your real prompt composition logic.
+/// What `PromptBuilder::fit` kept, trimmed
 #[derive(Debug, Clone, Default)]
-pub struct PromptBuilder {
-    buf: String,
+pub struct PromptParts {
+    /// Free-form lines from `push_line`
+    pub lines: String,
+    pub system: Option<String>,
+    pub context: Vec<String>,
+    pub history: Vec<(ChatRole, String)>,
+    pub user: Option<String>,
 }
 
-impl PromptBuilder {
-    pub fn new() -> Self { Self { buf: String::new() } }
+impl PromptParts {
+    /// The user turn with the retrieved context in front of it
+    pub fn request(&self) -> String {
+        let mut out = context_section(&self.context);
+        out.push_str(self.user.as_deref().unwrap_or_default());
+        out
+    }
 
-    pub fn push_line(mut self, s: impl AsRef<str>) -> Self {
-        self.buf.push_str(s.as_ref());
-        self.buf.push('\n');
-        self
+    /// Everything as one prompt, for models without a chat API
+    pub fn render(self) -> String {
+        let mut out = self.lines;
+
+        if let Some(system) = &self.system {
+            out.push_str(&format!("### System\n{}\n\n", system));
+        }
+
+        out.push_str(&context_section(&self.context));
+
+        if !self.history.is_empty() {
+            out.push_str("### Conversation\n");
+            out.extend(self.history.iter().map(|(role, text)| history_entry(role, text)));
+            out.push('\n');
+        }
+
+        if let Some(user) = &self.user {
+            out.push_str(&format!("### User\n{}\n\n### Assistant\n", user));
+        }
+
+        out
     }
+}
 
-    pub fn build(self) -> String { self.buf }
+fn context_entry(i: usize, chunk: &str) -> String {
+    format!("[{}] {}\n", i + 1, chunk.trim())
 }
 
+fn context_section(chunks: &[String]) -> String {
+    if chunks.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("### Context\n");
+    out.extend(chunks.iter().enumerate().map(|(i, chunk)| context_entry(i, chunk)));
+    out.push('\n');
+    out
+}
+
+fn history_entry(role: &ChatRole, text: &str) -> String {
+    format!("{}: {}\n", role_label(role), text.trim())
+}
+
+fn role_label(role: &ChatRole) -> &'static str {
+    match role {
+        ChatRole::User => "User",
+        ChatRole::Assistant => "Assistant",
+        ChatRole::System => "System",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_in_order() {
+        let prompt = PromptBuilder::new()
+            .system("You are terse.")
+            .context(vec!["fn main() {}".to_string()])
+            .history(&[(ChatRole::User, "hi".into()), (ChatRole::Assistant, "hello".into())])
+            .user("what now?")
+            .build();
+
+        let system = prompt.find("You are terse.").unwrap();
+        let context = prompt.find("[1] fn main() {}").unwrap();
+        let history = prompt.find("Assistant: hello").unwrap();
+        let user = prompt.find("what now?").unwrap();
+
+        assert!(system < context && context < history && history < user);
+        assert!(prompt.ends_with("### Assistant\n"));
+    }
+
+    #[test]
+    fn test_budget_drops_oldest_history_first() {
+        let old = "a".repeat(400);
+        let recent = "b".repeat(40);
+
+        let prompt = PromptBuilder::new()
+            .system("sys")
+            .history(&[(ChatRole::User, old.clone()), (ChatRole::User, recent.clone())])
+            .user("question")
+            .token_budget(60)
+            .build();
+
+        assert!(prompt.contains(&recent));
+        assert!(!prompt.contains(&old));
+        assert!(prompt.contains("question"));
+    }
+}
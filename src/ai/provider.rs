@@ -1,10 +1,17 @@
-﻿use anyhow::{Context, Result};
+﻿use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Mutex;
 use std::time::Duration;
 
+use crate::ai::chat::ChatRole;
+use crate::ai::prompt_builder::PromptBuilder;
+use crate::ai::tool_json_detector::ToolCall;
+use crate::config::schema::AiConfig;
+
 // --- DefiniÈ›ii necesare pentru fuziune ---
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -12,14 +19,189 @@ pub struct LlmResponse {
     pub output: String,
     pub model: String,
     pub tokens_used: Option<u32>,
+    /// Native tool calls returned by the model (`generate_with_tools`)
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// A tool offered to the model natively: name, description and the JSON
+/// Schema of its arguments
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+impl ToolSpec {
+    /// Ollama / OpenAI `tools` entry
+    pub fn to_ollama(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters,
+            }
+        })
+    }
+}
+
+/// One message of a tool conversation: the prompt, each assistant reply
+/// with the calls it made, and each tool result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    /// Calls made by an assistant message
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// Tool that produced a `tool` message
+    #[serde(default)]
+    pub tool_name: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: "system".into(), content: content.into(), tool_calls: vec![], tool_name: None }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: "user".into(), content: content.into(), tool_calls: vec![], tool_name: None }
+    }
+
+    pub fn assistant(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Self { role: "assistant".into(), content: content.into(), tool_calls, tool_name: None }
+    }
+
+    pub fn tool(name: &str, content: impl Into<String>) -> Self {
+        Self { role: "tool".into(), content: content.into(), tool_calls: vec![], tool_name: Some(name.to_string()) }
+    }
+
+    fn is_tool(&self) -> bool {
+        self.role == "tool"
+    }
+
+    /// Ollama `/api/chat` message
+    pub fn to_ollama(&self) -> Value {
+        let mut message = json!({ "role": self.role, "content": self.content });
+
+        if !self.tool_calls.is_empty() {
+            message["tool_calls"] = self
+                .tool_calls
+                .iter()
+                .map(|call| json!({ "function": { "name": call.tool, "arguments": call.args } }))
+                .collect();
+        }
+        if let Some(name) = &self.tool_name {
+            message["tool_name"] = json!(name);
+        }
+
+        message
+    }
+}
+
+/// Single prompt for models without native tools. System prompt and
+/// earlier turns come first in `PromptBuilder` layout, then the request and
+/// the tool results in order. Assistant turns that called tools are left
+/// out; their calls are answered by the results that follow them.
+pub fn render_messages(messages: &[ChatMessage]) -> String {
+    // The request is the last user turn before the first tool call
+    let first_call = messages
+        .iter()
+        .position(|m| m.is_tool() || !m.tool_calls.is_empty())
+        .unwrap_or(messages.len());
+    let request = messages[..first_call].iter().rposition(|m| m.role == "user");
+
+    let mut parts = Vec::new();
+    let mut rest = 0;
+
+    if let Some(at) = request.filter(|at| *at > 0) {
+        let mut builder = PromptBuilder::new();
+        let mut history = Vec::new();
+        for message in &messages[..at] {
+            match message.role.as_str() {
+                "system" => builder = builder.system(message.content.as_str()),
+                "assistant" => history.push((ChatRole::Assistant, message.content.clone())),
+                _ => history.push((ChatRole::User, message.content.clone())),
+            }
+        }
+
+        parts.push(builder.history(&history).user(messages[at].content.as_str()).build());
+        rest = at + 1;
+    }
+
+    for (i, message) in messages.iter().enumerate().skip(rest) {
+        if message.role == "assistant" {
+            continue;
+        }
+        parts.push(message.content.clone());
+
+        if message.is_tool() && !messages.get(i + 1).is_some_and(ChatMessage::is_tool) {
+            parts.push("Continue with the original request.".into());
+        }
+    }
+
+    parts.join("\n\n")
 }
 
 // Definim tipul pentru callback-ul de streaming
 pub type StreamCallback = Box<dyn Fn(String) + Send + Sync>;
 
+/// Sampling options passed through to the model (`[agents.options]`)
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct GenerationOptions {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f32>,
+    pub seed: Option<i64>,
+    pub stop: Vec<String>,
+}
+
+impl GenerationOptions {
+    /// Ollama `options` object, including the completion limit
+    pub fn to_ollama(&self, max_tokens: u32) -> serde_json::Value {
+        let mut options = json!({ "num_predict": max_tokens });
+
+        if let Some(t) = self.temperature { options["temperature"] = json!(t); }
+        if let Some(p) = self.top_p { options["top_p"] = json!(p); }
+        if let Some(k) = self.top_k { options["top_k"] = json!(k); }
+        if let Some(r) = self.repeat_penalty { options["repeat_penalty"] = json!(r); }
+        if let Some(s) = self.seed { options["seed"] = json!(s); }
+        if !self.stop.is_empty() { options["stop"] = json!(self.stop); }
+
+        options
+    }
+}
+
 #[async_trait]
 pub trait LlmProvider: Send + Sync {
     async fn generate(&self, prompt: &str, model: &str, max_tokens: u32) -> Result<LlmResponse>;
+    /// Generation with sampling options; providers without support ignore them
+    async fn generate_with_options(
+        &self,
+        prompt: &str,
+        model: &str,
+        max_tokens: u32,
+        _options: &GenerationOptions,
+    ) -> Result<LlmResponse> {
+        self.generate(prompt, model, max_tokens).await
+    }
+    /// Generation over a tool conversation that offers `tools` natively.
+    /// Providers or models without support get `messages` as one prompt and
+    /// answer in plain text; callers then detect calls in the output.
+    async fn generate_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        max_tokens: u32,
+        options: &GenerationOptions,
+        _tools: &[ToolSpec],
+    ) -> Result<LlmResponse> {
+        self.generate_with_options(&render_messages(messages), model, max_tokens, options).await
+    }
     async fn generate_stream(
         &self,
         prompt: &str,
@@ -35,6 +217,10 @@ pub trait LlmProvider: Send + Sync {
 pub struct OllamaProvider {
     pub endpoint: String,
     pub timeout_seconds: u64,
+    /// Send tool schemas through `/api/chat` (`ai.native_tools`)
+    pub native_tools: bool,
+    /// Models that rejected native tools; they get text prompts from then on
+    no_tool_models: Mutex<HashSet<String>>,
 }
 
 impl OllamaProvider {
@@ -42,9 +228,31 @@ impl OllamaProvider {
         Self {
             endpoint,
             timeout_seconds,
+            native_tools: true,
+            no_tool_models: Mutex::new(HashSet::new()),
         }
     }
 
+    pub fn with_native_tools(mut self, enabled: bool) -> Self {
+        self.native_tools = enabled;
+        self
+    }
+
+    pub fn from_config(ai: &AiConfig) -> Self {
+        let endpoint = ai
+            .ollama_endpoint
+            .clone()
+            .or_else(|| ai.endpoint.clone())
+            .unwrap_or_else(|| "http://127.0.0.1:11434".into());
+
+        Self::new(endpoint.trim_end_matches('/').to_string(), ai.timeout_seconds)
+            .with_native_tools(ai.native_tools)
+    }
+
+    fn supports_tools(&self, model: &str) -> bool {
+        self.native_tools && !self.no_tool_models.lock().unwrap().contains(model)
+    }
+
     fn client(&self) -> Result<Client> {
         Ok(Client::builder()
             .timeout(Duration::from_secs(self.timeout_seconds))
@@ -55,6 +263,16 @@ impl OllamaProvider {
 #[async_trait]
 impl LlmProvider for OllamaProvider {
     async fn generate(&self, prompt: &str, model: &str, max_tokens: u32) -> Result<LlmResponse> {
+        self.generate_with_options(prompt, model, max_tokens, &GenerationOptions::default()).await
+    }
+
+    async fn generate_with_options(
+        &self,
+        prompt: &str,
+        model: &str,
+        max_tokens: u32,
+        options: &GenerationOptions,
+    ) -> Result<LlmResponse> {
         let client = self.client()?;
 
         let resp = client
@@ -63,9 +281,7 @@ impl LlmProvider for OllamaProvider {
                 "model": model,
                 "prompt": prompt,
                 "stream": false,
-                "options": {
-                    "num_predict": max_tokens
-                }
+                "options": options.to_ollama(max_tokens)
             }))
             .send()
             .await
@@ -83,9 +299,53 @@ impl LlmProvider for OllamaProvider {
             output,
             model: model.to_string(),
             tokens_used: None,
+            tool_calls: vec![],
         })
     }
 
+    async fn generate_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        max_tokens: u32,
+        options: &GenerationOptions,
+        tools: &[ToolSpec],
+    ) -> Result<LlmResponse> {
+        if tools.is_empty() || !self.supports_tools(model) {
+            return self.generate_with_options(&render_messages(messages), model, max_tokens, options).await;
+        }
+
+        let client = self.client()?;
+
+        let resp = client
+            .post(format!("{}/api/chat", self.endpoint))
+            .json(&json!({
+                "model": model,
+                "messages": messages.iter().map(ChatMessage::to_ollama).collect::<Vec<_>>(),
+                "tools": tools.iter().map(ToolSpec::to_ollama).collect::<Vec<_>>(),
+                "stream": false,
+                "options": options.to_ollama(max_tokens)
+            }))
+            .send()
+            .await
+            .context("Ollama chat request failed")?;
+
+        if !resp.status().is_success() {
+            let body = resp.text().await.unwrap_or_default();
+
+            if body.contains("does not support tools") {
+                tracing::info!("Model {} has no native tool support, using text tool calls", model);
+                self.no_tool_models.lock().unwrap().insert(model.to_string());
+                return self.generate_with_options(&render_messages(messages), model, max_tokens, options).await;
+            }
+
+            bail!("Ollama chat failed: {}", body.trim());
+        }
+
+        let json: Value = resp.json().await.context("Invalid Ollama chat response")?;
+        Ok(parse_chat_response(&json, model))
+    }
+
     async fn generate_stream(
         &self,
         prompt: &str,
@@ -131,6 +391,7 @@ impl LlmProvider for OllamaProvider {
             output: final_output,
             model: model.to_string(),
             tokens_used: None,
+            tool_calls: vec![],
         })
     }
 
@@ -150,3 +411,81 @@ impl LlmProvider for OllamaProvider {
         Ok(())
     }
 }
+
+/// Text and native tool calls of an `/api/chat` reply
+fn parse_chat_response(json: &Value, model: &str) -> LlmResponse {
+    let message = &json["message"];
+
+    let tool_calls = message["tool_calls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|call| {
+            let function = &call["function"];
+            let args = match &function["arguments"] {
+                // Some models send the arguments as a JSON string
+                Value::String(raw) => serde_json::from_str(raw).unwrap_or(Value::Null),
+                other => other.clone(),
+            };
+            Some(ToolCall { tool: function["name"].as_str()?.to_string(), args })
+        })
+        .collect();
+
+    LlmResponse {
+        output: message["content"].as_str().unwrap_or("").to_string(),
+        model: model.to_string(),
+        tokens_used: json["eval_count"].as_u64().map(|n| n as u32),
+        tool_calls,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chat_tool_calls() {
+        let reply = json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    { "function": { "name": "read_file", "arguments": { "path": "src/main.rs" } } },
+                    { "function": { "name": "git_status", "arguments": "{}" } }
+                ]
+            },
+            "eval_count": 12
+        });
+
+        let response = parse_chat_response(&reply, "qwen2.5:7b");
+        assert_eq!(response.tokens_used, Some(12));
+        assert_eq!(response.tool_calls, vec![
+            ToolCall { tool: "read_file".into(), args: json!({ "path": "src/main.rs" }) },
+            ToolCall { tool: "git_status".into(), args: json!({}) },
+        ]);
+    }
+
+    #[test]
+    fn test_tool_conversation_messages() {
+        let call = ToolCall { tool: "read_file".into(), args: json!({ "path": "Cargo.toml" }) };
+        let messages = [
+            ChatMessage::user("What is the crate called?"),
+            ChatMessage::assistant("", vec![call]),
+            ChatMessage::tool("read_file", "name = \"axon\""),
+        ];
+
+        assert_eq!(messages.iter().map(ChatMessage::to_ollama).collect::<Vec<_>>(), vec![
+            json!({ "role": "user", "content": "What is the crate called?" }),
+            json!({
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{ "function": { "name": "read_file", "arguments": { "path": "Cargo.toml" } } }]
+            }),
+            json!({ "role": "tool", "content": "name = \"axon\"", "tool_name": "read_file" }),
+        ]);
+        assert_eq!(
+            render_messages(&messages),
+            "What is the crate called?\n\nname = \"axon\"\n\nContinue with the original request."
+        );
+    }
+}
@@ -0,0 +1,564 @@
+//! Compile-verify-revert loop for build errors.
+//!
+//! The repair agent gets the compiler errors, grouped by file with the code
+//! around them and rustc's machine-applicable fixes, and answers with a `PatchNode`
+//! (or diffs, SEARCH/REPLACE blocks or whole files, see `edit_parser`).
+//! The patch is applied (after review when `repair.review` is on) and the
+//! project is checked again. A patch that brings new errors, or changes
+//! nothing, is reverted and the errors are fed back for the next attempt.
+//! The run ends with a `BuildFinished` whose output summarises the changes.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::ai::approval::ApprovalOutcome;
+use crate::ai::chat::ChatSession;
+use crate::ai::edit_parser::parse_actions;
+use crate::ai::model_router::AiTaskType;
+use crate::ai::multi_agent_router::PatchNode;
+use crate::ai::patch_executor::{PatchExecutor, PatchOutcome};
+use crate::ai::patch_review::PatchReviewer;
+use crate::ai::provider::LlmProvider;
+use crate::ai::self_reflection::MultiAgentRouter;
+use crate::ai::workspace::GitWorkspace;
+use crate::ai::structured::{parse_as, schema_of};
+use crate::config::schema::{AxonConfig, RepairConfig};
+use crate::event::bus::EventSender;
+use crate::event::event::AxonEvent;
+use crate::shell::command::run_command_in;
+use crate::util::diagnostics::{self, by_file, excerpt, Diagnostic, CONTEXT_LINES};
+use crate::util::path::confine;
+
+/// Compiler output kept in a repair prompt
+const MAX_ERROR_CHARS: usize = 6000;
+
+/// `:line:column:` in a compiler message
+static LOCATION: Lazy<Regex> = Lazy::new(|| Regex::new(r":\d+:\d+:").unwrap());
+
+/// Outcome of checking a project
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub success: bool,
+    pub output: String,
+    /// Parsed compiler messages; empty when the check gave only text
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CheckResult {
+    /// Errors with their line and column dropped, since those shift as
+    /// code moves
+    pub fn errors(&self) -> Vec<String> {
+        if !self.diagnostics.is_empty() {
+            return self.diagnostics.iter().filter(|d| d.is_error()).map(Diagnostic::key).collect();
+        }
+
+        self.output
+            .lines()
+            .map(str::trim)
+            .filter(|l| l.contains("error") && !l.contains("could not compile") && !l.contains("aborting due to"))
+            .filter(|l| l.starts_with("error") || l.contains(": error"))
+            .map(|l| LOCATION.replace(l, ":").into_owned())
+            .collect()
+    }
+}
+
+/// Checks the project at the given root
+pub type CheckHandler = Arc<dyn Fn(PathBuf) -> BoxFuture<'static, Result<CheckResult>> + Send + Sync>;
+
+/// `cargo check` with its messages parsed from `--message-format=json`,
+/// limited by `repair.check_timeout_seconds`
+pub fn cargo_check(mut config: AxonConfig) -> CheckHandler {
+    config.shell.timeout_seconds = config.repair.check_timeout_seconds;
+    Arc::new(move |root| {
+        let config = config.clone();
+        Box::pin(async move {
+            let args = ["check", "--message-format=json"].map(String::from);
+            let (stdout, stderr, code) = run_command_in("cargo", &args, Some(&root), &config).await?;
+
+            let diagnostics = diagnostics::parse(&stdout);
+            let rendered: String = diagnostics.iter().map(|d| d.rendered.as_str()).collect();
+            Ok(CheckResult { success: code == 0, output: format!("{}{}", rendered, stderr), diagnostics })
+        })
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum AttemptOutcome {
+    /// The project compiles
+    Fixed,
+    /// Fewer errors and no new ones; the patch stays
+    Improved { remaining: usize },
+    /// Reverted because it caused these errors
+    Reverted { new_errors: Vec<String> },
+    /// Reverted because the errors stayed the same
+    Unchanged,
+    /// The patch could not be applied
+    NotApplied { reason: String },
+    /// The answer held no usable patch
+    NoPatch { reason: String },
+    /// The reviewer rejected the patch or let it time out
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepairAttempt {
+    pub iteration: usize,
+    pub patch: Option<String>,
+    /// Files the patch touched
+    pub files: Vec<String>,
+    pub outcome: AttemptOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepairSummary {
+    pub project: String,
+    pub success: bool,
+    pub attempts: Vec<RepairAttempt>,
+    /// Errors still reported by the last check
+    pub remaining: Vec<String>,
+}
+
+impl RepairSummary {
+    pub fn render(&self) -> String {
+        let verdict = if self.success { "compiles" } else { "still fails" };
+        let mut text = format!("Repair of {}: {} after {} attempt(s)", self.project, verdict, self.attempts.len());
+
+        for attempt in &self.attempts {
+            let patch = attempt.patch.as_deref().unwrap_or("no patch");
+            let files = if attempt.files.is_empty() { String::new() } else { format!(" [{}]", attempt.files.join(", ")) };
+            let outcome = match &attempt.outcome {
+                AttemptOutcome::Fixed => "fixed".to_string(),
+                AttemptOutcome::Improved { remaining } => format!("kept, {} error(s) left", remaining),
+                AttemptOutcome::Reverted { new_errors } => format!("reverted, {} new error(s)", new_errors.len()),
+                AttemptOutcome::Unchanged => "reverted, no effect".to_string(),
+                AttemptOutcome::NotApplied { reason } => format!("not applied: {}", reason),
+                AttemptOutcome::NoPatch { reason } => format!("no patch: {}", reason),
+                AttemptOutcome::Rejected => "rejected in review".to_string(),
+            };
+            text.push_str(&format!("\n  {}. {}{}: {}", attempt.iteration, patch, files, outcome));
+        }
+
+        if !self.remaining.is_empty() {
+            text.push_str(&format!("\nRemaining errors:\n{}", self.remaining.join("\n")));
+        }
+        text
+    }
+}
+
+pub struct RepairLoop<P: LlmProvider> {
+    agents: Arc<MultiAgentRouter<P>>,
+    tx: EventSender,
+    root: PathBuf,
+    config: RepairConfig,
+    check: CheckHandler,
+    reviewer: Option<Arc<PatchReviewer>>,
+    workspace: Option<Arc<GitWorkspace>>,
+}
+
+impl<P: LlmProvider> RepairLoop<P> {
+    pub fn new(agents: Arc<MultiAgentRouter<P>>, tx: EventSender, root: impl Into<PathBuf>, check: CheckHandler) -> Self {
+        Self {
+            agents,
+            tx,
+            root: root.into(),
+            config: RepairConfig::default(),
+            check,
+            reviewer: None,
+            workspace: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: RepairConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Show every patch to `reviewer` before applying it (when `repair.review` is on)
+    pub fn with_reviewer(mut self, reviewer: Arc<PatchReviewer>) -> Self {
+        self.reviewer = Some(reviewer);
+        self
+    }
+
+    /// Commit every patch that is kept to the AI branch of `workspace`
+    pub fn with_workspace(mut self, workspace: Arc<GitWorkspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Repair `project` (relative to the root) and announce the result with
+    /// `BuildFinished`
+    pub async fn run(&self, id: Uuid, project: &str) -> Result<RepairSummary> {
+        let start = Instant::now();
+        let result = self.repair(id, project).await;
+
+        let (success, logs, output) = match &result {
+            Ok((summary, last)) => (summary.success, last.output.clone(), summary.render()),
+            Err(e) => (false, String::new(), format!("Repair of {} failed: {:#}", project, e)),
+        };
+        self.tx.send(AxonEvent::BuildFinished {
+            project: project.to_string(),
+            success,
+            logs,
+            output,
+            duration_ms: start.elapsed().as_millis() as u64,
+        })?;
+
+        result.map(|(summary, _)| summary)
+    }
+
+    async fn repair(&self, id: Uuid, project: &str) -> Result<(RepairSummary, CheckResult)> {
+        let root = confine(&self.root, project)?;
+        let executor = PatchExecutor::new(root.clone());
+
+        let mut check = (self.check)(root.clone()).await.context("Initial check failed to run")?;
+        let mut attempts = Vec::new();
+        let mut feedback = String::new();
+
+        for iteration in 1..=self.config.max_iterations {
+            if check.success {
+                break;
+            }
+
+            let errors = check.errors();
+            info!("Repair [{}] attempt {} on {}: {} error(s)", id, iteration, project, errors.len());
+
+            let node = match self.propose(id, &executor, &check, &feedback).await {
+                Ok(node) => node,
+                Err(e) => {
+                    feedback = format!("Your last answer held no usable patch: {:#}", e);
+                    attempts.push(RepairAttempt { iteration, patch: None, files: vec![], outcome: AttemptOutcome::NoPatch { reason: format!("{:#}", e) } });
+                    continue;
+                }
+            };
+
+            let mut attempt = RepairAttempt {
+                iteration,
+                patch: Some(format!("{}: {}", node.id, node.description)),
+                files: vec![],
+                outcome: AttemptOutcome::Rejected,
+            };
+
+            let Some(applied) = self.apply(&executor, &node).await else {
+                attempts.push(attempt);
+                break;
+            };
+            attempt.files = applied.report.actions.iter().map(|a| a.file_path.clone()).collect();
+            attempt.files.sort();
+            attempt.files.dedup();
+
+            if !applied.report.success {
+                let reason = applied.report.summary();
+                feedback = format!("Your last patch could not be applied:\n{}", reason);
+                attempt.outcome = AttemptOutcome::NotApplied { reason };
+                attempts.push(attempt);
+                continue;
+            }
+
+            let after = match (self.check)(root.clone()).await {
+                Ok(after) => after,
+                Err(e) => {
+                    if let Err(restore) = applied.backup.restore() {
+                        warn!("Could not revert patch {} after the failed check: {:#}", node.id, restore);
+                    }
+                    return Err(e.context("Check after patch failed to run"));
+                }
+            };
+            let before: HashSet<&String> = errors.iter().collect();
+            let new_errors: Vec<String> = after.errors().into_iter().filter(|e| !before.contains(e)).collect();
+
+            attempt.outcome = if after.success {
+                AttemptOutcome::Fixed
+            } else if !new_errors.is_empty() {
+                AttemptOutcome::Reverted { new_errors: new_errors.clone() }
+            } else if after.errors().len() < errors.len() {
+                AttemptOutcome::Improved { remaining: after.errors().len() }
+            } else {
+                AttemptOutcome::Unchanged
+            };
+
+            match &attempt.outcome {
+                AttemptOutcome::Reverted { .. } | AttemptOutcome::Unchanged => {
+                    applied.backup.restore().context("Could not revert the patch")?;
+                    feedback = if new_errors.is_empty() {
+                        "Your last patch did not change the errors and was reverted.".to_string()
+                    } else {
+                        format!("Your last patch was reverted because it caused new errors:\n{}", new_errors.join("\n"))
+                    };
+                }
+                _ => {
+                    if let Some(workspace) = &self.workspace {
+                        if let Err(e) = workspace.commit(&node, &applied.report).await {
+                            warn!("Repair patch {} kept but not committed: {:#}", node.id, e);
+                        }
+                    }
+                    feedback.clear();
+                    check = after;
+                }
+            }
+            attempts.push(attempt);
+        }
+
+        let summary = RepairSummary {
+            project: project.to_string(),
+            success: check.success,
+            attempts,
+            remaining: check.errors(),
+        };
+        info!("Repair [{}] of {} finished: success={}", id, project, summary.success);
+        Ok((summary, check))
+    }
+
+    /// Ask the repair agent for a patch against the current errors
+    async fn propose(&self, id: Uuid, executor: &PatchExecutor, check: &CheckResult, feedback: &str) -> Result<PatchNode> {
+        let agent = match self.agents.get(&self.config.agent).await {
+            Some(agent) => agent,
+            None => self.agents.select(&check.output, Some(AiTaskType::Coding)).await,
+        };
+
+        let prompt = format!(
+            "The project fails to compile. Fix these errors with the smallest change:\n{}\n\n{}\n\n\
+             Read more of a file only when its excerpt is not enough. Then reply with only a JSON patch matching this schema; \
+             do not apply it yourself. Prefer ReplaceBlock actions whose `search` is copied exactly \
+             from the file. Paths are relative to the project root. SEARCH/REPLACE blocks or a \
+             unified diff are accepted too.\n{}",
+            describe(executor.root(), check),
+            feedback,
+            schema_of::<PatchNode>()
+        );
+
+        let (answer, _) = self.agents.execute_traced(id, agent, &prompt, &ChatSession::new(), &[]).await;
+        let answer = answer?;
+        let node = match parse_as::<PatchNode>(&answer) {
+            Ok(node) => node,
+            Err(json_error) => {
+                let parsed = parse_actions(&answer, executor).map_err(|_| json_error)?;
+                if !parsed.errors.is_empty() {
+                    bail!("{}", parsed.feedback());
+                }
+
+                let mut node = PatchNode::new(format!("repair-{}", id), "Edits from the reply");
+                node.actions = parsed.actions;
+                node
+            }
+        };
+        if node.flatten().is_empty() {
+            bail!("the patch has no actions");
+        }
+        Ok(node)
+    }
+
+    /// Apply `node`, through review when configured; `None` when the reviewer said no
+    async fn apply(&self, executor: &PatchExecutor, node: &PatchNode) -> Option<PatchOutcome> {
+        match (&self.reviewer, self.config.review) {
+            (Some(reviewer), true) => {
+                let result = reviewer.review_with(executor, &self.config.agent, node).await;
+                if result.outcome != ApprovalOutcome::Approved {
+                    warn!("Repair patch {} was not approved ({:?})", node.id, result.outcome);
+                }
+                result.applied
+            }
+            _ => Some(executor.apply(node)),
+        }
+    }
+}
+
+/// The errors of `check` grouped by file, each file with the code around
+/// its errors and rustc's machine-applicable fixes; the raw output when
+/// there are no parsed diagnostics. Only files inside `root` are read.
+fn describe(root: &Path, check: &CheckResult) -> String {
+    let errors: Vec<Diagnostic> = check.diagnostics.iter().filter(|d| d.is_error()).cloned().collect();
+    let mut out = String::new();
+
+    if errors.is_empty() {
+        out.push_str(&check.output);
+    } else {
+        out.push_str("Excerpts start each line with its number; leave the numbers out of `search`.\n");
+    }
+
+    for (file, diagnostics) in by_file(&errors) {
+        out.push_str(&format!("\n## {}\n", if file.is_empty() { "(crate)" } else { &file }));
+
+        for diagnostic in &diagnostics {
+            out.push_str(&format!("- {}\n", diagnostic.headline()));
+            for fix in diagnostic.suggestions.iter().filter(|s| s.is_machine_applicable()) {
+                out.push_str(&format!(
+                    "  rustc fix ({}): replace {}:{}:{}-{}:{} with `{}`\n",
+                    fix.message, fix.span.file, fix.span.line_start, fix.span.column_start, fix.span.line_end, fix.span.column_end, fix.replacement
+                ));
+            }
+        }
+
+        let ranges: Vec<(usize, usize)> = diagnostics.iter().filter_map(|d| d.span.as_ref()).map(|s| (s.line_start, s.line_end)).collect();
+        // rustc names dependency sources by absolute path
+        let Ok(path) = confine(root, &file) else {
+            continue;
+        };
+        if let Ok(text) = std::fs::read_to_string(path) {
+            if !ranges.is_empty() {
+                out.push_str(&format!("```rust\n{}```\n", excerpt(&text, &ranges, CONTEXT_LINES)));
+            }
+        }
+    }
+
+    if let Some((cut, _)) = out.char_indices().nth(MAX_ERROR_CHARS) {
+        out.truncate(cut);
+        out.push_str("\n... [more errors truncated]");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::ModelRegistry;
+    use crate::ai::test_provider::ScriptedProvider;
+    use crate::config::schema::AiConfig;
+    use tokio::sync::broadcast;
+
+    fn replace(search: &str, content: &str) -> String {
+        serde_json::json!({
+            "id": "fix",
+            "description": format!("{} -> {}", search, content),
+            "actions": [{
+                "file_path": "lib.rs",
+                "description": "fix",
+                "change_type": "ReplaceBlock",
+                "search": search,
+                "content": content,
+            }],
+            "children": [],
+        })
+        .to_string()
+    }
+
+    /// Every line of lib.rs containing `bad` or `worse` is an error
+    fn fake_check() -> CheckHandler {
+        Arc::new(|root: PathBuf| {
+            Box::pin(async move {
+                let text = std::fs::read_to_string(root.join("lib.rs"))?;
+                let output: Vec<String> = text
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, l)| l.contains("bad") || l.contains("worse"))
+                    .map(|(i, l)| format!("lib.rs:{}:1: error: {}", i + 1, l.trim()))
+                    .collect();
+                Ok(CheckResult { success: output.is_empty(), output: output.join("\n"), diagnostics: vec![] })
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_reverts_regressions_and_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn a() { bad }\n").unwrap();
+
+        // A regression, a patch that does not apply, then the fix
+        let answers = vec![replace("bad", "worse"), replace("missing", "x"), replace("bad", "good")];
+        let provider = Arc::new(ScriptedProvider::answers(answers));
+        let agents = Arc::new(MultiAgentRouter::new(provider.clone(), ModelRegistry::from_ai_config(&AiConfig::default())));
+        let (tx, mut rx) = broadcast::channel(16);
+
+        let config = RepairConfig { review: false, ..Default::default() };
+        let repair = RepairLoop::new(agents, tx, dir.path(), fake_check()).with_config(config);
+        let summary = repair.run(Uuid::new_v4(), ".").await.unwrap();
+
+        let outcomes: Vec<&AttemptOutcome> = summary.attempts.iter().map(|a| &a.outcome).collect();
+        assert!(matches!(outcomes[0], AttemptOutcome::Reverted { new_errors } if new_errors[0].contains("worse")));
+        assert!(matches!(outcomes[1], AttemptOutcome::NotApplied { .. }));
+        assert_eq!(outcomes[2], &AttemptOutcome::Fixed);
+        assert_eq!(std::fs::read_to_string(dir.path().join("lib.rs")).unwrap(), "fn a() { good }\n");
+
+        // The reverted errors were fed back
+        assert!(provider.prompts()[1].contains("caused new errors"));
+
+        match rx.recv().await.unwrap() {
+            AxonEvent::BuildFinished { success, output, .. } => {
+                assert!(success);
+                assert!(output.contains("reverted, 1 new error(s)"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_describe_excerpts_files_inside_root() {
+        use crate::util::diagnostics::{Span, Suggestion};
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("lib.rs"), "fn a() {}\n\nfn b() { cout }\n").unwrap();
+        std::fs::write(dir.path().join("outside.rs"), "SECRET\n").unwrap();
+
+        let span = |file: &str, line: usize| Span { file: file.into(), line_start: line, line_end: line, column_start: 10, column_end: 14 };
+        let error = |file: &str, suggestions: Vec<Suggestion>| Diagnostic {
+            level: "error".into(),
+            code: Some("E0425".into()),
+            message: "cannot find value `cout` in this scope".into(),
+            span: Some(span(file, 3)),
+            label: None,
+            suggestions,
+            rendered: String::new(),
+        };
+        let fix = Suggestion {
+            message: "a local variable with a similar name exists".into(),
+            span: span("lib.rs", 3),
+            replacement: "count".into(),
+            applicability: Some("MachineApplicable".into()),
+        };
+        let check = CheckResult {
+            success: false,
+            output: String::new(),
+            diagnostics: vec![error("lib.rs", vec![fix]), error("../outside.rs", vec![]), error("/etc/hostname", vec![])],
+        };
+
+        let prompt = describe(&root, &check);
+        assert!(prompt.contains("3 | fn b() { cout }"), "{}", prompt);
+        assert!(prompt.contains("rustc fix (a local variable with a similar name exists): replace lib.rs:3:10-3:14 with `count`"), "{}", prompt);
+        assert!(prompt.contains("## ../outside.rs") && prompt.contains("## /etc/hostname"));
+        assert!(!prompt.contains("SECRET"));
+        assert_eq!(prompt.matches("```rust").count(), 1, "{}", prompt);
+    }
+
+    #[tokio::test]
+    async fn test_failed_check_reverts_the_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn a() { bad }\n").unwrap();
+
+        // The check after the patch cannot run
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let inner = fake_check();
+        let check: CheckHandler = Arc::new(move |root| {
+            let inner = inner.clone();
+            let call = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                if call == 1 {
+                    bail!("cargo timed out");
+                }
+                inner(root).await
+            })
+        });
+
+        let provider = Arc::new(ScriptedProvider::answers([replace("bad", "good")]));
+        let agents = Arc::new(MultiAgentRouter::new(provider, ModelRegistry::from_ai_config(&AiConfig::default())));
+        let (tx, _rx) = broadcast::channel(16);
+
+        let config = RepairConfig { review: false, ..Default::default() };
+        let repair = RepairLoop::new(agents, tx, dir.path(), check).with_config(config);
+        let error = repair.run(Uuid::new_v4(), ".").await.unwrap_err();
+
+        assert!(format!("{:#}", error).contains("cargo timed out"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("lib.rs")).unwrap(), "fn a() { bad }\n");
+    }
+}
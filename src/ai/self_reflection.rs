@@ -1,151 +1,770 @@
-use anyhow::Result;
-use std::collections::HashMap;
+use anyhow::{bail, Context, Result};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
 
-use crate::ai::provider::LlmProvider;
-use crate::ai::models::ModelRegistry; 
+use crate::ai::chat::{ChatRole, ChatSession};
+use crate::ai::model_router::{mentions, AiTaskType};
+use crate::ai::provider::{ChatMessage, GenerationOptions, LlmProvider, LlmResponse, ToolSpec};
+use crate::ai::models::ModelRegistry;
 use crate::ai::prompt_builder::PromptBuilder;
+use crate::ai::structured::{generate_structured, DEFAULT_REPROMPTS};
+use crate::ai::delegation::TraceNode;
+use crate::ai::tool_router::ToolRouter;
+pub use crate::ai::streaming_ollama::SelfReflectionEngine;
+use crate::config::schema::{AgentConfig, AgentRoutingConfig, ModelInfo, ReflectionConfig, ToolLoopConfig};
+use crate::event::bus::EventSender;
+use crate::event::event::AxonEvent;
+use crate::rag::vector_store::VectorStore;
 
-/// Tipuri de agenți disponibili în AXON
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum AgentType {
-    General,
-    Coder,
-    Analyst,
-    Planner,
-}
+/// Share of the model window kept free for the answer (1/N)
+const COMPLETION_SHARE: u32 = 4;
+
+/// RAG chunks retrieved per request
+const CONTEXT_CHUNKS: usize = 4;
 
 /// Un agent AI configurat
 pub struct AiAgent<P: LlmProvider> {
-    pub agent_type: AgentType,
+    pub name: String,
+    pub description: String,
     pub system_prompt: String,
     pub provider: Arc<P>,
     pub model_name: String,
     pub max_tokens: u32,
+    pub task: AiTaskType,
+    pub options: GenerationOptions,
+    pub allowed_tools: Vec<String>,
+    pub keywords: Vec<String>,
+    /// Self-check answers by default
+    pub reflect: bool,
+}
+
+impl<P: LlmProvider> Clone for AiAgent<P> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            system_prompt: self.system_prompt.clone(),
+            provider: self.provider.clone(),
+            model_name: self.model_name.clone(),
+            max_tokens: self.max_tokens,
+            task: self.task,
+            options: self.options.clone(),
+            allowed_tools: self.allowed_tools.clone(),
+            keywords: self.keywords.clone(),
+            reflect: self.reflect,
+        }
+    }
 }
 
 impl<P: LlmProvider> AiAgent<P> {
 
-    pub async fn execute(&self, input: &str) -> Result<String> {
-        let builder = PromptBuilder::new();
+    /// Build an agent from its `[[agents]]` entry.
+    /// `base_dir` resolves `system_prompt_file`.
+    pub fn from_config(
+        config: &AgentConfig,
+        provider: Arc<P>,
+        registry: &ModelRegistry,
+        base_dir: &Path,
+    ) -> Result<Self> {
+        let system_prompt = match (&config.system_prompt, &config.system_prompt_file) {
+            (Some(_), Some(_)) => {
+                bail!("Agent '{}': set either system_prompt or system_prompt_file, not both", config.name)
+            }
+            (Some(inline), None) => inline.clone(),
+            (None, Some(file)) => {
+                let path = base_dir.join(file);
+                std::fs::read_to_string(&path).with_context(|| {
+                    format!("Agent '{}': cannot read prompt file {}", config.name, path.display())
+                })?
+            }
+            (None, None) => String::new(),
+        };
 
-        let _fake_session = {
-            use crate::ai::chat::{ChatSession, ChatRole};
-            let mut s = ChatSession::new();
-            s.push(ChatRole::User, input.to_string());
-            s
+        let model = match &config.model {
+            Some(name) => registry.find(name).unwrap_or_else(|| crate::config::schema::ModelInfo {
+                name: name.clone(),
+                max_tokens: registry.model_for(config.task).max_tokens,
+            }),
+            None => registry.model_for(config.task),
         };
 
-        let prompt = builder.build();
+        Ok(Self {
+            name: config.name.clone(),
+            description: config.description.clone(),
+            system_prompt,
+            provider,
+            model_name: model.name,
+            max_tokens: config.max_tokens.unwrap_or(model.max_tokens),
+            task: config.task,
+            options: config.options.clone(),
+            allowed_tools: config.tools.clone(),
+            keywords: config.keywords.iter().map(|k| k.to_lowercase()).collect(),
+            reflect: config.reflect,
+        })
+    }
+
+    /// Same agent on a different model (explicit per-request override)
+    pub fn with_model(&self, model_name: &str, max_tokens: u32) -> Self {
+        Self {
+            model_name: model_name.to_string(),
+            max_tokens,
+            ..self.clone()
+        }
+    }
+
+    /// Same agent with extra system instructions appended
+    pub fn with_system_suffix(&self, suffix: &str) -> Self {
+        Self {
+            system_prompt: format!("{}\n\n{}", self.system_prompt.trim_end(), suffix),
+            ..self.clone()
+        }
+    }
+
+    fn matches(&self, lower_input: &str) -> bool {
+        self.keywords.iter().any(|k| mentions(lower_input, k))
+    }
+
+    pub async fn execute(&self, input: &str) -> Result<String> {
+        self.execute_with(input, &ChatSession::new(), &[]).await
+    }
 
-        let response = self.provider
-            .generate(
-                &prompt,
+    /// Run one turn with the prior conversation and retrieved RAG chunks.
+    /// The prompt is trimmed to fit `max_tokens` minus the answer reserve.
+    pub async fn execute_with(
+        &self,
+        input: &str,
+        session: &ChatSession,
+        rag_context: &[String],
+    ) -> Result<String> {
+        Ok(self.execute_with_tools(input, session, rag_context, &[], &[]).await?.output)
+    }
+
+    /// Like `execute_with`, offering `tools` to the model natively.
+    /// `turns` are the tool calls and results of this request so far.
+    /// The response carries any native `tool_calls`.
+    pub async fn execute_with_tools(
+        &self,
+        input: &str,
+        session: &ChatSession,
+        rag_context: &[String],
+        turns: &[ChatMessage],
+        tools: &[ToolSpec],
+    ) -> Result<LlmResponse> {
+        let completion_tokens = (self.max_tokens / COMPLETION_SHARE).max(1);
+
+        let parts = PromptBuilder::new()
+            .system(self.system_prompt.as_str())
+            .context(rag_context.iter().cloned())
+            .history(&session.history)
+            .user(input)
+            .token_budget(self.max_tokens.saturating_sub(completion_tokens))
+            .fit();
+
+        let mut messages: Vec<ChatMessage> = parts.system.iter().map(ChatMessage::system).collect();
+        messages.extend(parts.history.iter().map(|(role, text)| match role {
+            ChatRole::User => ChatMessage::user(text),
+            ChatRole::Assistant => ChatMessage::assistant(text, vec![]),
+            ChatRole::System => ChatMessage::system(text),
+        }));
+        messages.push(ChatMessage::user(parts.request()));
+        messages.extend_from_slice(turns);
+
+        self.provider
+            .generate_with_tools(
+                &messages,
                 &self.model_name,
-                self.max_tokens,
+                completion_tokens,
+                &self.options,
+                tools,
             )
-            .await?;
-
-        Ok(response.output)
+            .await
     }
 }
 
+/// Answer of an agent run plus its delegation trace and reflection stats
+pub struct AgentOutcome {
+    pub result: Result<String>,
+    pub trace: TraceNode,
+    /// Reflection score of the returned answer, when reflection ran
+    pub confidence: Option<f32>,
+    /// Generations it took, retries included
+    pub attempts: u32,
+    /// Reflection score of every graded attempt, in order
+    pub scores: Vec<f32>,
+}
+
+/// Agent picked for a request, with how sure the router is and why
+pub struct AgentSelection<P: LlmProvider> {
+    pub agent: Arc<AiAgent<P>>,
+    pub confidence: f32,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct AgentVerdict {
+    agent: String,
+    #[serde(default)]
+    confidence: f32,
+    #[serde(default)]
+    reason: String,
+}
+
+/// Agents in routing order plus the index of the fallback agent
+struct AgentSet<P: LlmProvider> {
+    agents: Vec<Arc<AiAgent<P>>>,
+    fallback: usize,
+}
+
 /// Router care gestionează mai mulți agenți
 pub struct MultiAgentRouter<P: LlmProvider> {
-    agents: HashMap<AgentType, AiAgent<P>>,
+    agents: RwLock<AgentSet<P>>,
+    provider: Arc<P>,
+    registry: ModelRegistry,
+    base_dir: PathBuf,
+    retriever: Option<Arc<RwLock<VectorStore>>>,
+    tools: Option<Arc<ToolRouter>>,
+    events: Option<EventSender>,
+    routing: AgentRoutingConfig,
+    reflection: ReflectionConfig,
+    tool_loop: ToolLoopConfig,
 }
 
 impl<P: LlmProvider> MultiAgentRouter<P> {
 
+    /// Router with the built-in agents (`AgentConfig::defaults`)
     pub fn new(provider: Arc<P>, registry: ModelRegistry) -> Self {
+        Self::from_config(provider, registry, &AgentConfig::defaults(), Path::new("."))
+            .expect("built-in agents are valid")
+    }
 
-        let mut agents = HashMap::new();
-
-        agents.insert(
-            AgentType::General,
-            AiAgent {
-                agent_type: AgentType::General,
-                system_prompt: "You are a helpful AI assistant.".to_string(),
-                provider: provider.clone(),
-                model_name: registry.default_model().name.clone(),
-                max_tokens: registry.default_model().max_tokens,
-            },
-        );
+    pub fn from_config(
+        provider: Arc<P>,
+        registry: ModelRegistry,
+        configs: &[AgentConfig],
+        base_dir: &Path,
+    ) -> Result<Self> {
+        let set = build_agents(configs, &provider, &registry, base_dir)?;
+
+        Ok(Self {
+            agents: RwLock::new(set),
+            provider,
+            registry,
+            base_dir: base_dir.to_path_buf(),
+            retriever: None,
+            tools: None,
+            events: None,
+            routing: AgentRoutingConfig::default(),
+            reflection: ReflectionConfig::default(),
+            tool_loop: ToolLoopConfig::default(),
+        })
+    }
 
-        agents.insert(
-            AgentType::Coder,
-            AiAgent {
-                agent_type: AgentType::Coder,
-                system_prompt: "You are a senior software engineer. Provide precise code.".to_string(),
-                provider: provider.clone(),
-                model_name: "default".to_string(),
-                max_tokens: 4096,
-            },
-        );
+    /// Replace all agents from a fresh config. On error the current agents stay.
+    pub async fn reload(&self, configs: &[AgentConfig]) -> Result<()> {
+        let set = build_agents(configs, &self.provider, &self.registry, &self.base_dir)?;
+        *self.agents.write().await = set;
+        Ok(())
+    }
 
-        agents.insert(
-            AgentType::Analyst,
-            AiAgent {
-                agent_type: AgentType::Analyst,
-                system_prompt: "You analyze problems step by step with structured reasoning.".to_string(),
-                provider: provider.clone(),
-                model_name: registry.default_model().name.clone(),
-                max_tokens: registry.default_model().max_tokens,
-            },
-        );
+    /// Enable model-based agent selection per `[agent_routing]`
+    pub fn with_routing(mut self, routing: AgentRoutingConfig) -> Self {
+        self.routing = routing;
+        self
+    }
+
+    /// Thresholds for the self-reflection retry loop
+    pub fn with_reflection(mut self, reflection: ReflectionConfig) -> Self {
+        self.reflection = reflection;
+        self
+    }
+
+    /// Iteration and time budget for tool calls per request
+    pub fn with_tool_loop(mut self, tool_loop: ToolLoopConfig) -> Self {
+        self.tool_loop = tool_loop;
+        self
+    }
+
+    /// Publish agent steps (`ToolStep`) on the bus
+    pub fn with_events(mut self, tx: EventSender) -> Self {
+        self.events = Some(tx);
+        self
+    }
+
+    pub fn routing(&self) -> &AgentRoutingConfig {
+        &self.routing
+    }
+
+    pub fn tool_loop(&self) -> &ToolLoopConfig {
+        &self.tool_loop
+    }
+
+    pub(crate) fn publish(&self, event: AxonEvent) {
+        if let Some(tx) = &self.events {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Ground agent prompts in chunks from the RAG index
+    pub fn with_retriever(mut self, store: Arc<RwLock<VectorStore>>) -> Self {
+        self.retriever = Some(store);
+        self
+    }
+
+    /// Tools agents may call, as listed in their `tools`
+    pub fn with_tools(mut self, tools: Arc<ToolRouter>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    pub fn tools(&self) -> Option<&Arc<ToolRouter>> {
+        self.tools.as_ref()
+    }
+
+    /// RAG chunks relevant to `input` (empty without a retriever)
+    pub async fn retrieve(&self, input: &str) -> Vec<String> {
+        let Some(store) = &self.retriever else {
+            return Vec::new();
+        };
+
+        store
+            .read()
+            .await
+            .search(input, CONTEXT_CHUNKS)
+            .into_iter()
+            .map(|(file, chunk, _)| format!("{}:\n{}", file, chunk))
+            .collect()
+    }
+
+    pub async fn agents(&self) -> Vec<Arc<AiAgent<P>>> {
+        self.agents.read().await.agents.clone()
+    }
+
+    pub async fn get(&self, name: &str) -> Option<Arc<AiAgent<P>>> {
+        self.agents.read().await.agents.iter().find(|a| a.name == name).cloned()
+    }
+
+    /// Pick an agent with the heuristics only
+    pub async fn select(&self, input: &str, task: Option<AiTaskType>) -> Arc<AiAgent<P>> {
+        self.select_heuristic(input, task).await.agent
+    }
 
-        agents.insert(
-            AgentType::Planner,
-            AiAgent {
-                agent_type: AgentType::Planner,
-                system_prompt: "You break complex tasks into actionable plans.".to_string(),
-                provider,
-                model_name: registry.default_model().name.clone(),
-                max_tokens: registry.default_model().max_tokens,
-            },
+    /// Pick an agent: the routing model when enabled and confident enough,
+    /// otherwise the keyword heuristics.
+    pub async fn choose(&self, input: &str, task: Option<AiTaskType>) -> AgentSelection<P> {
+        if self.routing.llm {
+            match self.select_with_model(input).await {
+                Ok(choice) if choice.confidence >= self.routing.min_confidence => return choice,
+                Ok(choice) => tracing::debug!(
+                    "Agent routing below threshold ({} at {:.2}), using heuristics",
+                    choice.agent.name,
+                    choice.confidence
+                ),
+                Err(e) => tracing::warn!("Agent routing failed: {:#}", e),
+            }
+        }
+
+        self.select_heuristic(input, task).await
+    }
+
+    /// First keyword match in config order, then the first agent serving
+    /// the request's task, then the fallback agent.
+    async fn select_heuristic(&self, input: &str, task: Option<AiTaskType>) -> AgentSelection<P> {
+        let set = self.agents.read().await;
+        let lower = input.to_lowercase();
+
+        if let Some(agent) = set.agents.iter().find(|a| a.matches(&lower)) {
+            let keyword = agent.keywords.iter().find(|k| mentions(&lower, k)).cloned().unwrap_or_default();
+            return AgentSelection {
+                agent: agent.clone(),
+                confidence: 0.7,
+                reason: format!("keyword '{}'", keyword),
+            };
+        }
+
+        let by_task = task
+            .filter(|t| *t != AiTaskType::General)
+            .and_then(|t| set.agents.iter().find(|a| a.task == t));
+        if let Some(agent) = by_task {
+            return AgentSelection {
+                agent: agent.clone(),
+                confidence: 0.6,
+                reason: format!("serves {:?} tasks", agent.task),
+            };
+        }
+
+        AgentSelection {
+            agent: set.agents[set.fallback].clone(),
+            confidence: 0.5,
+            reason: "fallback agent".into(),
+        }
+    }
+
+    async fn select_with_model(&self, input: &str) -> Result<AgentSelection<P>> {
+        let agents = self.agents().await;
+
+        let listing = agents
+            .iter()
+            .map(|a| format!("- {}: {}", a.name, a.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let prompt = format!(
+            "Choose the agent best suited to handle the request.\n\n\
+             Agents:\n{}\n\n\
+             Request:\n{}\n\n\
+             Respond strictly in JSON:\n\
+             {{\"agent\":\"name\",\"confidence\":0.0,\"reason\":\"...\"}}",
+            listing, input
         );
 
-        Self { agents }
+        let model = self
+            .routing
+            .model
+            .clone()
+            .unwrap_or_else(|| self.registry.default_model().name);
+
+        let verdict: AgentVerdict =
+            generate_structured(self.provider.as_ref(), &prompt, &model, 128, DEFAULT_REPROMPTS)
+                .await
+                .context("Invalid agent routing answer")?;
+
+        let agent = agents
+            .into_iter()
+            .find(|a| a.name.eq_ignore_ascii_case(verdict.agent.trim()))
+            .with_context(|| format!("Routing model chose unknown agent '{}'", verdict.agent))?;
+
+        Ok(AgentSelection {
+            agent,
+            confidence: verdict.confidence.clamp(0.0, 1.0),
+            reason: format!("{} (model {})", verdict.reason, model),
+        })
+    }
+
+    /// Run `agent` and, when reflection is on (`reflect`, else the agent's
+    /// setting), grade the answer and regenerate weak ones with the critique
+    /// fed back, up to `[reflection] max_attempts`. The best answer wins.
+    pub async fn execute_reflective(
+        &self,
+        request_id: Uuid,
+        agent: Arc<AiAgent<P>>,
+        input: &str,
+        session: &ChatSession,
+        rag_context: &[String],
+        reflect: Option<bool>,
+    ) -> AgentOutcome {
+        let (result, trace) = self.execute_traced(request_id, agent.clone(), input, session, rag_context).await;
+
+        if !reflect.unwrap_or(agent.reflect) {
+            return AgentOutcome { result, trace, confidence: None, attempts: 1, scores: vec![] };
+        }
+
+        let Ok(first) = result else {
+            return AgentOutcome { result, trace, confidence: None, attempts: 1, scores: vec![] };
+        };
+
+        let engine = SelfReflectionEngine::new(self.provider.clone());
+        let judge = ModelInfo {
+            name: self.reflection.model.clone().unwrap_or_else(|| agent.model_name.clone()),
+            max_tokens: agent.max_tokens,
+        };
+        let max_attempts = self.reflection.max_attempts.max(1);
+
+        let mut best: Option<(String, TraceNode, f32)> = None;
+        let mut scores = Vec::new();
+        let mut current = (first, trace);
+        let mut attempts = 1;
+
+        loop {
+            let (output, trace) = current;
+
+            let review = match engine.evaluate(input, &output, &judge).await {
+                Ok(review) => review,
+                Err(e) => {
+                    tracing::warn!("Self-reflection failed: {:#}", e);
+                    return AgentOutcome { result: Ok(output), trace, confidence: None, attempts, scores };
+                }
+            };
+
+            tracing::info!(
+                "Reflection on '{}' attempt {}: confidence {:.2}, retry {}",
+                agent.name, attempts, review.confidence_score, review.should_retry
+            );
+
+            scores.push(review.confidence_score);
+            let good = review.confidence_score >= self.reflection.min_confidence && !review.should_retry;
+            let critique = review.reasoning.clone();
+
+            if best.as_ref().is_none_or(|(_, _, score)| review.confidence_score > *score) {
+                best = Some((output.clone(), trace, review.confidence_score));
+            }
+
+            if good || attempts >= max_attempts {
+                break;
+            }
+
+            let retry = format!(
+                "{}\n\nYour previous answer:\n{}\n\nReviewer critique (confidence {:.2}):\n{}\n\n\
+                 Write an improved answer that addresses the critique.",
+                input, output, review.confidence_score, critique
+            );
+
+            let (result, trace) = self.execute_traced(request_id, agent.clone(), &retry, session, rag_context).await;
+            match result {
+                Ok(output) => {
+                    attempts += 1;
+                    current = (output, trace);
+                }
+                // No answer came back, so this attempt is not counted
+                Err(e) => {
+                    tracing::warn!("Reflection retry failed: {:#}", e);
+                    break;
+                }
+            }
+        }
+
+        let (output, trace, confidence) = best.expect("at least one answer was graded");
+        AgentOutcome { result: Ok(output), trace, confidence: Some(confidence), attempts, scores }
     }
 
     /// Select agent based on task heuristics
     pub async fn route(&self, input: &str) -> Result<String> {
+        self.route_in_session(&mut ChatSession::new(), input).await
+    }
 
-        let lower = input.to_lowercase();
+    /// Like `route`, but with conversation memory; both turns are appended
+    pub async fn route_in_session(&self, session: &mut ChatSession, input: &str) -> Result<String> {
+        let agent = self.choose(input, None).await.agent;
 
-        let agent_type = if lower.contains("code") ||
-                            lower.contains("bug") ||
-                            lower.contains("error") {
-            AgentType::Coder
-        } else if lower.contains("plan") ||
-                  lower.contains("steps") {
-            AgentType::Planner
-        } else if lower.contains("analyze") {
-            AgentType::Analyst
-        } else {
-            AgentType::General
-        };
+        let context = self.retrieve(input).await;
+        let (output, _trace) = self.execute_traced(Uuid::new_v4(), agent, input, session, &context).await;
+        let output = output?;
 
-        let agent = self.agents
-            .get(&agent_type)
-            .expect("Agent not found");
+        session.push(ChatRole::User, input.to_string());
+        session.push(ChatRole::Assistant, output.clone());
 
-        agent.execute(input).await
+        Ok(output)
     }
 }
 
-// === AXON_COMPAT: SelfReflectionEngine stub ===
-/// Compatibility stub for SelfReflectionEngine.
-#[derive(Debug, Clone)]
-pub struct SelfReflectionEngine;
+fn build_agents<P: LlmProvider>(
+    configs: &[AgentConfig],
+    provider: &Arc<P>,
+    registry: &ModelRegistry,
+    base_dir: &Path,
+) -> Result<AgentSet<P>> {
+    if configs.is_empty() {
+        bail!("At least one agent must be configured");
+    }
+
+    let mut seen = HashSet::new();
+    let mut agents = Vec::with_capacity(configs.len());
 
-impl SelfReflectionEngine {
-    pub fn new() -> Self { SelfReflectionEngine }
+    for config in configs {
+        if config.name.trim().is_empty() {
+            bail!("Agent names must not be empty");
+        }
+        if !seen.insert(config.name.as_str()) {
+            bail!("Duplicate agent name '{}'", config.name);
+        }
+
+        agents.push(Arc::new(AiAgent::from_config(config, provider.clone(), registry, base_dir)?));
+    }
+
+    let fallback = configs
+        .iter()
+        .position(|c| c.fallback)
+        .or_else(|| configs.iter().position(|c| c.name == "general"))
+        .unwrap_or(0);
+
+    Ok(AgentSet { agents, fallback })
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::test_provider::ScriptedProvider;
+    use crate::config::schema::{AiConfig, ModelInfo};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn noop() -> ScriptedProvider {
+        ScriptedProvider::new(|_| String::new())
+    }
+
+    /// Always answers with the same routing verdict
+    fn verdict(answer: &'static str) -> ScriptedProvider {
+        ScriptedProvider::new(move |_| answer.to_string())
+    }
+
+    fn registry() -> ModelRegistry {
+        ModelRegistry::from_ai_config(&AiConfig {
+            coder_model: ModelInfo { name: "coder:7b".into(), max_tokens: 8192 },
+            ..AiConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_default_agents_route_like_before() {
+        let router = MultiAgentRouter::new(Arc::new(noop()), registry());
+
+        assert_eq!(router.select("fix this bug", None).await.name, "coder");
+        assert_eq!(router.select("give me the steps", None).await.name, "planner");
+        assert_eq!(router.select("hello", None).await.name, "general");
+        assert_eq!(router.select("hello", Some(AiTaskType::Coding)).await.model_name, "coder:7b");
+    }
+
+    #[tokio::test]
+    async fn test_system_prompt_and_history_are_role_messages() {
+        let provider = Arc::new(ScriptedProvider::chat(|_| crate::ai::test_provider::text("sure")));
+        let config = AgentConfig {
+            name: "coder".into(),
+            system_prompt: Some("You are terse.".into()),
+            ..Default::default()
+        };
+        let agent = AiAgent::from_config(&config, provider.clone(), &registry(), Path::new(".")).unwrap();
+
+        let mut session = ChatSession::new();
+        session.push(ChatRole::User, "hi".into());
+        session.push(ChatRole::Assistant, "hello".into());
+        agent.execute_with("what now?", &session, &["fn main() {}".into()]).await.unwrap();
+
+        let conversation = provider.conversations().pop().unwrap();
+        let roles: Vec<&str> = conversation.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+        assert_eq!(conversation[0].content, "You are terse.");
+        assert_eq!(conversation[3].content, "### Context\n[1] fn main() {}\n\nwhat now?");
+
+        // Models without a chat API get the same conversation as one prompt
+        let prompt = provider.prompts().pop().unwrap();
+        assert!(prompt.starts_with("### System\nYou are terse."));
+        assert!(prompt.contains("User: hi\nAssistant: hello"));
+        assert!(prompt.ends_with("what now?\n\n### Assistant\n"));
+    }
+
+    #[tokio::test]
+    async fn test_keywords_match_whole_words() {
+        let router = MultiAgentRouter::new(Arc::new(noop()), registry());
+
+        assert_eq!(router.select("decode this base64", None).await.name, "general");
+        assert_eq!(router.select("name a planet", None).await.name, "general");
+        assert_eq!(router.select("review this code.", None).await.name, "coder");
+    }
 
+    #[tokio::test]
+    async fn test_model_routing_with_threshold() {
+        let routing = AgentRoutingConfig { llm: true, ..Default::default() };
+        let input = "explain this error message in plain words";
+
+        let confident = MultiAgentRouter::new(
+            Arc::new(verdict(r#"{"agent":"general","confidence":0.9,"reason":"plain explanation"}"#)),
+            registry(),
+        ).with_routing(routing.clone());
+        let choice = confident.choose(input, None).await;
+        assert_eq!(choice.agent.name, "general");
+        assert!(choice.reason.starts_with("plain explanation"));
+
+        // Unsure or unparseable answers fall back to keywords ("bug" -> coder)
+        for answer in [r#"{"agent":"general","confidence":0.3}"#, "no idea", r#"{"agent":"poet","confidence":1.0}"#] {
+            let router = MultiAgentRouter::new(Arc::new(verdict(answer)), registry())
+                .with_routing(routing.clone());
+            let choice = router.choose("explain this bug in plain words", None).await;
+            assert_eq!(choice.agent.name, "coder");
+            assert_eq!(choice.reason, "keyword 'bug'");
+        }
+
+        // A mere mention of an error is not a coding task
+        let keywords = MultiAgentRouter::new(Arc::new(noop()), registry());
+        assert_eq!(keywords.choose(input, None).await.agent.name, "general");
+    }
+
+    /// Answers get better with each attempt; the reviewer scores them in turn
+    fn improving(scores: &'static [f32]) -> ScriptedProvider {
+        let answers = AtomicUsize::new(0);
+        let reviews = AtomicUsize::new(0);
+
+        ScriptedProvider::new(move |prompt| {
+            if prompt.starts_with("You are evaluating") {
+                let score = scores[reviews.fetch_add(1, Ordering::SeqCst)];
+                format!(r#"{{"confidence":{},"retry":false,"reason":"be more specific"}}"#, score)
+            } else {
+                assert!(answers.load(Ordering::SeqCst) == 0 || prompt.contains("be more specific"));
+                format!("answer {}", answers.fetch_add(1, Ordering::SeqCst) + 1)
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_reflection_retries_and_keeps_best() {
+        let run = |scores: &'static [f32], reflect: Option<bool>| async move {
+            let router = MultiAgentRouter::new(Arc::new(improving(scores)), registry());
+            let agent = router.get("general").await.unwrap();
+            router.execute_reflective(Uuid::new_v4(), agent, "hello", &ChatSession::new(), &[], reflect).await
+        };
+
+        let outcome = run(&[0.2, 0.9], Some(true)).await;
+        assert_eq!(outcome.result.unwrap(), "answer 2");
+        assert_eq!(outcome.attempts, 2);
+        assert_eq!(outcome.confidence, Some(0.9));
+
+        // Capped at max_attempts (3); the best-scoring answer is returned
+        let outcome = run(&[0.2, 0.5, 0.3], Some(true)).await;
+        assert_eq!(outcome.result.unwrap(), "answer 2");
+        assert_eq!(outcome.attempts, 3);
 
+        // Off unless the agent or request opts in
+        let outcome = run(&[], None).await;
+        assert_eq!((outcome.attempts, outcome.confidence), (1, None));
+    }
+
+    #[tokio::test]
+    async fn test_failed_retry_is_not_an_attempt() {
+        let provider = ScriptedProvider::fallible(|prompt| {
+            if prompt.starts_with("You are evaluating") {
+                Ok(r#"{"confidence":0.2,"retry":true,"reason":"too vague"}"#.into())
+            } else if prompt.contains("too vague") {
+                anyhow::bail!("model unavailable")
+            } else {
+                Ok("first answer".into())
+            }
+        });
+        let router = MultiAgentRouter::new(Arc::new(provider), registry());
+        let agent = router.get("general").await.unwrap();
+
+        let outcome = router.execute_reflective(Uuid::new_v4(), agent, "hello", &ChatSession::new(), &[], Some(true)).await;
+        assert_eq!(outcome.result.unwrap(), "first answer");
+        assert_eq!((outcome.attempts, outcome.scores), (1, vec![0.2]));
+    }
 
+    #[tokio::test]
+    async fn test_config_agent_and_reload() {
+        let sql = AgentConfig {
+            name: "sql_reviewer".into(),
+            system_prompt: Some("Review SQL.".into()),
+            keywords: vec!["sql".into()],
+            model: Some("sqlcoder".into()),
+            ..Default::default()
+        };
 
+        let router = MultiAgentRouter::from_config(
+            Arc::new(noop()),
+            registry(),
+            std::slice::from_ref(&sql),
+            Path::new("."),
+        ).unwrap();
 
+        let agent = router.select("review this SQL query", None).await;
+        assert_eq!(agent.name, "sql_reviewer");
+        assert_eq!(agent.model_name, "sqlcoder");
 
+        // Invalid reload keeps the current agents
+        assert!(router.reload(&[sql.clone(), sql]).await.is_err());
+        assert!(router.get("sql_reviewer").await.is_some());
+
+        router.reload(&AgentConfig::defaults()).await.unwrap();
+        assert!(router.get("sql_reviewer").await.is_none());
+    }
+}
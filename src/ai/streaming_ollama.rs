@@ -1,7 +1,10 @@
 use anyhow::Result;
+use schemars::JsonSchema;
+use serde::Deserialize;
 use std::sync::Arc;
 
 use crate::ai::provider::LlmProvider;
+use crate::ai::structured::{generate_structured, DEFAULT_REPROMPTS};
 use crate::config::schema::ModelInfo;
 
 /// Result of AI self-evaluation
@@ -12,6 +15,16 @@ pub struct ReflectionResult {
     pub should_retry: bool,
 }
 
+/// Shape the reviewer must answer in
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ReflectionVerdict {
+    confidence: f32,
+    #[serde(default)]
+    retry: bool,
+    #[serde(default)]
+    reason: String,
+}
+
 pub struct SelfReflectionEngine<P: LlmProvider> {
     provider: Arc<P>,
 }
@@ -45,38 +58,25 @@ impl<P: LlmProvider> SelfReflectionEngine<P> {
             ai_output
         );
 
-        let response = self.provider
-            .generate(
-                &reflection_prompt,
-                &model.name,
-                512,
-            )
-            .await?;
-
-        // Try parsing JSON
-        let parsed: serde_json::Value =
-            serde_json::from_str(&response.output).unwrap_or_default();
-
-        let confidence = parsed
-            .get("confidence")
-            .and_then(|v| v.as_f64())
-            .unwrap_or(0.5) as f32;
-
-        let retry = parsed
-            .get("retry")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-
-        let reasoning = parsed
-            .get("reason")
-            .and_then(|v| v.as_str())
-            .unwrap_or("No reasoning provided")
-            .to_string();
+        let verdict: ReflectionVerdict = generate_structured(
+            self.provider.as_ref(),
+            &reflection_prompt,
+            &model.name,
+            512,
+            DEFAULT_REPROMPTS,
+        )
+        .await?;
+
+        let reasoning = if verdict.reason.trim().is_empty() {
+            "No reasoning provided".to_string()
+        } else {
+            verdict.reason
+        };
 
         Ok(ReflectionResult {
-            confidence_score: confidence,
+            confidence_score: verdict.confidence.clamp(0.0, 1.0),
             reasoning,
-            should_retry: retry,
+            should_retry: verdict.retry,
         })
     }
 }
@@ -92,3 +92,5 @@ impl StreamingEngine {
     pub fn new() -> Self { StreamingEngine }
 }
 
+impl Default for StreamingEngine { fn default() -> Self { Self::new() } }
+
@@ -0,0 +1,429 @@
+//! Structured (JSON) output from models.
+//!
+//! Models wrap JSON in prose or code fences, use single quotes, leave
+//! trailing commas or stop mid-object. This module finds the JSON in an
+//! answer, repairs the common defects, validates it against the target
+//! type's schema and, when that fails, asks the model again with the errors.
+
+use std::fmt;
+
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::ai::provider::LlmProvider;
+use crate::event::schema::{validate, SchemaViolation};
+
+/// Extra attempts `generate_structured` makes after an unusable answer
+pub const DEFAULT_REPROMPTS: usize = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructuredError {
+    /// Nothing in the text parses as JSON, even after repair
+    NoJson,
+    /// JSON was found but does not match the schema
+    Invalid(Vec<SchemaViolation>),
+}
+
+impl fmt::Display for StructuredError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StructuredError::NoJson => write!(f, "no JSON found in the reply"),
+            StructuredError::Invalid(violations) => {
+                let list = violations.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("; ");
+                write!(f, "JSON does not match the expected shape: {}", list)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StructuredError {}
+
+/// JSON Schema of `T` as a plain value
+pub fn schema_of<T: JsonSchema>() -> Value {
+    serde_json::to_value(schemars::schema_for!(T)).unwrap_or(Value::Bool(true))
+}
+
+/// Possible JSON snippets in `text`: fenced code blocks first, then every
+/// top-level `{...}` / `[...]` span in reading order. A span cut off by the
+/// end of the text runs to the end (repair can close it).
+pub fn extract_json(text: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+
+    let mut rest = text;
+    while let Some(open) = rest.find("```") {
+        let after = &rest[open + 3..];
+        let body_start = after.find('\n').map_or(after.len(), |n| n + 1);
+        let body = &after[body_start..];
+
+        match body.find("```") {
+            Some(close) => {
+                let block = body[..close].trim();
+                if block.starts_with(['{', '[']) {
+                    found.push(block);
+                }
+                rest = &body[close + 3..];
+            }
+            None => {
+                let block = body.trim();
+                if block.starts_with(['{', '[']) {
+                    found.push(block);
+                }
+                break;
+            }
+        }
+    }
+
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if matches!(bytes[i], b'{' | b'[') {
+            let end = matching_close(text, i).unwrap_or(text.len());
+            let span = &text[i..end];
+            if !found.contains(&span) {
+                found.push(span);
+            }
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    found
+}
+
+/// Every complete top-level JSON object or array in `text`, in reading
+/// order, fenced or not. Each span is repaired if needed; a span that
+/// still does not parse is skipped and its inside scanned instead.
+pub fn json_values(text: &str) -> Vec<Value> {
+    let mut values = Vec::new();
+
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if matches!(bytes[i], b'{' | b'[') {
+            if let Some(end) = matching_close(text, i) {
+                let span = &text[i..end];
+                let parsed = serde_json::from_str::<Value>(span)
+                    .or_else(|_| serde_json::from_str::<Value>(&repair_json(span)));
+
+                if let Ok(value) = parsed {
+                    values.push(value);
+                    i = end;
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+
+    values
+}
+
+/// Byte index just past the bracket closing the one at `start`
+fn matching_close(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (offset, c) in text[start..].char_indices() {
+        if let Some(q) = quote {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                c if c == q => quote = None,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => quote = Some('"'),
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return Some(start + offset + c.len_utf8());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Fix the defects models commonly produce: smart and single quotes,
+/// unquoted keys, `//` and `/* */` comments, trailing commas, Python
+/// literals and unclosed strings, objects or arrays.
+pub fn repair_json(input: &str) -> String {
+    let chars: Vec<char> = input
+        .chars()
+        .map(|c| match c {
+            '\u{201C}' | '\u{201D}' => '"',
+            '\u{2018}' | '\u{2019}' => '\'',
+            c => c,
+        })
+        .collect();
+
+    let mut out = String::with_capacity(input.len());
+    let mut closers: Vec<char> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        match c {
+            '"' | '\'' => {
+                i = read_string(&chars, i, &mut out);
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            '{' | '[' => {
+                closers.push(if c == '{' { '}' } else { ']' });
+                out.push(c);
+                i += 1;
+            }
+            '}' | ']' => {
+                drop_trailing_comma(&mut out);
+                closers.pop();
+                out.push(c);
+                i += 1;
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '.' | '+' | '-')) {
+                    out.push(chars[i]);
+                    i += 1;
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                let next = chars[i..].iter().find(|c| !c.is_whitespace());
+                if next == Some(&':') {
+                    out.push('"');
+                    out.push_str(&word);
+                    out.push('"');
+                } else {
+                    out.push_str(match word.as_str() {
+                        "True" => "true",
+                        "False" => "false",
+                        "None" => "null",
+                        other => other,
+                    });
+                }
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    drop_trailing_comma(&mut out);
+    while let Some(closer) = closers.pop() {
+        out.push(closer);
+    }
+
+    out
+}
+
+/// Copy the string starting at `chars[start]` as a double-quoted JSON
+/// string; returns the index after it. Unterminated strings are closed.
+fn read_string(chars: &[char], start: usize, out: &mut String) -> usize {
+    let quote = chars[start];
+    let mut i = start + 1;
+    out.push('"');
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                let next = chars[i + 1];
+                if next == '\'' {
+                    out.push('\'');
+                } else {
+                    out.push('\\');
+                    out.push(next);
+                }
+                i += 2;
+                continue;
+            }
+            c if c == quote => {
+                out.push('"');
+                return i + 1;
+            }
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+        i += 1;
+    }
+
+    out.push('"');
+    i
+}
+
+fn drop_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end().len();
+    if out[..trimmed].ends_with(',') {
+        out.truncate(trimmed - 1);
+    }
+}
+
+/// Parsed candidates in preference order; each snippet as-is, then repaired
+fn parsed_candidates(text: &str) -> impl Iterator<Item = Value> + '_ {
+    extract_json(text).into_iter().flat_map(|snippet| {
+        serde_json::from_str::<Value>(snippet)
+            .ok()
+            .into_iter()
+            .chain(std::iter::once_with(move || serde_json::from_str::<Value>(&repair_json(snippet)).ok()).flatten())
+    })
+}
+
+/// First JSON value found in `text`, repaired if needed
+pub fn parse_json(text: &str) -> Result<Value, StructuredError> {
+    parsed_candidates(text).next().ok_or(StructuredError::NoJson)
+}
+
+/// First JSON value in `text` that matches `T`'s schema, deserialized.
+/// When none matches, the violations of the first candidate are returned.
+pub fn parse_as<T: DeserializeOwned + JsonSchema>(text: &str) -> Result<T, StructuredError> {
+    let schema = schema_of::<T>();
+    let mut first_error: Option<Vec<SchemaViolation>> = None;
+
+    for value in parsed_candidates(text) {
+        let violations = validate(&schema, &value);
+
+        if violations.is_empty() {
+            match serde_json::from_value::<T>(value) {
+                Ok(parsed) => return Ok(parsed),
+                Err(e) => {
+                    first_error.get_or_insert_with(|| vec![SchemaViolation { path: String::new(), message: e.to_string() }]);
+                }
+            }
+        } else {
+            first_error.get_or_insert(violations);
+        }
+    }
+
+    Err(first_error.map_or(StructuredError::NoJson, StructuredError::Invalid))
+}
+
+/// Generate and parse a `T`, re-prompting up to `reprompts` times with the
+/// parse or validation error when the answer cannot be used.
+pub async fn generate_structured<T, P>(
+    provider: &P,
+    prompt: &str,
+    model: &str,
+    max_tokens: u32,
+    reprompts: usize,
+) -> Result<T>
+where
+    T: DeserializeOwned + JsonSchema,
+    P: LlmProvider + ?Sized,
+{
+    let mut current = prompt.to_string();
+
+    for attempt in 0..=reprompts {
+        let response = provider.generate(&current, model, max_tokens).await?;
+
+        match parse_as::<T>(&response.output) {
+            Ok(parsed) => return Ok(parsed),
+            Err(e) if attempt < reprompts => {
+                tracing::debug!("Structured output rejected ({}), re-prompting", e);
+                current = format!(
+                    "{}\n\nYour previous reply could not be used: {}\n\nPrevious reply:\n{}\n\n\
+                     Reply again with only the JSON.",
+                    prompt, e, response.output
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    unreachable!("the last attempt always returns")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use crate::ai::test_provider::ScriptedProvider;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct Verdict {
+        confidence: f32,
+        #[serde(default)]
+        retry: bool,
+    }
+
+    #[test]
+    fn test_extracts_fenced_and_inline_json() {
+        let fenced = "Here you go:\n```json\n{\"confidence\": 0.8}\n```\nHope it helps {really}";
+        assert_eq!(parse_as::<Verdict>(fenced), Ok(Verdict { confidence: 0.8, retry: false }));
+
+        let inline = "I'd say {\"confidence\": 0.4, \"retry\": true} overall.";
+        assert_eq!(parse_as::<Verdict>(inline), Ok(Verdict { confidence: 0.4, retry: true }));
+
+        // The first `{` belongs to prose; the second span is the answer
+        let prose = "Set {x} aside. {\"confidence\": 1}";
+        assert_eq!(parse_as::<Verdict>(prose).unwrap().confidence, 1.0);
+    }
+
+    #[test]
+    fn test_repairs_common_defects() {
+        let broken = "{confidence: 0.9, 'retry': True, // sure\n}";
+        assert_eq!(parse_as::<Verdict>(broken), Ok(Verdict { confidence: 0.9, retry: true }));
+
+        let truncated = r#"{"tool": "read_file", "args": {"path": "src/main.rs"#;
+        let value = parse_json(truncated).unwrap();
+        assert_eq!(value["args"]["path"], "src/main.rs");
+
+        assert_eq!(repair_json("[1, 2, ]"), "[1, 2]");
+        assert_eq!(repair_json("{\u{201C}a\u{201D}: 'it\\'s \"x\"'}"), r#"{"a": "it's \"x\""}"#);
+    }
+
+    /// Replies with prose first, then valid JSON once told what was wrong
+    fn stubborn() -> ScriptedProvider {
+        ScriptedProvider::answers(["I am fairly sure.", r#"{"confidence": 0.7}"#])
+    }
+
+    #[tokio::test]
+    async fn test_reprompts_with_the_error() {
+        let provider = stubborn();
+
+        let verdict: Verdict = generate_structured(&provider, "rate it", "m", 64, 1).await.unwrap();
+        assert_eq!(verdict.confidence, 0.7);
+
+        assert!(provider.prompts()[1].contains("no JSON found in the reply"));
+
+        let provider = stubborn();
+        assert!(generate_structured::<Verdict, _>(&provider, "rate it", "m", 64, 0).await.is_err());
+    }
+
+    #[test]
+    fn test_schema_violations_reported() {
+        match parse_as::<Verdict>(r#"{"confidence": "high"}"#) {
+            Err(StructuredError::Invalid(violations)) => assert_eq!(violations[0].path, "/confidence"),
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert_eq!(parse_as::<Verdict>("no json here"), Err(StructuredError::NoJson));
+    }
+}
@@ -0,0 +1,103 @@
+//! Scripted `LlmProvider` shared by the tests: answers come from a closure
+//! and every conversation it was sent is recorded.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::ai::provider::{
+    render_messages, ChatMessage, GenerationOptions, LlmProvider, LlmResponse, StreamCallback, ToolSpec,
+};
+
+type Reply = Box<dyn Fn(&[ChatMessage]) -> Result<LlmResponse> + Send + Sync>;
+
+pub struct ScriptedProvider {
+    reply: Reply,
+    conversations: Mutex<Vec<Vec<ChatMessage>>>,
+}
+
+impl ScriptedProvider {
+    /// Answers each prompt with `reply(prompt)`; tool conversations arrive
+    /// as the single prompt a model without native tools would get
+    pub fn new(reply: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        Self::fallible(move |prompt| Ok(reply(prompt)))
+    }
+
+    /// Like `new`, for scripts where a generation fails
+    pub fn fallible(reply: impl Fn(&str) -> Result<String> + Send + Sync + 'static) -> Self {
+        Self {
+            reply: Box::new(move |messages| reply(&render_messages(messages)).map(text)),
+            conversations: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Answers with `answers` in order
+    pub fn answers<S: Into<String>>(answers: impl IntoIterator<Item = S>) -> Self {
+        let answers: Mutex<VecDeque<String>> = Mutex::new(answers.into_iter().map(Into::into).collect());
+        Self::new(move |_| answers.lock().unwrap().pop_front().expect("no scripted answer left"))
+    }
+
+    /// Answers each conversation as a model with native tool calling would
+    pub fn chat(reply: impl Fn(&[ChatMessage]) -> LlmResponse + Send + Sync + 'static) -> Self {
+        Self {
+            reply: Box::new(move |messages| Ok(reply(messages))),
+            conversations: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Every conversation received, in order
+    pub fn conversations(&self) -> Vec<Vec<ChatMessage>> {
+        self.conversations.lock().unwrap().clone()
+    }
+
+    /// Every conversation received, as one prompt each
+    pub fn prompts(&self) -> Vec<String> {
+        self.conversations().iter().map(|c| render_messages(c)).collect()
+    }
+
+    fn answer(&self, messages: Vec<ChatMessage>, model: &str) -> Result<LlmResponse> {
+        let response = (self.reply)(&messages);
+        self.conversations.lock().unwrap().push(messages);
+
+        Ok(LlmResponse { model: model.to_string(), ..response? })
+    }
+}
+
+/// Plain text reply without native tool calls
+pub fn text(output: impl Into<String>) -> LlmResponse {
+    LlmResponse { output: output.into(), model: String::new(), tokens_used: None, tool_calls: vec![] }
+}
+
+#[async_trait]
+impl LlmProvider for ScriptedProvider {
+    async fn generate(&self, prompt: &str, model: &str, _max_tokens: u32) -> Result<LlmResponse> {
+        self.answer(vec![ChatMessage::user(prompt)], model)
+    }
+
+    async fn generate_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        _max_tokens: u32,
+        _options: &GenerationOptions,
+        _tools: &[ToolSpec],
+    ) -> Result<LlmResponse> {
+        self.answer(messages.to_vec(), model)
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        model: &str,
+        max_tokens: u32,
+        _on_token: Option<StreamCallback>,
+    ) -> Result<LlmResponse> {
+        self.generate(prompt, model, max_tokens).await
+    }
+
+    async fn health(&self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,16 +1,107 @@
-﻿use crate::event::event::{AxonEvent, WorkerHealth};
-pub use crate::ai::model_router::ToolCall;
+﻿//! The one place tool calls are read from model output: native
+//! `tool_calls` from the provider when present, else JSON in the text.
 
-pub struct ToolRouter;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-pub fn detect_tool_call(_text: &str) -> Option<ToolCall> {
-    None
+use crate::ai::structured::json_values;
+
+/// Format standard pentru tool call generat de AI
+///
+/// Modelul trebuie să genereze ceva de genul:
+///
+/// ```json
+/// {
+///   "tool": "run_shell",
+///   "args": {
+///       "command": "cargo build"
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ToolCall {
+    pub tool: String,
+    pub args: Value,
+}
+
+/// Detect JSON tool call inside AI output.
+/// Returns Some(ToolCall) if valid JSON tool detected.
+pub fn detect_tool_call(output: &str) -> Option<ToolCall> {
+    detect_tool_calls(output).into_iter().next()
+}
+
+/// Every tool call written in `output`, in order: bare or fenced objects
+/// with `tool` and `args`, and arrays of them
+pub fn detect_tool_calls(output: &str) -> Vec<ToolCall> {
+    let mut calls = Vec::new();
+
+    for value in json_values(output) {
+        match value {
+            Value::Array(items) => calls.extend(items.into_iter().filter_map(as_tool_call)),
+            other => calls.extend(as_tool_call(other)),
+        }
+    }
+
+    calls
+}
+
+/// Tool calls of a provider response: the native ones, else those written
+/// in the text by models without native tool support
+pub fn response_tool_calls(response: &crate::ai::provider::LlmResponse) -> Vec<ToolCall> {
+    if response.tool_calls.is_empty() {
+        detect_tool_calls(&response.output)
+    } else {
+        response.tool_calls.clone()
+    }
 }
 
-pub async fn handle_potential_tool_call(_text: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let _ = AxonEvent::WorkerStatus { 
-        name: "tool_detector".to_string(), 
-        health: WorkerHealth::Healthy 
-    };
-    Ok(())
+fn as_tool_call(value: Value) -> Option<ToolCall> {
+    let object = value.as_object()?;
+    if !object.contains_key("args") {
+        return None;
+    }
+    object.get("tool")?.as_str()?;
+
+    serde_json::from_value(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::provider::LlmResponse;
+    use serde_json::json;
+
+    #[test]
+    fn test_native_calls_win_over_text() {
+        let text = r#"Let me check. ```json
+{"tool": "read_file", "args": {"path": "Cargo.toml"}}
+```"#;
+        let mut response = LlmResponse { output: text.into(), model: "m".into(), tokens_used: None, tool_calls: vec![] };
+        assert_eq!(response_tool_calls(&response)[0].args, json!({"path": "Cargo.toml"}));
+
+        let native = ToolCall { tool: "git_status".into(), args: json!({}) };
+        response.tool_calls.push(native.clone());
+        assert_eq!(response_tool_calls(&response), vec![native]);
+
+        assert!(detect_tool_call("no tools here").is_none());
+    }
+
+    #[test]
+    fn test_detects_every_call_in_order() {
+        let text = r#"First {"tool": "list_dir", "args": {}} then a fenced batch:
+```json
+[{"tool": "read_file", "args": {"path": "a.rs"}}, {"tool": "read_file", "args": {"path": "b.rs"}}]
+```
+and a sloppy one: {tool: 'git_status', args: {},}
+Not a call: {"path": "c.rs"} nor [1, 2]"#;
+
+        let calls: Vec<(String, Value)> = detect_tool_calls(text).into_iter().map(|c| (c.tool, c.args)).collect();
+        assert_eq!(calls, vec![
+            ("list_dir".to_string(), json!({})),
+            ("read_file".to_string(), json!({"path": "a.rs"})),
+            ("read_file".to_string(), json!({"path": "b.rs"})),
+            ("git_status".to_string(), json!({})),
+        ]);
+    }
 }
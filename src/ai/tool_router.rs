@@ -1,8 +1,31 @@
-﻿use anyhow::Result;
+﻿use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::ai::provider::LlmProvider;
+use crate::ai::tool_json_detector::ToolCall;
+use crate::ai::approval::{ApprovalGate, ApprovalOutcome};
+use crate::ai::multi_agent_router::PatchNode;
+use crate::ai::patch_review::{PatchReviewer, PATCH_TOOL};
+use crate::ai::provider::{LlmProvider, ToolSpec};
+use crate::ai::structured::schema_of;
 use crate::config::schema::ModelInfo;
+use crate::core::state::AppState;
+use crate::event::schema::validate;
+use crate::shell::command::{needs_approval, run_confined, run_for_ai};
+use crate::util::path::confine;
+
+/// Tool output kept for the model; the tail is cut beyond this
+const MAX_OUTPUT_CHARS: usize = 8000;
+
+/// Largest file `read_file` returns
+const MAX_READ_BYTES: usize = 64 * 1024;
 
 /// High-level streaming helper for Ollama
 pub struct StreamingEngine<P: LlmProvider> {
@@ -29,7 +52,7 @@ impl<P: LlmProvider> StreamingEngine<P> {
                 &prompt,
                 &model.name,
                 model.max_tokens,
-                Some(Box::new(|token: String| {
+                Some(Box::new(|_token: String| {
                 })),
             )
             .await?;
@@ -40,15 +63,534 @@ impl<P: LlmProvider> StreamingEngine<P> {
     }
 }
 
+/// Something the model can call with `{"tool": name, "args": {...}}`
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    /// JSON Schema of the `args` object
+    fn schema(&self) -> Value;
+    async fn execute(&self, args: Value) -> Result<String>;
+
+    /// `execute` on behalf of `agent`, for tools that need to know the caller
+    async fn execute_as(&self, _agent: &str, args: Value) -> Result<String> {
+        self.execute(args).await
+    }
 
-// === AXON_COMPAT: ToolRouter stub ===
-/// Compatibility stub.
-/// If you already have a real ToolRouter elsewhere, re-export it instead.
-#[derive(Debug, Clone)]
-pub struct ToolRouter;
+    /// Why this call needs human approval, if it does
+    fn needs_approval(&self, _args: &Value) -> Option<String> {
+        None
+    }
+
+    /// Has no side effects, so several calls may run in parallel
+    fn read_only(&self) -> bool {
+        false
+    }
+}
+
+/// Registry of callable tools, keyed by name
+#[derive(Clone, Default)]
+pub struct ToolRouter {
+    tools: BTreeMap<String, Arc<dyn Tool>>,
+    approvals: Option<Arc<ApprovalGate>>,
+    /// Tools that always need approval (`approval.dangerous_tools`)
+    dangerous: HashSet<String>,
+}
 
 impl ToolRouter {
-    pub fn new() -> Self { ToolRouter }
+    pub fn new() -> Self { Self::default() }
+
+    /// Registry with every built-in tool, working inside `root`
+    pub fn builtin(state: Arc<AppState>, root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+
+        Self::new()
+            .with_tool(RunShell { state: state.clone(), root: root.clone() })
+            .with_tool(ReadFile { root: root.clone() })
+            .with_tool(ListDir { root: root.clone() })
+            .with_tool(WriteFile { root: root.clone() })
+            .with_tool(RagSearch { state: state.clone() })
+            .with_tool(Build { state: state.clone(), root: root.clone() })
+            .with_tool(GitStatus { state, root })
+    }
+
+    /// Suspend dangerous calls until approved through `gate`
+    pub fn with_approvals(mut self, gate: Arc<ApprovalGate>, dangerous_tools: &[String]) -> Self {
+        self.approvals = Some(gate);
+        self.dangerous = dangerous_tools.iter().cloned().collect();
+        self
+    }
+
+    /// Offer `apply_patch`, which shows patches to `reviewer` before applying them
+    pub fn with_patch_review(self, reviewer: Arc<PatchReviewer>) -> Self {
+        self.with_tool(ApplyPatch { reviewer })
+    }
+
+    pub fn with_tool(mut self, tool: impl Tool + 'static) -> Self {
+        self.register(Arc::new(tool));
+        self
+    }
+
+    /// Add or replace a tool
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.insert(tool.name().to_string(), tool);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.get(name).cloned()
+    }
+
+    pub fn is_read_only(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|tool| tool.read_only())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.tools.keys().cloned().collect()
+    }
+
+    /// Validate the arguments against the tool schema, get approval when
+    /// needed, then run it on behalf of `agent`
+    pub async fn call(&self, agent: &str, call: &ToolCall) -> Result<String> {
+        let tool = self.get(&call.tool).with_context(|| format!("Unknown tool '{}'", call.tool))?;
+
+        let args = if call.args.is_null() { json!({}) } else { call.args.clone() };
+        let violations = validate(&tool.schema(), &args);
+        if !violations.is_empty() {
+            let list = violations.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("; ");
+            bail!("Invalid arguments for '{}': {}", call.tool, list);
+        }
+
+        let reason = tool.needs_approval(&args).or_else(|| {
+            self.dangerous.contains(&call.tool).then(|| "listed in approval.dangerous_tools".to_string())
+        });
+        if let Some(reason) = reason {
+            let Some(gate) = &self.approvals else {
+                bail!("'{}' needs approval ({}) but no approval channel is set up", call.tool, reason);
+            };
+
+            match gate.request(agent, &call.tool, &args, &reason).await {
+                ApprovalOutcome::Approved => {}
+                ApprovalOutcome::Denied => bail!("'{}' was denied by the user", call.tool),
+                ApprovalOutcome::TimedOut => bail!("'{}' was not approved in time", call.tool),
+            }
+        }
+
+        let output = tool.execute_as(agent, args).await?;
+        Ok(truncate(output, MAX_OUTPUT_CHARS))
+    }
+
+    /// Native tool definitions for the registered tools among `names`
+    pub fn specs(&self, names: &[String]) -> Vec<ToolSpec> {
+        names
+            .iter()
+            .filter_map(|name| self.tools.get(name))
+            .map(|tool| ToolSpec {
+                name: tool.name().to_string(),
+                description: tool.description().to_string(),
+                parameters: tool.schema(),
+            })
+            .collect()
+    }
+
+    /// One line per registered tool among `names`, e.g.
+    /// `- read_file(path: string, max_bytes?: integer): Read a text file`
+    pub fn describe(&self, names: &[String]) -> Vec<String> {
+        names
+            .iter()
+            .filter_map(|name| self.tools.get(name))
+            .map(|tool| format!("- {}({}): {}", tool.name(), signature(&tool.schema()), tool.description()))
+            .collect()
+    }
+}
+
+/// System prompt section listing the tools an agent may call
+pub fn tool_prompt(lines: &[String]) -> String {
+    format!(
+        "You can call tools. To call one, reply with only:\n\
+         {{\"tool\":\"<name>\",\"args\":{{...}}}}\n\
+         The result will be added to your context. Answer normally once you have what you need.\n\
+         Tools:\n{}",
+        lines.join("\n")
+    )
+}
+
+/// `name: type` per property, optional ones marked with `?`
+fn signature(schema: &Value) -> String {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let Some(properties) = schema["properties"].as_object() else {
+        return String::new();
+    };
+
+    properties
+        .iter()
+        .map(|(name, prop)| {
+            let ty = match &prop["type"] {
+                Value::String(t) => t.as_str(),
+                Value::Array(types) => types.iter().filter_map(Value::as_str).find(|t| *t != "null").unwrap_or("any"),
+                _ => "any",
+            };
+            let optional = if required.contains(&name.as_str()) { "" } else { "?" };
+            format!("{}{}: {}", name, optional, ty)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_args<T: DeserializeOwned>(args: Value) -> Result<T> {
+    serde_json::from_value(args).context("Invalid tool arguments")
+}
+
+fn truncate(mut text: String, max_chars: usize) -> String {
+    if let Some((cut, _)) = text.char_indices().nth(max_chars) {
+        let dropped = text[cut..].chars().count();
+        text.truncate(cut);
+        text.push_str(&format!("\n... [{} more characters truncated]", dropped));
+    }
+    text
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct RunShellArgs {
+    /// Command line, e.g. `cargo test`
+    command: String,
+}
+
+/// Whitelisted shell command (`[shell].allowed_commands`), sandboxed
+struct RunShell {
+    state: Arc<AppState>,
+    root: PathBuf,
+}
+
+#[async_trait]
+impl Tool for RunShell {
+    fn name(&self) -> &str { "run_shell" }
+
+    fn description(&self) -> &str { "Run a whitelisted shell command in the project root" }
+
+    fn schema(&self) -> Value { schema_of::<RunShellArgs>() }
+
+    async fn execute(&self, args: Value) -> Result<String> {
+        let args: RunShellArgs = parse_args(args)?;
+        run_for_ai(&args.command, &self.root, &self.state.config).await
+    }
+
+    fn needs_approval(&self, args: &Value) -> Option<String> {
+        let command = args["command"].as_str()?;
+        needs_approval(command, &self.state.config).then(|| format!("`{}` matches shell.require_approval_for", command))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ReadFileArgs {
+    path: String,
+    #[serde(default)]
+    max_bytes: Option<usize>,
+}
+
+struct ReadFile {
+    root: PathBuf,
+}
+
+#[async_trait]
+impl Tool for ReadFile {
+    fn name(&self) -> &str { "read_file" }
+
+    fn description(&self) -> &str { "Read a text file relative to the project root" }
+
+    fn schema(&self) -> Value { schema_of::<ReadFileArgs>() }
+
+    fn read_only(&self) -> bool { true }
+
+    async fn execute(&self, args: Value) -> Result<String> {
+        let args: ReadFileArgs = parse_args(args)?;
+        let path = confine(&self.root, &args.path)?;
+        let limit = args.max_bytes.unwrap_or(MAX_READ_BYTES).min(MAX_READ_BYTES);
+
+        let bytes = tokio::fs::read(&path).await.with_context(|| format!("Cannot read {}", args.path))?;
+        let mut text = String::from_utf8_lossy(&bytes[..bytes.len().min(limit)]).into_owned();
+        if bytes.len() > limit {
+            text.push_str(&format!("\n... [{} more bytes]", bytes.len() - limit));
+        }
+        Ok(text)
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ListDirArgs {
+    #[serde(default)]
+    path: Option<String>,
+}
+
+struct ListDir {
+    root: PathBuf,
+}
+
+#[async_trait]
+impl Tool for ListDir {
+    fn name(&self) -> &str { "list_dir" }
+
+    fn description(&self) -> &str { "List a directory relative to the project root; directories end with /" }
+
+    fn schema(&self) -> Value { schema_of::<ListDirArgs>() }
+
+    fn read_only(&self) -> bool { true }
+
+    async fn execute(&self, args: Value) -> Result<String> {
+        let args: ListDirArgs = parse_args(args)?;
+        let dir = confine(&self.root, args.path.as_deref().unwrap_or("."))?;
+
+        let mut entries = Vec::new();
+        let mut read = tokio::fs::read_dir(&dir).await.with_context(|| format!("Cannot list {}", dir.display()))?;
+        while let Some(entry) = read.next_entry().await? {
+            let mut name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().await?.is_dir() {
+                name.push('/');
+            }
+            entries.push(name);
+        }
+
+        entries.sort();
+        Ok(entries.join("\n"))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct WriteFileArgs {
+    path: String,
+    content: String,
 }
 
+struct WriteFile {
+    root: PathBuf,
+}
+
+#[async_trait]
+impl Tool for WriteFile {
+    fn name(&self) -> &str { "write_file" }
+
+    fn description(&self) -> &str { "Create or overwrite a file relative to the project root" }
+
+    fn schema(&self) -> Value { schema_of::<WriteFileArgs>() }
+
+    async fn execute(&self, args: Value) -> Result<String> {
+        let args: WriteFileArgs = parse_args(args)?;
+        let path = confine(&self.root, &args.path)?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, &args.content).await.with_context(|| format!("Cannot write {}", args.path))?;
+
+        Ok(format!("Wrote {} bytes to {}", args.content.len(), args.path))
+    }
+
+    fn needs_approval(&self, args: &Value) -> Option<String> {
+        Some(format!("writes {}", args["path"].as_str().unwrap_or("a file")))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct RagSearchArgs {
+    query: String,
+    #[serde(default)]
+    top_k: Option<usize>,
+}
+
+struct RagSearch {
+    state: Arc<AppState>,
+}
 
+#[async_trait]
+impl Tool for RagSearch {
+    fn name(&self) -> &str { "rag_search" }
+
+    fn description(&self) -> &str { "Search the indexed project for chunks relevant to a query" }
+
+    fn schema(&self) -> Value { schema_of::<RagSearchArgs>() }
+
+    fn read_only(&self) -> bool { true }
+
+    async fn execute(&self, args: Value) -> Result<String> {
+        let args: RagSearchArgs = parse_args(args)?;
+        let hits = crate::rag::search::retrieve(&self.state, &args.query, args.top_k.unwrap_or(5)).await;
+
+        if hits.is_empty() {
+            return Ok("No matches".into());
+        }
+        Ok(hits.join("\n\n"))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct BuildArgs {
+    #[serde(default)]
+    release: bool,
+}
+
+struct Build {
+    state: Arc<AppState>,
+    root: PathBuf,
+}
+
+#[async_trait]
+impl Tool for Build {
+    fn name(&self) -> &str { "build" }
+
+    fn description(&self) -> &str {
+        "Run cargo build in the project root and return the compiler output; cargo must be whitelisted"
+    }
+
+    fn schema(&self) -> Value { schema_of::<BuildArgs>() }
+
+    async fn execute(&self, args: Value) -> Result<String> {
+        let args: BuildArgs = parse_args(args)?;
+
+        let mut cargo_args = vec!["build".to_string(), "--color=never".to_string()];
+        if args.release {
+            cargo_args.push("--release".into());
+        }
+
+        let config = &self.state.config;
+        let limit = Duration::from_secs(config.shell.build_timeout_seconds);
+        let out = run_confined("cargo", &cargo_args, &self.root, config, limit).await?;
+        let status = match out.code {
+            Some(0) => "Build succeeded".to_string(),
+            Some(code) => format!("Build failed (exit {})", code),
+            None => "Build killed (resource limit?)".to_string(),
+        };
+
+        Ok(format!("{}\n{}{}", status, out.stdout, out.stderr))
+    }
+}
+
+struct GitStatus {
+    state: Arc<AppState>,
+    root: PathBuf,
+}
+
+#[async_trait]
+impl Tool for GitStatus {
+    fn name(&self) -> &str { "git_status" }
+
+    fn description(&self) -> &str { "Show the current branch and changed files" }
+
+    fn schema(&self) -> Value { json!({ "type": "object", "properties": {} }) }
+
+    fn read_only(&self) -> bool { true }
+
+    async fn execute(&self, _args: Value) -> Result<String> {
+        run_for_ai("git status --short --branch", &self.root, &self.state.config).await
+    }
+}
+
+/// Patch tree applied after a human reviewed its diff
+struct ApplyPatch {
+    reviewer: Arc<PatchReviewer>,
+}
+
+#[async_trait]
+impl Tool for ApplyPatch {
+    fn name(&self) -> &str { PATCH_TOOL }
+
+    fn description(&self) -> &str {
+        "Change files with a patch tree; the user reviews the diff, may approve only some actions, and nothing is written before that"
+    }
+
+    fn schema(&self) -> Value { schema_of::<PatchNode>() }
+
+    async fn execute(&self, args: Value) -> Result<String> {
+        self.execute_as("unknown", args).await
+    }
+
+    async fn execute_as(&self, agent: &str, args: Value) -> Result<String> {
+        let node: PatchNode = parse_args(args)?;
+        let result = self.reviewer.review(agent, &node).await;
+
+        match (result.outcome, result.applied) {
+            (ApprovalOutcome::Approved, Some(applied)) if applied.report.success => {
+                Ok(format!("Patch applied:\n{}", applied.report.summary()))
+            }
+            (ApprovalOutcome::Approved, Some(applied)) => {
+                bail!("Patch failed and was rolled back:\n{}", applied.report.summary())
+            }
+            (ApprovalOutcome::Denied, _) => bail!("Patch {} was rejected by the user", node.id),
+            _ => bail!("Patch {} was not reviewed in time", node.id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::AxonConfig;
+
+    fn call(tool: &str, args: Value) -> ToolCall {
+        ToolCall { tool: tool.into(), args }
+    }
+
+    #[tokio::test]
+    async fn test_file_tools_and_validation() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(AppState::new(AxonConfig::default()));
+
+        let unguarded = ToolRouter::builtin(state.clone(), dir.path());
+        let refused = unguarded.call("coder", &call("write_file", json!({"path": "a.txt", "content": ""}))).await;
+        assert!(refused.unwrap_err().to_string().contains("needs approval"));
+
+        // Approve every request, as a user watching the CLI would
+        let (tx, mut rx) = tokio::sync::broadcast::channel(16);
+        let gate = Arc::new(
+            ApprovalGate::new(tx.clone(), std::time::Duration::from_secs(5))
+                .with_audit_file(dir.path().join("audit.jsonl"))
+        );
+        tokio::spawn(gate.clone().run());
+        tokio::spawn(async move {
+            while let Ok(event) = rx.recv().await {
+                if let crate::event::event::AxonEvent::ApprovalRequested { id, .. } = event {
+                    let _ = tx.send(crate::event::event::AxonEvent::FixApproved { alert_id: id, by: None });
+                }
+            }
+        });
+        let tools = ToolRouter::builtin(state, dir.path()).with_approvals(gate, &[]);
+
+        let written = tools.call("coder", &call("write_file", json!({"path": "src/lib.rs", "content": "pub fn x() {}"}))).await;
+        assert_eq!(written.unwrap(), "Wrote 13 bytes to src/lib.rs");
+
+        assert_eq!(tools.call("coder", &call("read_file", json!({"path": "src/lib.rs"}))).await.unwrap(), "pub fn x() {}");
+        assert_eq!(tools.call("coder", &call("list_dir", Value::Null)).await.unwrap(), "audit.jsonl\nsrc/");
+
+        let escape = tools.call("coder", &call("read_file", json!({"path": "../etc/passwd"}))).await;
+        assert!(escape.unwrap_err().to_string().contains("escapes"));
+
+        let missing = tools.call("coder", &call("write_file", json!({"path": "a.txt"}))).await;
+        assert!(missing.unwrap_err().to_string().contains("Invalid arguments for 'write_file'"));
+
+        let shell = tools.call("coder", &call("run_shell", json!({"command": "rm -rf /"}))).await;
+        assert!(shell.unwrap_err().to_string().contains("not allowed"));
+
+        let lines = tools.describe(&["read_file".into(), "delegate".into()]);
+        assert_eq!(lines, vec!["- read_file(max_bytes?: integer, path: string): Read a text file relative to the project root"]);
+    }
+
+    #[tokio::test]
+    async fn test_build_and_git_obey_the_whitelist() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").arg("init").arg("-q").current_dir(dir.path()).status().unwrap();
+
+        let tools = ToolRouter::builtin(Arc::new(AppState::new(AxonConfig::default())), dir.path());
+        for tool in ["build", "git_status"] {
+            let refused = tools.call("coder", &call(tool, json!({}))).await;
+            assert!(refused.unwrap_err().to_string().contains("not allowed"), "{} ran", tool);
+        }
+
+        let mut config = AxonConfig::default();
+        config.shell.allowed_commands = vec!["git".into()];
+        let tools = ToolRouter::builtin(Arc::new(AppState::new(config)), dir.path());
+        let status = tools.call("coder", &call("git_status", json!({}))).await.unwrap();
+        assert!(status.starts_with("## "), "{}", status);
+    }
+}
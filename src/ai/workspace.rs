@@ -0,0 +1,365 @@
+//! Keeps AI changes off the user's checkout.
+//!
+//! Patches are applied in a git worktree on `isolation.branch` and
+//! committed there, one commit per `PatchNode`. The checkout only changes
+//! when the user merges the branch; discarding drops the branch and starts
+//! a fresh worktree from `HEAD`. Everything goes through the git CLI.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::ai::approval::is_approver;
+use crate::ai::multi_agent_router::PatchNode;
+use crate::ai::patch_executor::PatchReport;
+use crate::config::schema::{AxonConfig, IsolationConfig};
+use crate::event::bus::EventSender;
+use crate::event::event::AxonEvent;
+use crate::shell::command::run_command_in;
+
+/// Longest commit subject taken from a patch description
+const MAX_SUBJECT: usize = 72;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceAction {
+    /// List the commits waiting to be merged
+    Changes,
+    Merge,
+    Discard,
+}
+
+pub struct GitWorkspace {
+    /// The user's checkout
+    repo: PathBuf,
+    /// Worktree of the AI branch; `None` when changes go straight to `repo`
+    worktree: Option<PathBuf>,
+    config: IsolationConfig,
+    shell: AxonConfig,
+    /// One git operation at a time
+    lock: Mutex<()>,
+}
+
+impl GitWorkspace {
+    /// No isolation: AI changes are written to `repo` and not committed
+    pub fn direct(repo: impl Into<PathBuf>, config: &AxonConfig) -> Self {
+        Self {
+            repo: repo.into(),
+            worktree: None,
+            config: config.isolation.clone(),
+            shell: config.clone(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Isolate AI changes of `repo` when `isolation.enabled`, creating the
+    /// worktree (and branch) if needed
+    pub async fn open(repo: impl Into<PathBuf>, config: &AxonConfig) -> Result<Self> {
+        let mut workspace = Self::direct(repo, config);
+        if !workspace.config.enabled {
+            return Ok(workspace);
+        }
+
+        let worktree = if workspace.config.worktree_dir.is_empty() {
+            let git_dir = workspace.git(&workspace.repo, &["rev-parse", "--git-common-dir"]).await?;
+            workspace.repo.join(git_dir.trim()).join("axon-worktree")
+        } else {
+            workspace.repo.join(&workspace.config.worktree_dir)
+        };
+
+        workspace.worktree = Some(worktree);
+        workspace.create().await?;
+        Ok(workspace)
+    }
+
+    /// The user's checkout
+    pub fn repo(&self) -> &Path {
+        &self.repo
+    }
+
+    /// Where AI changes are written
+    pub fn root(&self) -> &Path {
+        self.worktree.as_deref().unwrap_or(&self.repo)
+    }
+
+    pub fn is_isolated(&self) -> bool {
+        self.worktree.is_some()
+    }
+
+    pub fn branch(&self) -> &str {
+        &self.config.branch
+    }
+
+    /// Commit everything `node` changed in the worktree; the short hash,
+    /// or `None` when not isolated or nothing changed
+    pub async fn commit(&self, node: &PatchNode, report: &PatchReport) -> Result<Option<String>> {
+        let subject = node.description.lines().next().unwrap_or_default().trim();
+        let subject = if subject.is_empty() { format!("Apply patch {}", node.id) } else { subject.to_string() };
+        let subject: String = subject.chars().take(MAX_SUBJECT).collect();
+        let body = format!("Patch {}\n\n{}", node.id, report.summary());
+
+        let _guard = self.lock.lock().await;
+        self.commit_all(&subject, &body).await
+    }
+
+    /// Commits on the AI branch that the checkout doesn't have yet
+    pub async fn changes(&self) -> Result<String> {
+        let Some(worktree) = &self.worktree else {
+            bail!("AI changes are not isolated; enable [isolation] in config.toml");
+        };
+        let _guard = self.lock.lock().await;
+
+        let range = format!("HEAD..{}", self.config.branch);
+        let log = self.git(&self.repo, &["log", "--oneline", "--no-decorate", &range]).await?;
+        let pending = self.git(worktree, &["status", "--porcelain"]).await?;
+
+        if log.trim().is_empty() && pending.trim().is_empty() {
+            return Ok(format!("No AI changes on {}", self.config.branch));
+        }
+
+        let mut out = format!("{} commit(s) on {} to merge:\n{}", log.lines().count(), self.config.branch, log.trim_end());
+        let stat = self.git(&self.repo, &["diff", "--stat", &format!("HEAD...{}", self.config.branch)]).await?;
+        if !stat.trim().is_empty() {
+            out.push_str(&format!("\n{}", stat.trim_end()));
+        }
+        if !pending.trim().is_empty() {
+            out.push_str(&format!("\nNot committed yet (committed on merge):\n{}", pending.trim_end()));
+        }
+        Ok(out)
+    }
+
+    /// Merge the AI branch into the checkout's current branch
+    pub async fn merge(&self) -> Result<String> {
+        let Some(worktree) = &self.worktree else {
+            bail!("AI changes are not isolated; nothing to merge");
+        };
+        let _guard = self.lock.lock().await;
+
+        // Files written by tools outside a patch
+        self.commit_all("Uncommitted AI changes", "Written outside a reviewed patch").await?;
+
+        let range = format!("HEAD..{}", self.config.branch);
+        let count: usize = self.git(&self.repo, &["rev-list", "--count", &range]).await?.trim().parse().unwrap_or(0);
+        if count == 0 {
+            return Ok(format!("Nothing to merge from {}", self.config.branch));
+        }
+
+        let message = format!("Merge AI changes from {}", self.config.branch);
+        if let Err(e) = self.git(&self.repo, &["merge", "--no-ff", "--no-edit", "-m", &message, &self.config.branch]).await {
+            let _ = self.git(&self.repo, &["merge", "--abort"]).await;
+            return Err(e.context("Merge failed; the checkout was left as it was"));
+        }
+
+        // Let the AI continue from what was merged
+        let head = self.git(&self.repo, &["rev-parse", "HEAD"]).await?;
+        if let Err(e) = self.git(worktree, &["merge", "--ff-only", "-q", head.trim()]).await {
+            warn!("AI worktree not fast-forwarded to the merge: {:#}", e);
+        }
+
+        info!("Merged {} AI commit(s) from {}", count, self.config.branch);
+        Ok(format!("Merged {} commit(s) from {}", count, self.config.branch))
+    }
+
+    /// Drop the AI branch and its changes; the worktree restarts from `HEAD`
+    pub async fn discard(&self) -> Result<String> {
+        let Some(worktree) = &self.worktree else {
+            bail!("AI changes are not isolated; nothing to discard");
+        };
+        let _guard = self.lock.lock().await;
+
+        let range = format!("HEAD..{}", self.config.branch);
+        let count: usize = match self.git(&self.repo, &["rev-list", "--count", &range]).await {
+            Ok(count) => count.trim().parse().unwrap_or(0),
+            Err(_) => 0,
+        };
+
+        // A worktree deleted by hand is only pruned, so the branch still goes
+        self.git(&self.repo, &["worktree", "prune"]).await?;
+        if worktree.join(".git").exists() {
+            let path = worktree.to_string_lossy();
+            self.git(&self.repo, &["worktree", "remove", "--force", &path]).await?;
+        }
+        let branch = format!("refs/heads/{}", self.config.branch);
+        if self.git(&self.repo, &["rev-parse", "--verify", "--quiet", &branch]).await.is_ok() {
+            self.git(&self.repo, &["branch", "-D", &self.config.branch]).await?;
+        }
+        self.create().await?;
+
+        info!("Discarded {} AI commit(s) from {}", count, self.config.branch);
+        Ok(format!("Discarded {} commit(s); {} restarts from HEAD", count, self.config.branch))
+    }
+
+    pub async fn handle(&self, action: WorkspaceAction) -> Result<String> {
+        match action {
+            WorkspaceAction::Changes => self.changes().await,
+            WorkspaceAction::Merge => self.merge().await,
+            WorkspaceAction::Discard => self.discard().await,
+        }
+    }
+
+    /// Answer `WorkspaceRequested` events from the CLI and the dashboard;
+    /// only approvers may merge or discard
+    pub async fn run(self: Arc<Self>, tx: EventSender) -> Result<()> {
+        let mut rx = tx.subscribe();
+
+        loop {
+            let (request_id, origin, action) = match rx.recv().await {
+                Ok(AxonEvent::WorkspaceRequested { request_id, origin, action }) => (request_id, origin, action),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Workspace lagged, skipped {} events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            };
+
+            let text = if action != WorkspaceAction::Changes && !is_approver(&self.shell, &origin) {
+                format!("Only an approver can {} the AI changes", if action == WorkspaceAction::Merge { "merge" } else { "discard" })
+            } else {
+                match self.handle(action).await {
+                    Ok(text) => text,
+                    Err(e) => format!("{:?} failed: {:#}", action, e),
+                }
+            };
+            let _ = tx.send(AxonEvent::CommandReply { request_id, origin, text, model: None });
+        }
+    }
+
+    /// Add the worktree, reusing the branch when it already exists
+    async fn create(&self) -> Result<()> {
+        let Some(worktree) = &self.worktree else {
+            return Ok(());
+        };
+        if worktree.join(".git").exists() {
+            return Ok(());
+        }
+
+        self.git(&self.repo, &["worktree", "prune"]).await?;
+
+        let path = worktree.to_string_lossy();
+        let branch = format!("refs/heads/{}", self.config.branch);
+        if self.git(&self.repo, &["rev-parse", "--verify", "--quiet", &branch]).await.is_ok() {
+            self.git(&self.repo, &["worktree", "add", &path, &self.config.branch]).await?;
+        } else {
+            self.git(&self.repo, &["worktree", "add", "-b", &self.config.branch, &path, "HEAD"]).await?;
+        }
+
+        info!("AI changes go to {} at {}", self.config.branch, worktree.display());
+        Ok(())
+    }
+
+    /// Commit all changes in the worktree; the caller holds the lock
+    async fn commit_all(&self, subject: &str, body: &str) -> Result<Option<String>> {
+        let Some(worktree) = &self.worktree else {
+            return Ok(None);
+        };
+
+        self.git(worktree, &["add", "-A"]).await?;
+        if self.git(worktree, &["status", "--porcelain"]).await?.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let name = format!("user.name={}", self.config.author_name);
+        let email = format!("user.email={}", self.config.author_email);
+        self.git(worktree, &["-c", &name, "-c", &email, "commit", "-q", "-m", subject, "-m", body]).await?;
+
+        let hash = self.git(worktree, &["rev-parse", "--short", "HEAD"]).await?.trim().to_string();
+        info!("Committed {} on {}: {}", hash, self.config.branch, subject);
+        Ok(Some(hash))
+    }
+
+    /// Run git in `dir`; a non-zero exit is an error carrying stderr
+    async fn git(&self, dir: &Path, args: &[&str]) -> Result<String> {
+        let owned: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let (stdout, stderr, code) = run_command_in("git", &owned, Some(dir), &self.shell)
+            .await
+            .with_context(|| format!("git {}", args.join(" ")))?;
+
+        if code != 0 {
+            bail!("git {} failed: {}", args.join(" "), stderr.trim());
+        }
+        Ok(stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::patch_executor::PatchExecutor;
+    use crate::ai::multi_agent_router::{PatchAction, PatchType};
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let out = Command::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(out.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_no_silent_fallback_to_the_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(GitWorkspace::open(dir.path(), &AxonConfig::default()).await.is_err());
+
+        let mut config = AxonConfig::default();
+        config.isolation.enabled = false;
+        let workspace = GitWorkspace::open(dir.path(), &config).await.unwrap();
+        assert!(!workspace.is_isolated());
+    }
+
+    #[tokio::test]
+    async fn test_commit_merge_and_discard() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init", "-q", "-b", "main"]);
+        git(repo, &["config", "user.name", "Dev"]);
+        git(repo, &["config", "user.email", "dev@example.com"]);
+        std::fs::write(repo.join("lib.rs"), "fn a() {}\n").unwrap();
+        git(repo, &["add", "-A"]);
+        git(repo, &["commit", "-q", "-m", "init"]);
+
+        // Isolation needs no configuration
+        let config = AxonConfig::default();
+        let workspace = GitWorkspace::open(repo, &config).await.unwrap();
+        assert!(workspace.root().starts_with(repo.join(".git")));
+
+        let mut node = PatchNode::new("p1", "Add b()");
+        node.actions.push(PatchAction {
+            file_path: "lib.rs".into(),
+            description: "add b".into(),
+            change_type: PatchType::ModifyFile,
+            content: Some("fn a() {}\nfn b() {}\n".into()),
+            search: None,
+        });
+        let outcome = PatchExecutor::new(workspace.root()).apply(&node);
+        assert!(workspace.commit(&node, &outcome.report).await.unwrap().is_some());
+
+        // The checkout is untouched until the merge
+        assert_eq!(std::fs::read_to_string(repo.join("lib.rs")).unwrap(), "fn a() {}\n");
+        assert!(git(repo, &["status", "--porcelain"]).is_empty());
+        assert!(workspace.changes().await.unwrap().contains("Add b()"));
+
+        assert_eq!(workspace.merge().await.unwrap(), "Merged 1 commit(s) from axon/ai");
+        assert_eq!(std::fs::read_to_string(repo.join("lib.rs")).unwrap(), "fn a() {}\nfn b() {}\n");
+
+        std::fs::write(workspace.root().join("junk.rs"), "x").unwrap();
+        let junk = PatchNode::new("p2", "Junk");
+        workspace.commit(&junk, &PatchExecutor::new(workspace.root()).apply(&junk).report).await.unwrap();
+        assert!(workspace.discard().await.unwrap().starts_with("Discarded 1 commit(s)"));
+        assert!(!workspace.root().join("junk.rs").exists());
+        assert!(workspace.root().join("lib.rs").exists());
+
+        // A worktree removed by hand does not keep the branch alive
+        std::fs::write(workspace.root().join("junk.rs"), "x").unwrap();
+        workspace.commit(&junk, &PatchExecutor::new(workspace.root()).apply(&junk).report).await.unwrap();
+        std::fs::remove_dir_all(workspace.root()).unwrap();
+        assert!(workspace.discard().await.unwrap().starts_with("Discarded 1 commit(s)"));
+        assert!(workspace.root().join("lib.rs").exists());
+        assert!(!workspace.root().join("junk.rs").exists());
+    }
+}
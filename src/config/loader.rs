@@ -1,7 +1,10 @@
 ﻿use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use once_cell::sync::OnceCell;
 
 use crate::config::schema::AxonConfig;
 
@@ -14,7 +17,54 @@ pub fn load_config(path: &Path) -> Result<AxonConfig> {
     Ok(config)
 }
 
-pub fn get_config() -> crate::config::schema::AxonConfig {
-    // Fallback pentru compilare
-    unimplemented!("Config must be loaded via load_config() first");
+static GLOBAL_CONFIG: OnceCell<AxonConfig> = OnceCell::new();
+
+/// Make the loaded config available to code without access to `AppState`.
+/// Only the first call has an effect.
+pub fn set_global_config(config: AxonConfig) {
+    let _ = GLOBAL_CONFIG.set(config);
+}
+
+/// Config registered with `set_global_config`, or defaults before startup
+pub fn get_config() -> AxonConfig {
+    GLOBAL_CONFIG.get().cloned().unwrap_or_default()
+}
+
+/// Watch the config file and call `on_change` with every version that parses.
+/// Invalid edits are logged and skipped so the running config stays in place.
+pub async fn watch_config<F>(path: PathBuf, mut on_change: F) -> Result<()>
+where
+    F: FnMut(AxonConfig) + Send,
+{
+    let (watch_tx, mut watch_rx) = tokio::sync::mpsc::channel(16);
+
+    let mut watcher = notify::recommended_watcher(move |res| {
+        let _ = watch_tx.blocking_send(res);
+    })?;
+
+    // Watch the directory: editors often replace the file instead of writing it
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    while let Some(res) = watch_rx.recv().await {
+        let Ok(event): notify::Result<notify::Event> = res else { continue };
+
+        let touched = (event.kind.is_modify() || event.kind.is_create())
+            && event.paths.iter().any(|p| p.file_name() == path.file_name());
+        if !touched {
+            continue;
+        }
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        match load_config(&path) {
+            Ok(config) => {
+                tracing::info!("Config reloaded from {}", path.display());
+                on_change(config);
+            }
+            Err(e) => tracing::warn!("Config reload skipped: {:#}", e),
+        }
+    }
+
+    Ok(())
 }
@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use crate::ai::model_router::AiTaskType;
+use crate::ai::provider::GenerationOptions;
+use crate::orchestrator::classifier::CommandClass;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct AxonConfig {
@@ -8,6 +12,14 @@ pub struct AxonConfig {
     pub telegram: TelegramConfig,
     pub filesystem: FileConfig,
     pub shell: ShellConfig,
+    pub classifier: ClassifierConfig,
+    pub agents: Vec<AgentConfig>,
+    pub agent_routing: AgentRoutingConfig,
+    pub reflection: ReflectionConfig,
+    pub tool_loop: ToolLoopConfig,
+    pub approval: ApprovalConfig,
+    pub repair: RepairConfig,
+    pub isolation: IsolationConfig,
 }
 
 impl Default for AxonConfig {
@@ -18,6 +30,14 @@ impl Default for AxonConfig {
             telegram: TelegramConfig::default(),
             filesystem: FileConfig::default(),
             shell: ShellConfig::default(),
+            classifier: ClassifierConfig::default(),
+            agents: AgentConfig::defaults(),
+            agent_routing: AgentRoutingConfig::default(),
+            reflection: ReflectionConfig::default(),
+            tool_loop: ToolLoopConfig::default(),
+            approval: ApprovalConfig::default(),
+            repair: RepairConfig::default(),
+            isolation: IsolationConfig::default(),
         }
     }
 }
@@ -39,6 +59,10 @@ pub struct AiConfig {
     pub embed_model: ModelInfo,
 
     pub max_tokens: u32,
+
+    /// Offer tools through Ollama's native tool calling; models without
+    /// support fall back to JSON written in the answer
+    pub native_tools: bool,
 }
 
 impl Default for AiConfig {
@@ -57,6 +81,7 @@ impl Default for AiConfig {
                 max_tokens: 2048,
             },
             max_tokens: 4096,
+            native_tools: true,
         }
     }
 }
@@ -77,38 +102,20 @@ impl Default for ModelInfo {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(default)]
 pub struct DiscordConfig {
     pub token: String,
     pub channel_id: u64,
 }
 
-impl Default for DiscordConfig {
-    fn default() -> Self {
-        Self {
-            token: String::new(),
-            channel_id: 0,
-        }
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(default)]
 pub struct TelegramConfig {
     pub bot_token: String,
     pub admin_id: i64,
 }
 
-impl Default for TelegramConfig {
-    fn default() -> Self {
-        Self {
-            bot_token: String::new(),
-            admin_id: 0,
-        }
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct FileConfig {
@@ -128,7 +135,11 @@ impl Default for FileConfig {
 pub struct ShellConfig {
     pub allowed_commands: Vec<String>,
     pub timeout_seconds: u64,
+    /// Timeout of the AI `build` tool; cargo builds outlast `timeout_seconds`
+    pub build_timeout_seconds: u64,
     pub require_approval_for: Vec<String>,
+    /// Limits for commands run by AI tools (`[shell.sandbox]`)
+    pub sandbox: SandboxConfig,
 }
 
 impl Default for ShellConfig {
@@ -136,7 +147,369 @@ impl Default for ShellConfig {
         Self {
             allowed_commands: vec![],
             timeout_seconds: 60,
+            build_timeout_seconds: 600,
             require_approval_for: vec![],
+            sandbox: SandboxConfig::default(),
+        }
+    }
+}
+
+/// `[shell.sandbox]`: how AI-issued commands are confined.
+/// The rlimits apply on Linux only.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SandboxConfig {
+    /// Off runs AI commands like any other whitelisted command
+    pub enabled: bool,
+    /// Environment variables passed through; everything else is dropped
+    pub env_allow: Vec<String>,
+    /// CPU time per process (RLIMIT_CPU)
+    pub cpu_seconds: u64,
+    /// Address space per process (RLIMIT_AS)
+    pub memory_mb: u64,
+    /// Processes of the user (RLIMIT_NPROC); counts ones started outside too
+    pub max_processes: u64,
+    /// Largest file a command may write (RLIMIT_FSIZE)
+    pub max_file_mb: u64,
+    /// Captured stdout and stderr, each
+    pub max_output_bytes: usize,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            env_allow: ["PATH", "HOME", "USER", "LANG", "LC_ALL", "TERM", "TMPDIR", "CARGO_HOME", "RUSTUP_HOME"]
+                .map(String::from)
+                .to_vec(),
+            cpu_seconds: 300,
+            memory_mb: 8192,
+            max_processes: 4096,
+            max_file_mb: 1024,
+            max_output_bytes: 64 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ClassifierConfig {
+    /// Ask the model when no rule reaches `min_confidence`
+    pub llm_fallback: bool,
+    /// Model used for fallback classification (defaults to `ai.default_model`)
+    pub model: Option<String>,
+    pub min_confidence: f32,
+    pub rules: Vec<ClassifierRule>,
+}
+
+impl Default for ClassifierConfig {
+    fn default() -> Self {
+        Self {
+            llm_fallback: true,
+            model: None,
+            min_confidence: 0.6,
+            rules: ClassifierRule::defaults(),
+        }
+    }
+}
+
+/// One `[[classifier.rules]]` entry. A rule matches when the input starts
+/// with one of `prefixes` (as a whole word) or matches `pattern`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ClassifierRule {
+    pub name: String,
+    pub class: CommandClass,
+    pub prefixes: Vec<String>,
+    /// Regex applied to the lowercased input
+    pub pattern: Option<String>,
+    /// Higher wins when several rules match
+    pub priority: i32,
+    pub confidence: f32,
+}
+
+impl Default for ClassifierRule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            class: CommandClass::Unknown,
+            prefixes: vec![],
+            pattern: None,
+            priority: 0,
+            confidence: 0.9,
+        }
+    }
+}
+
+impl ClassifierRule {
+    pub fn defaults() -> Vec<ClassifierRule> {
+        vec![
+            ClassifierRule {
+                name: "build".into(),
+                class: CommandClass::Build,
+                prefixes: vec!["build".into(), "cargo".into(), "rebuild".into(), "compile".into()],
+                priority: 30,
+                ..Default::default()
+            },
+            ClassifierRule {
+                name: "status".into(),
+                class: CommandClass::Status,
+                prefixes: vec!["status".into(), "stat".into(), "health".into()],
+                pattern: Some(r"^workers?\s+(status|health)\b".into()),
+                priority: 30,
+                ..Default::default()
+            },
+            ClassifierRule {
+                name: "rag_search".into(),
+                class: CommandClass::RagSearch,
+                prefixes: vec!["search".into(), "find".into(), "rag".into(), "lookup".into()],
+                priority: 20,
+                ..Default::default()
+            },
+            ClassifierRule {
+                name: "question".into(),
+                class: CommandClass::AiQuery,
+                pattern: Some(r"\?\s*$".into()),
+                priority: 10,
+                confidence: 0.8,
+                ..Default::default()
+            },
+        ]
+    }
+}
+
+/// `[agent_routing]`: how requests are assigned to agents
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AgentRoutingConfig {
+    /// Let a model pick the agent from the agent descriptions
+    pub llm: bool,
+    /// Model used for routing (defaults to `ai.default_model`)
+    pub model: Option<String>,
+    /// Below this the keyword heuristics decide
+    pub min_confidence: f32,
+    /// How deep `delegate` calls may nest
+    pub max_delegation_depth: usize,
+    /// Total `delegate` calls allowed per request
+    pub max_delegations: usize,
+}
+
+impl Default for AgentRoutingConfig {
+    fn default() -> Self {
+        Self {
+            llm: false,
+            model: None,
+            min_confidence: 0.6,
+            max_delegation_depth: 2,
+            max_delegations: 4,
         }
     }
-}
\ No newline at end of file
+}
+
+/// `[reflection]`: self-check and retry for agents or requests that opt in
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReflectionConfig {
+    /// Answers scoring below this are regenerated
+    pub min_confidence: f32,
+    /// Generations per request, the first one included
+    pub max_attempts: u32,
+    /// Model that grades answers (defaults to the agent's model)
+    pub model: Option<String>,
+}
+
+impl Default for ReflectionConfig {
+    fn default() -> Self {
+        Self {
+            min_confidence: 0.7,
+            max_attempts: 3,
+            model: None,
+        }
+    }
+}
+
+/// `[tool_loop]`: budget for the tool calls made while answering one request
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ToolLoopConfig {
+    /// Tool calls per request, delegations included
+    pub max_iterations: usize,
+    /// Wall-clock limit for the whole request
+    pub timeout_seconds: u64,
+}
+
+impl Default for ToolLoopConfig {
+    fn default() -> Self {
+        Self {
+            max_iterations: 8,
+            timeout_seconds: 180,
+        }
+    }
+}
+
+/// `[repair]`: the compile-verify-revert loop that fixes build errors
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RepairConfig {
+    /// Patches tried before giving up
+    pub max_iterations: usize,
+    /// Agent asked for the patches
+    pub agent: String,
+    /// Show each patch for review before it is applied
+    pub review: bool,
+    /// Limit for one `cargo check`, which takes longer than the commands
+    /// under `shell.timeout_seconds`
+    pub check_timeout_seconds: u64,
+}
+
+impl Default for RepairConfig {
+    fn default() -> Self {
+        Self {
+            max_iterations: 3,
+            agent: "coder".into(),
+            review: true,
+            check_timeout_seconds: 600,
+        }
+    }
+}
+
+/// `[isolation]`: AI changes are committed to their own branch, checked
+/// out in a separate worktree, until the user merges them. On by default;
+/// outside a git repository set `enabled = false` to let AI changes go
+/// straight to the checkout
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct IsolationConfig {
+    /// Off: AI changes are written straight into the checkout
+    pub enabled: bool,
+    pub branch: String,
+    /// Worktree location; empty keeps it inside the `.git` directory
+    pub worktree_dir: String,
+    /// Identity of the AI commits
+    pub author_name: String,
+    pub author_email: String,
+}
+
+impl Default for IsolationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            branch: "axon/ai".into(),
+            worktree_dir: String::new(),
+            author_name: "Axon".into(),
+            author_email: "axon@localhost".into(),
+        }
+    }
+}
+
+/// `[approval]`: human sign-off for dangerous tool calls
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ApprovalConfig {
+    /// Unanswered requests are rejected after this long; keep it below
+    /// `tool_loop.timeout_seconds` so the agent sees the rejection
+    pub timeout_seconds: u64,
+    /// Tools that always need approval, on top of those marked dangerous
+    /// and shell commands matching `shell.require_approval_for`
+    pub dangerous_tools: Vec<String>,
+    /// Accept decisions from the dashboard too; off: only the CLI decides
+    pub dashboard: bool,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 120,
+            dangerous_tools: vec![],
+            dashboard: false,
+        }
+    }
+}
+
+/// One `[[agents]]` entry
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AgentConfig {
+    pub name: String,
+    /// What the agent is for; shown in listings and used for routing
+    pub description: String,
+    pub system_prompt: Option<String>,
+    /// Prompt file, relative to the config file's directory
+    pub system_prompt_file: Option<String>,
+    /// Model name; when unset the registry model for `task` is used
+    pub model: Option<String>,
+    pub task: AiTaskType,
+    /// Overrides the model's token budget
+    pub max_tokens: Option<u32>,
+    pub options: GenerationOptions,
+    /// Tool names this agent may call
+    pub tools: Vec<String>,
+    /// Lowercase phrases that route a request to this agent
+    pub keywords: Vec<String>,
+    /// Receives requests no other agent claims
+    pub fallback: bool,
+    /// Self-check answers and retry weak ones (see `[reflection]`)
+    pub reflect: bool,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            description: String::new(),
+            system_prompt: None,
+            system_prompt_file: None,
+            model: None,
+            task: AiTaskType::General,
+            max_tokens: None,
+            options: GenerationOptions::default(),
+            tools: vec![],
+            keywords: vec![],
+            fallback: false,
+            reflect: false,
+        }
+    }
+}
+
+impl AgentConfig {
+    /// Built-in agents used when `config.toml` defines none
+    pub fn defaults() -> Vec<AgentConfig> {
+        vec![
+            AgentConfig {
+                name: "coder".into(),
+                description: "Writes, fixes and explains code".into(),
+                system_prompt: Some("You are a senior software engineer. Provide precise code.".into()),
+                task: AiTaskType::Coding,
+                tools: ["delegate", "read_file", "list_dir", "rag_search", "build", "git_status", "apply_patch"]
+                    .map(String::from)
+                    .to_vec(),
+                keywords: vec!["code".into(), "bug".into()],
+                ..Default::default()
+            },
+            AgentConfig {
+                name: "planner".into(),
+                description: "Breaks complex tasks into actionable steps".into(),
+                system_prompt: Some("You break complex tasks into actionable plans.".into()),
+                tools: vec!["delegate".into()],
+                keywords: vec!["plan".into(), "steps".into()],
+                ..Default::default()
+            },
+            AgentConfig {
+                name: "analyst".into(),
+                description: "Analyzes problems, logs and data with structured reasoning".into(),
+                system_prompt: Some("You analyze problems step by step with structured reasoning.".into()),
+                task: AiTaskType::Analysis,
+                tools: ["read_file", "list_dir", "rag_search"].map(String::from).to_vec(),
+                keywords: vec!["analyze".into()],
+                ..Default::default()
+            },
+            AgentConfig {
+                name: "general".into(),
+                description: "General-purpose assistant".into(),
+                system_prompt: Some("You are a helpful AI assistant.".into()),
+                fallback: true,
+                ..Default::default()
+            },
+        ]
+    }
+}
@@ -0,0 +1,71 @@
+//! Terminal ingress: stdin lines become `UserCommand` events.
+//!
+//! Commands with a body (`/edit`) read the following lines verbatim until
+//! a line holding only `.`.
+
+use anyhow::Result;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use uuid::Uuid;
+
+use crate::event::bus::EventSender;
+use crate::event::event::{AxonEvent, Ingress};
+use crate::orchestrator::command::expects_body;
+
+/// Line that ends a multi-line body
+pub const BODY_END: &str = ".";
+
+/// Send every command read from `reader` until it is exhausted
+pub async fn read_commands<R: AsyncBufRead + Unpin>(reader: R, tx: &EventSender) -> Result<()> {
+    let mut lines = reader.lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let mut text = line.trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+
+        if expects_body(&text) {
+            println!("Enter the content, then a line with only '{}':", BODY_END);
+
+            while let Some(line) = lines.next_line().await? {
+                if line.trim_end() == BODY_END {
+                    break;
+                }
+                text.push('\n');
+                text.push_str(&line);
+            }
+        }
+
+        tx.send(AxonEvent::UserCommand {
+            id: Uuid::new_v4(),
+            text,
+            origin: Ingress::Cli,
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::command::{parse, CommandRequest};
+
+    #[tokio::test]
+    async fn test_edit_reads_its_body_until_dot() {
+        let (tx, mut rx) = tokio::sync::broadcast::channel(8);
+        let input = "/edit ab12 2\nfn main() {\n    run();\n}\n.\n\n/status\n";
+
+        read_commands(input.as_bytes(), &tx).await.unwrap();
+
+        let mut requests = Vec::new();
+        while let Ok(AxonEvent::UserCommand { text, origin, .. }) = rx.try_recv() {
+            assert_eq!(origin, Ingress::Cli);
+            requests.push(parse(&text).unwrap());
+        }
+        assert_eq!(requests, vec![
+            CommandRequest::Edit { id: "ab12".into(), action: 2, content: "fn main() {\n    run();\n}".into() },
+            CommandRequest::Status,
+        ]);
+    }
+}
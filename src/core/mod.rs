@@ -1,4 +1,5 @@
-﻿pub mod runtime;
+﻿pub mod cli;
+pub mod runtime;
 pub mod shutdown;
 pub mod state;
 pub mod ws_bridge;
@@ -1,6 +1,4 @@
 use anyhow::Result;
-use tracing::{info, error};
-use std::io::{self, Write};
 use crate::ai::ollama::OllamaProvider; // Import the provider that we previously fixed
 
 pub struct AxonRuntime {
@@ -3,11 +3,13 @@ use tokio::sync::RwLock;
 use std::collections::HashMap;
 use crate::config::schema::AxonConfig;
 use crate::event::event::WorkerHealth; // Folosim tipul centralizat
+use crate::rag::vector_store::VectorStore;
 
 pub struct AppState {
     pub config: AxonConfig,
     pub worker_status: Arc<RwLock<HashMap<String, WorkerHealth>>>,
     pub rag_indexed: Arc<RwLock<u64>>,
+    pub vector_store: Arc<RwLock<VectorStore>>,
 }
 
 impl AppState {
@@ -16,6 +18,7 @@ impl AppState {
             config,
             worker_status: Arc::new(RwLock::new(HashMap::new())),
             rag_indexed: Arc::new(RwLock::new(0)),
+            vector_store: Arc::new(RwLock::new(VectorStore::new())),
         }
     }
 
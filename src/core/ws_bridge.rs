@@ -3,33 +3,75 @@
     routing::get,
     extract::{
         ws::{WebSocketUpgrade, WebSocket, Message},
-        State,
+        Path, State,
     },
+    http::StatusCode,
     response::IntoResponse,
+    Json,
 };
 
 use futures::{SinkExt, StreamExt};
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use serde::Serialize;
+use schemars::JsonSchema;
 use anyhow::Result;
 use tracing::{info, debug};
 use uuid::Uuid;
 
+use crate::ai::approval::{is_approver, ApprovalOutcome};
+use crate::ai::patch_review::{PatchPreview, ReviewDecision};
+use crate::ai::workspace::WorkspaceAction;
 use crate::core::state::AppState;
-use crate::event::event::AxonEvent;
+use crate::event::event::{AxonEvent, Ingress};
+use crate::event::schema::{self, SchemaViolation, WireType, SCHEMA_VERSION};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(tag = "type", content = "payload")]
 pub enum WsEvent {
-    InitialState { rag_indexed: usize },
+    InitialState { rag_indexed: usize, schema_version: u32 },
     ChatResponse { text: String, model: String },
+    /// A tool call an agent made while answering a request
+    AgentStep {
+        request_id: Uuid,
+        agent: String,
+        step: u32,
+        tool: String,
+        output: String,
+        success: bool,
+        duration_ms: u64,
+    },
+    /// A tool call waits for `Approve` / `Deny`
+    ApprovalRequested {
+        id: String,
+        agent: String,
+        tool: String,
+        args: serde_json::Value,
+        reason: String,
+        timeout_seconds: u64,
+    },
+    ApprovalResolved { id: String, outcome: ApprovalOutcome },
+    /// A patch waits for `ReviewPatch`; resolved with `ApprovalResolved`
+    PatchReview {
+        id: String,
+        agent: String,
+        preview: PatchPreview,
+        timeout_seconds: u64,
+    },
+    /// Sent back to the client whose command failed schema validation
+    CommandRejected { errors: Vec<SchemaViolation>, schema_version: u32 },
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, JsonSchema)]
 #[serde(tag = "type", content = "payload")]
 pub enum UiCommand {
     Chat { message: String },
+    Approve { id: String },
+    Deny { id: String },
+    /// Approve all or some actions of a patch, reject it or edit an action
+    ReviewPatch { id: String, decision: ReviewDecision },
+    /// List, merge or discard the AI branch; answered with `ChatResponse`
+    Workspace { action: WorkspaceAction },
 }
 
 pub struct WsBridgeState {
@@ -51,6 +93,8 @@ pub async fn run(
 
     let app = Router::new()
         .route("/ws", get(ws_handler))
+        .route("/schema", get(schema_bundle_handler))
+        .route("/schema/:name", get(schema_handler))
         .with_state(bridge_state);
 
     info!("WebSocket listening on ws://{}", bind_addr);
@@ -68,6 +112,17 @@ async fn ws_handler(
     ws.on_upgrade(move |socket| handle_socket(socket, state))
 }
 
+async fn schema_bundle_handler() -> impl IntoResponse {
+    Json(schema::schema_bundle())
+}
+
+async fn schema_handler(Path(name): Path<String>) -> impl IntoResponse {
+    match WireType::from_name(&name) {
+        Some(wire) => Json(wire.schema()).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Unknown schema: {}", name)).into_response(),
+    }
+}
+
 async fn handle_socket(
     socket: WebSocket,
     state: Arc<WsBridgeState>,
@@ -76,7 +131,7 @@ async fn handle_socket(
     let mut event_rx = state.event_tx.subscribe();
 
     let rag_indexed = *state.app_state.rag_indexed.read().await as usize;
-    let init = WsEvent::InitialState { rag_indexed };
+    let init = WsEvent::InitialState { rag_indexed, schema_version: SCHEMA_VERSION };
 
     if let Ok(json) = serde_json::to_string(&init) {
         let _ = sender.send(Message::Text(json)).await;
@@ -86,8 +141,27 @@ async fn handle_socket(
         tokio::select! {
 
             Ok(event) = event_rx.recv() => {
-                if let AxonEvent::AiResponse { output, model, .. } = event {
-                    let msg = WsEvent::ChatResponse { text: output, model };
+                let msg = match event {
+                    AxonEvent::CommandReply { origin: Ingress::WebSocket, text, model, .. } => {
+                        let model = model.unwrap_or_else(|| "axon".into());
+                        Some(WsEvent::ChatResponse { text, model })
+                    }
+                    AxonEvent::ToolStep { request_id, agent, step, tool, output, success, duration_ms, .. } => {
+                        Some(WsEvent::AgentStep { request_id, agent, step, tool, output, success, duration_ms })
+                    }
+                    AxonEvent::ApprovalRequested { id, agent, tool, args, reason, timeout_seconds } => {
+                        Some(WsEvent::ApprovalRequested { id, agent, tool, args, reason, timeout_seconds })
+                    }
+                    AxonEvent::ApprovalResolved { id, outcome } => {
+                        Some(WsEvent::ApprovalResolved { id, outcome })
+                    }
+                    AxonEvent::PatchReviewRequested { id, agent, preview, timeout_seconds } => {
+                        Some(WsEvent::PatchReview { id, agent, preview, timeout_seconds })
+                    }
+                    _ => None,
+                };
+
+                if let Some(msg) = msg {
                     if let Ok(json) = serde_json::to_string(&msg) {
                         if sender.send(Message::Text(json)).await.is_err() {
                             break;
@@ -98,15 +172,50 @@ async fn handle_socket(
 
             Some(Ok(msg)) = receiver.next() => {
                 if let Message::Text(text) = msg {
-                    if let Ok(cmd) = serde_json::from_str::<UiCommand>(&text) {
-                        if let UiCommand::Chat { message } = cmd {
-                            let _ = state.event_tx.send(AxonEvent::AiRequest {
+                    let command = schema::parse_ui_command(&text).and_then(|command| match command {
+                        UiCommand::Approve { .. } | UiCommand::Deny { .. } | UiCommand::ReviewPatch { .. }
+                            if !is_approver(&state.app_state.config, &Ingress::WebSocket) =>
+                        {
+                            Err(vec![SchemaViolation {
+                                path: "/type".into(),
+                                message: "decisions from the dashboard are off (approval.dashboard)".into(),
+                            }])
+                        }
+                        command => Ok(command),
+                    });
+                    match command {
+                        Ok(UiCommand::Chat { message }) => {
+                            let _ = state.event_tx.send(AxonEvent::UserCommand {
                                 id: Uuid::new_v4(),
-                                prompt: message,
-                                model: None,
-                                context: None,
+                                text: message,
+                                origin: Ingress::WebSocket,
+                            });
+                        }
+                        Ok(UiCommand::Approve { id }) => {
+                            let _ = state.event_tx.send(AxonEvent::FixApproved { alert_id: id, by: Some(Ingress::WebSocket) });
+                        }
+                        Ok(UiCommand::Deny { id }) => {
+                            let _ = state.event_tx.send(AxonEvent::FixDenied { alert_id: id, by: Some(Ingress::WebSocket) });
+                        }
+                        Ok(UiCommand::ReviewPatch { id, decision }) => {
+                            let _ = state.event_tx.send(AxonEvent::PatchReviewDecided { id, decision, by: Some(Ingress::WebSocket) });
+                        }
+                        Ok(UiCommand::Workspace { action }) => {
+                            let _ = state.event_tx.send(AxonEvent::WorkspaceRequested {
+                                request_id: Uuid::new_v4(),
+                                origin: Ingress::WebSocket,
+                                action,
                             });
                         }
+                        Err(errors) => {
+                            debug!("Rejected UI command: {:?}", errors);
+                            let msg = WsEvent::CommandRejected { errors, schema_version: SCHEMA_VERSION };
+                            if let Ok(json) = serde_json::to_string(&msg) {
+                                if sender.send(Message::Text(json)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
                     }
                 }
             }
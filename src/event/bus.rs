@@ -1,9 +1,12 @@
-﻿use tokio::sync::broadcast;
+﻿use tokio::sync::{broadcast, mpsc};
 use crate::event::event::AxonEvent;
 
 pub type EventSender = broadcast::Sender<AxonEvent>;
 pub type EventReceiver = broadcast::Receiver<AxonEvent>;
 
+/// Direct queue into the AI runtime (`ai::patch_tree::run`)
+pub type AiSender = mpsc::Sender<AxonEvent>;
+
 pub fn create_event_bus(capacity: usize) -> (EventSender, EventReceiver) {
     broadcast::channel(capacity)
 }
@@ -1,8 +1,17 @@
-﻿use serde::{Deserialize, Serialize};
+﻿use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+use crate::ai::approval::ApprovalOutcome;
+use crate::ai::delegation::TraceNode;
+use crate::ai::memory::conversation_embeddings::JobStatus;
+use crate::ai::model_router::{AiTaskType, RoutingDecision};
+use crate::ai::patch_review::{PatchPreview, ReviewDecision};
+use crate::ai::workspace::WorkspaceAction;
+use crate::orchestrator::classifier::CommandClass;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub enum WorkerHealth {
     Running,
     Idle,
@@ -11,7 +20,7 @@ pub enum WorkerHealth {
     Error(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub enum LogLevel {
     Info,
     Warn,
@@ -33,7 +42,7 @@ impl LogLevel {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AlertRecord {
     pub id: Uuid,
     pub level: LogLevel,
@@ -44,13 +53,27 @@ pub struct AlertRecord {
     pub resolved: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Where a user command came from, so the reply can go back the same way
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema)]
+pub enum Ingress {
+    Cli,
+    WebSocket,
+    Telegram { chat_id: i64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum AxonEvent {
     AiRequest { 
         id: Uuid, 
         prompt: String, 
         model: Option<String>, 
         context: Option<String>,
+        /// Drives model routing; inferred from the prompt when absent
+        #[serde(default)]
+        task: Option<AiTaskType>,
+        /// Force self-reflection on or off; the agent's setting when absent
+        #[serde(default)]
+        reflect: Option<bool>,
     },
     AiResponse { 
         request_id: Uuid, 
@@ -58,6 +81,17 @@ pub enum AxonEvent {
         model: String,
         context: Option<String>,
         response: String, 
+        #[serde(default)]
+        routing: Option<RoutingDecision>,
+        /// Agents involved in the answer, including delegations
+        #[serde(default)]
+        trace: Option<Box<TraceNode>>,
+        /// Self-reflection score of the final answer, when reflection ran
+        #[serde(default)]
+        confidence: Option<f32>,
+        /// Generations it took, retries included
+        #[serde(default)]
+        attempts: Option<u32>,
     },
     WorkerStatus { name: String, health: WorkerHealth },
     LogDetected { 
@@ -85,5 +119,87 @@ pub enum AxonEvent {
         command: Option<String>, 
         args: Vec<String> 
     },
-    FixApproved { alert_id: String },
+    /// `by` is the ingress the decision came from, for the audit trail
+    FixApproved {
+        alert_id: String,
+        #[serde(default)]
+        by: Option<Ingress>,
+    },
+    FixDenied {
+        alert_id: String,
+        #[serde(default)]
+        by: Option<Ingress>,
+    },
+    /// A tool call waits for a human decision (`FixApproved` / `FixDenied`)
+    ApprovalRequested {
+        id: String,
+        agent: String,
+        tool: String,
+        args: serde_json::Value,
+        reason: String,
+        timeout_seconds: u64,
+    },
+    ApprovalResolved { id: String, outcome: ApprovalOutcome },
+    /// A patch waits for review; settled by `PatchReviewDecided`, then
+    /// announced with `ApprovalResolved` under the same id
+    PatchReviewRequested {
+        id: String,
+        agent: String,
+        preview: PatchPreview,
+        timeout_seconds: u64,
+    },
+    PatchReviewDecided {
+        id: String,
+        decision: ReviewDecision,
+        #[serde(default)]
+        by: Option<Ingress>,
+    },
+    /// Free-text command typed by a user, classified by the orchestrator
+    UserCommand {
+        id: Uuid,
+        text: String,
+        origin: Ingress,
+    },
+    /// A `UserCommand` the model classified in the background
+    CommandClassified {
+        id: Uuid,
+        text: String,
+        origin: Ingress,
+        class: CommandClass,
+    },
+    /// Answer to a `UserCommand`, addressed to the ingress that sent it
+    CommandReply {
+        request_id: Uuid,
+        origin: Ingress,
+        text: String,
+        model: Option<String>,
+    },
+    /// Ask the AI runtime to plan `goal` and execute the steps
+    PlanRequested { id: Uuid, goal: String },
+    /// Ask the AI runtime to fix the compile errors of `project`; answered
+    /// with `BuildFinished`
+    RepairRequested { id: Uuid, project: String },
+    /// List, merge or discard the AI branch; answered with `CommandReply`
+    WorkspaceRequested { request_id: Uuid, origin: Ingress, action: WorkspaceAction },
+    /// A plan step changed status; `step` is 1-based
+    JobProgress {
+        job_id: Uuid,
+        step: usize,
+        total: usize,
+        description: String,
+        status: JobStatus,
+    },
+    /// One tool call made by an agent while answering `request_id`
+    ToolStep {
+        request_id: Uuid,
+        agent: String,
+        step: u32,
+        tool: String,
+        args: serde_json::Value,
+        /// Tool result or error, shortened for display
+        output: String,
+        success: bool,
+        duration_ms: u64,
+    },
+    JobFinished { job_id: Uuid, success: bool, summary: String },
 }
@@ -1,2 +1,4 @@
 ﻿pub mod bus;
+#[allow(clippy::module_inception)]
 pub mod event;
+pub mod schema;
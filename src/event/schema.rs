@@ -0,0 +1,425 @@
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::core::ws_bridge::{UiCommand, WsEvent};
+use crate::event::event::AxonEvent;
+
+/// Version of the wire protocol. Bump on any breaking change to the
+/// shapes of `AxonEvent`, `WsEvent` or `UiCommand`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Types that cross the process boundary (bus dumps, WebSocket, CLI)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireType {
+    AxonEvent,
+    WsEvent,
+    UiCommand,
+}
+
+impl WireType {
+    pub const ALL: [WireType; 3] = [WireType::AxonEvent, WireType::WsEvent, WireType::UiCommand];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WireType::AxonEvent => "axon_event",
+            WireType::WsEvent => "ws_event",
+            WireType::UiCommand => "ui_command",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WireType> {
+        let name = name.trim_end_matches(".json");
+        Self::ALL.into_iter().find(|w| w.name() == name)
+    }
+
+    fn root_schema(&self) -> RootSchema {
+        match self {
+            WireType::AxonEvent => schema_for!(AxonEvent),
+            WireType::WsEvent => schema_for!(WsEvent),
+            WireType::UiCommand => schema_for!(UiCommand),
+        }
+    }
+
+    /// JSON Schema (draft-07) for this type, stamped with the protocol version
+    pub fn schema(&self) -> Value {
+        let mut schema = serde_json::to_value(self.root_schema()).unwrap_or_default();
+
+        if let Value::Object(map) = &mut schema {
+            map.insert("$id".into(), json!(schema_id(self.name())));
+            map.insert("x-axon-schema-version".into(), json!(SCHEMA_VERSION));
+        }
+
+        schema
+    }
+}
+
+fn schema_id(name: &str) -> String {
+    format!("urn:axon:schema:v{}:{}", SCHEMA_VERSION, name)
+}
+
+/// All wire schemas in one document, keyed by `WireType::name`
+pub fn schema_bundle() -> Value {
+    let schemas: Map<String, Value> = WireType::ALL
+        .iter()
+        .map(|w| (w.name().to_string(), w.schema()))
+        .collect();
+
+    json!({
+        "version": SCHEMA_VERSION,
+        "schemas": schemas,
+    })
+}
+
+/// One mismatch between a JSON document and a schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value ("" is the document root)
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { &self.path };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// Parse an inbound UI command, reporting schema violations instead of
+/// silently dropping malformed input.
+pub fn parse_ui_command(text: &str) -> Result<UiCommand, Vec<SchemaViolation>> {
+    let value: Value = serde_json::from_str(text).map_err(|e| {
+        vec![SchemaViolation {
+            path: String::new(),
+            message: format!("invalid JSON: {}", e),
+        }]
+    })?;
+
+    let violations = validate(&WireType::UiCommand.schema(), &value);
+    if !violations.is_empty() {
+        return Err(violations);
+    }
+
+    // The schema accepted it; serde may still disagree on details the
+    // schema cannot express, so report those the same way.
+    serde_json::from_value(value).map_err(|e| {
+        vec![SchemaViolation {
+            path: String::new(),
+            message: e.to_string(),
+        }]
+    })
+}
+
+/// Validate `instance` against a schemars-generated schema.
+///
+/// Supports the subset schemars emits: `$ref` into `definitions`,
+/// `type`, `enum`, `const`, `required`, `properties`,
+/// `additionalProperties`, `items`, `oneOf`/`anyOf`/`allOf`, and
+/// numeric `minimum`/`maximum`.
+pub fn validate(schema: &Value, instance: &Value) -> Vec<SchemaViolation> {
+    let mut out = Vec::new();
+    Validator { root: schema }.check(schema, instance, "", &mut out);
+    out.into_iter().map(|v| v.violation).collect()
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+}
+
+/// Violation plus the allowed values when it came from an `enum` check,
+/// used to merge discriminator mismatches across `oneOf` branches.
+struct Found {
+    violation: SchemaViolation,
+    allowed: Option<Vec<Value>>,
+}
+
+impl Found {
+    fn new(path: &str, message: String) -> Self {
+        Self {
+            violation: SchemaViolation { path: path.to_string(), message },
+            allowed: None,
+        }
+    }
+}
+
+impl<'a> Validator<'a> {
+    fn resolve(&self, reference: &str) -> Option<&'a Value> {
+        let pointer = reference.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+
+    fn check(&self, schema: &'a Value, instance: &Value, path: &str, out: &mut Vec<Found>) {
+        let Value::Object(schema) = schema else {
+            // `true` accepts anything, `false` rejects everything
+            if schema == &Value::Bool(false) {
+                out.push(Found::new(path, "no value is allowed here".into()));
+            }
+            return;
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match self.resolve(reference) {
+                Some(target) => self.check(target, instance, path, out),
+                None => out.push(Found::new(path, format!("unresolvable schema reference {}", reference))),
+            }
+            return;
+        }
+
+        if let Some(expected) = schema.get("type") {
+            if !type_matches(expected, instance) {
+                out.push(Found::new(
+                    path,
+                    format!("expected {}, found {}", describe_type(expected), json_type(instance)),
+                ));
+                return;
+            }
+        }
+
+        if let Some(constant) = schema.get("const") {
+            if constant != instance {
+                out.push(Found {
+                    violation: SchemaViolation {
+                        path: path.to_string(),
+                        message: format!("expected {}, found {}", constant, instance),
+                    },
+                    allowed: Some(vec![constant.clone()]),
+                });
+            }
+        }
+
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(instance) {
+                out.push(Found {
+                    violation: SchemaViolation {
+                        path: path.to_string(),
+                        message: format!("expected one of {}, found {}", list(allowed), instance),
+                    },
+                    allowed: Some(allowed.clone()),
+                });
+            }
+        }
+
+        if let Some(n) = instance.as_f64() {
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    out.push(Found::new(path, format!("{} is below the minimum {}", n, min)));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    out.push(Found::new(path, format!("{} is above the maximum {}", n, max)));
+                }
+            }
+        }
+
+        if let Value::Object(fields) = instance {
+            self.check_object(schema, fields, path, out);
+        }
+
+        if let (Some(items), Value::Array(values)) = (schema.get("items"), instance) {
+            for (i, value) in values.iter().enumerate() {
+                self.check(items, value, &format!("{}/{}", path, i), out);
+            }
+        }
+
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for branch in all {
+                self.check(branch, instance, path, out);
+            }
+        }
+
+        for key in ["oneOf", "anyOf"] {
+            if let Some(Value::Array(branches)) = schema.get(key) {
+                self.check_branches(branches, instance, path, out);
+            }
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &'a Map<String, Value>,
+        fields: &Map<String, Value>,
+        path: &str,
+        out: &mut Vec<Found>,
+    ) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !fields.contains_key(name) {
+                    out.push(Found::new(path, format!("missing required field `{}`", name)));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        for (name, value) in fields {
+            let child = format!("{}/{}", path, name);
+            match properties.and_then(|p| p.get(name)) {
+                Some(sub) => self.check(sub, value, &child, out),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        out.push(Found::new(&child, format!("unknown field `{}`", name)));
+                    }
+                    Some(extra @ Value::Object(_)) => self.check(extra, value, &child, out),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// A value must satisfy at least one branch. When none match, report
+    /// the branch that came closest; if every branch was rejected on the
+    /// same discriminator (e.g. an unknown `type` tag), list all accepted
+    /// values instead.
+    fn check_branches(&self, branches: &'a [Value], instance: &Value, path: &str, out: &mut Vec<Found>) {
+        let mut results = Vec::with_capacity(branches.len());
+
+        for branch in branches {
+            let mut found = Vec::new();
+            self.check(branch, instance, path, &mut found);
+            if found.is_empty() {
+                return;
+            }
+            results.push(found);
+        }
+
+        let discriminator = results.first().and_then(|first| {
+            first.iter().find_map(|f| f.allowed.as_ref().map(|_| f.violation.path.clone()))
+        });
+
+        if let Some(disc_path) = discriminator {
+            let rejected_by_disc = |found: &Vec<Found>| {
+                found.iter().any(|f| f.allowed.is_some() && f.violation.path == disc_path)
+            };
+
+            if results.iter().all(rejected_by_disc) {
+                let mut allowed: Vec<Value> = Vec::new();
+                for found in &results {
+                    for f in found.iter().filter(|f| f.violation.path == disc_path) {
+                        for v in f.allowed.iter().flatten() {
+                            if !allowed.contains(v) {
+                                allowed.push(v.clone());
+                            }
+                        }
+                    }
+                }
+
+                let actual = instance
+                    .pointer(&disc_path[path.len()..])
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "nothing".into());
+
+                out.push(Found {
+                    violation: SchemaViolation {
+                        path: disc_path,
+                        message: format!("expected one of {}, found {}", list(&allowed), actual),
+                    },
+                    allowed: Some(allowed),
+                });
+                return;
+            }
+        }
+
+        if let Some(best) = results.into_iter().min_by_key(|found| found.len()) {
+            out.extend(best);
+        }
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn type_matches(expected: &Value, instance: &Value) -> bool {
+    let one = |name: &str| match name {
+        "number" => instance.is_number(),
+        "integer" => instance.is_i64() || instance.is_u64(),
+        other => json_type(instance) == other,
+    };
+
+    match expected {
+        Value::String(name) => one(name),
+        Value::Array(names) => names.iter().filter_map(Value::as_str).any(one),
+        _ => true,
+    }
+}
+
+fn describe_type(expected: &Value) -> String {
+    match expected {
+        Value::String(name) => name.clone(),
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        other => other.to_string(),
+    }
+}
+
+fn list(values: &[Value]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_is_versioned() {
+        let bundle = schema_bundle();
+        assert_eq!(bundle["version"], json!(SCHEMA_VERSION));
+
+        for wire in WireType::ALL {
+            let schema = &bundle["schemas"][wire.name()];
+            assert_eq!(schema["$id"], json!(schema_id(wire.name())));
+        }
+    }
+
+    #[test]
+    fn test_valid_command_parses() {
+        let cmd = parse_ui_command(r#"{"type":"Chat","payload":{"message":"hi"}}"#);
+        assert!(matches!(cmd, Ok(UiCommand::Chat { .. })));
+    }
+
+    #[test]
+    fn test_unknown_tag_lists_variants() {
+        let errors = parse_ui_command(r#"{"type":"Chatt","payload":{}}"#).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "/type");
+        assert!(errors[0].message.contains("\"Chat\""));
+    }
+
+    #[test]
+    fn test_wrong_field_type_reports_path() {
+        let errors = parse_ui_command(r#"{"type":"Chat","payload":{"message":42}}"#).unwrap_err();
+        assert_eq!(errors[0].path, "/payload/message");
+        assert!(errors[0].message.contains("expected string"));
+    }
+
+    #[test]
+    fn test_raw_lines_is_optional_string() {
+        let schema = WireType::AxonEvent.schema();
+        let event = json!({
+            "LogDetected": {
+                "source": "file_watcher",
+                "level": "Error",
+                "message": "boom",
+                "source_file": null,
+                "raw_lines": ["not", "a", "string"],
+            }
+        });
+
+        let errors = validate(&schema, &event);
+        assert!(errors.iter().any(|e| e.path == "/LogDetected/raw_lines"));
+    }
+}
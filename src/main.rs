@@ -1,92 +1,266 @@
 ﻿use std::sync::Arc;
 use std::path::Path;
 use std::io::Write;
-use tokio::io::{self, AsyncBufReadExt, BufReader};
+use tokio::io::{self, BufReader};
 use tokio::sync::broadcast;
-use uuid::Uuid;
-
-// Modules
-mod core;
-mod ai;
-mod event;
-mod config;
-mod shell;
-mod memory;
-mod orchestrator;
-mod util;
-mod worker;
-mod workers;
-mod session;
-mod rag;
-
-use crate::event::event::AxonEvent;
-use crate::config::loader::load_config;
-use crate::core::state::AppState;
+
+use axon::event::event::{AxonEvent, Ingress};
+use axon::config::loader::load_config;
+use axon::core::state::AppState;
+use axon::event::schema::{schema_bundle, WireType};
+use axon::ai::models::ModelRegistry;
+use axon::ai::provider::OllamaProvider;
+use axon::ai::self_reflection::MultiAgentRouter;
+use axon::ai::memory::conversation_embeddings::JobStore;
+use axon::ai::memory::eval_store::{parse_export_args, EvalStore};
+use axon::ai::planner::{PlanExecutor, ToolHandler};
+use axon::ai::approval::ApprovalGate;
+use axon::ai::patch_review::PatchReviewer;
+use axon::ai::repair_loop::{cargo_check, RepairLoop};
+use axon::ai::tool_router::ToolRouter;
+use axon::ai::workspace::GitWorkspace;
+use axon::shell::command::{needs_approval, run_for_ai};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("AXON ENGINE ONLINE | Model: Qwen2.5:7b");
+    // `axon schema [axon_event|ws_event|ui_command]` prints the wire schemas and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("schema") {
+        let schema = match args.get(1) {
+            Some(name) => WireType::from_name(name)
+                .ok_or_else(|| format!("Unknown schema: {}", name))?
+                .schema(),
+            None => schema_bundle(),
+        };
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
+
+    // `axon evals export [--agent ..] [--model ..] [--min-score ..] [--max-score ..] [--out ..]`
+    if args.first().map(String::as_str) == Some("evals") {
+        if args.get(1).map(String::as_str) != Some("export") {
+            return Err("Usage: axon evals export [options]".into());
+        }
+
+        let (filter, out) = parse_export_args(&args[2..])?;
+        let store = EvalStore::new();
+        let count = match out {
+            Some(path) => store.export_jsonl(&filter, &mut std::fs::File::create(&path)?)?,
+            None => store.export_jsonl(&filter, &mut std::io::stdout().lock())?,
+        };
+        eprintln!("Exported {} evaluation records", count);
+        return Ok(());
+    }
+
+    println!("AXON ENGINE ONLINE");
 
     // 1️⃣ Load config
     let config_path = Path::new("config.toml");
     let config = load_config(config_path)
         .expect("Failed to load config.toml");
 
-    let state = Arc::new(AppState::new(config));
+    println!(
+        "Models | default: {} | coder: {}",
+        config.ai.default_model.name, config.ai.coder_model.name
+    );
+
+    axon::config::loader::set_global_config(config.clone());
+    let state = Arc::new(AppState::new(config.clone()));
 
     // 2️⃣ Channels
     let (tx, _rx) = broadcast::channel::<AxonEvent>(1024);
     let (ai_tx, ai_rx) = tokio::sync::mpsc::channel::<AxonEvent>(100);
 
+    // AI changes are committed to their own branch and worktree until /merge;
+    // they only reach the checkout directly when [isolation] is turned off
+    let workspace = Arc::new(GitWorkspace::open(".", &config).await.map_err(|e| {
+        format!("Git isolation unavailable: {:#}\nSet [isolation] enabled = false to let AI changes go to the checkout", e)
+    })?);
+    if workspace.is_isolated() {
+        println!("AI branch | {} at {}", workspace.branch(), workspace.root().display());
+    } else {
+        println!("AI branch | off, AI changes go to the checkout");
+    }
+    tokio::spawn({
+        let workspace = workspace.clone();
+        let tx = tx.clone();
+        async move {
+            if let Err(e) = workspace.run(tx).await {
+                eprintln!("Workspace error: {:?}", e);
+            }
+        }
+    });
+    let ai_root = workspace.root().to_path_buf();
+
+    // Dangerous tool calls wait for /approve, /deny or the dashboard
+    let approvals = Arc::new(ApprovalGate::from_config(tx.clone(), &config.approval));
+    tokio::spawn({
+        let approvals = approvals.clone();
+        async move {
+            if let Err(e) = approvals.run().await {
+                eprintln!("Approval gate error: {:?}", e);
+            }
+        }
+    });
+
+    // AI patches are applied only after their diff was reviewed
+    let reviewer = Arc::new(
+        PatchReviewer::from_config(tx.clone(), ai_root.clone(), &config.approval).with_workspace(workspace.clone())
+    );
+    tokio::spawn({
+        let reviewer = reviewer.clone();
+        async move {
+            if let Err(e) = reviewer.run().await {
+                eprintln!("Patch reviewer error: {:?}", e);
+            }
+        }
+    });
+
+    // Agents from `[[agents]]`; prompt files resolve next to config.toml
+    let config_dir = config_path.parent().unwrap_or(Path::new("."));
+    let agents = Arc::new(
+        MultiAgentRouter::from_config(
+            Arc::new(OllamaProvider::from_config(&config.ai)),
+            ModelRegistry::from_ai_config(&config.ai),
+            &config.agents,
+            config_dir,
+        )?
+        .with_routing(config.agent_routing.clone())
+        .with_reflection(config.reflection.clone())
+        .with_retriever(state.vector_store.clone())
+        .with_tools(Arc::new(
+            ToolRouter::builtin(state.clone(), ai_root.clone())
+                .with_approvals(approvals.clone(), &config.approval.dangerous_tools)
+                .with_patch_review(reviewer.clone())
+        ))
+        .with_tool_loop(config.tool_loop.clone())
+        .with_events(tx.clone())
+    );
+
+    println!("Agents | {}", config.agents.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", "));
+
+    // Build errors go through the compile-verify-revert loop
+    let repairs = Arc::new(
+        RepairLoop::new(agents.clone(), tx.clone(), ai_root.clone(), cargo_check(config.clone()))
+            .with_config(config.repair.clone())
+            .with_reviewer(reviewer.clone())
+            .with_workspace(workspace.clone())
+    );
+
+    let shell_config = config;
+
+    // Planner -> executor pipeline; plan steps may call whitelisted shell commands, sandboxed
+    let shell_tool: ToolHandler = Arc::new(move |line: String| {
+        let config = shell_config.clone();
+        let root = ai_root.clone();
+        Box::pin(async move {
+            if needs_approval(&line, &config) {
+                anyhow::bail!("Command requires approval: {}", line);
+            }
+            run_for_ai(&line, &root, &config).await
+        })
+    });
+    let plans = Arc::new(
+        PlanExecutor::new(agents.clone(), JobStore::new(), tx.clone()).with_tool("shell", shell_tool)
+    );
+
+    // Hot-reload agent definitions when config.toml changes
+    tokio::spawn({
+        let agents = agents.clone();
+        let path = config_path.to_path_buf();
+
+        async move {
+            let handle = tokio::runtime::Handle::current();
+            let result = axon::config::loader::watch_config(path, move |new_config| {
+                let agents = agents.clone();
+                handle.spawn(async move {
+                    match agents.reload(&new_config.agents).await {
+                        Ok(()) => tracing::info!("Agents reloaded: {} configured", new_config.agents.len()),
+                        Err(e) => tracing::warn!("Agent reload rejected: {:#}", e),
+                    }
+                });
+            }).await;
+
+            if let Err(e) = result {
+                eprintln!("Config watcher error: {:?}", e);
+            }
+        }
+    });
+
     // 3️⃣ START CORE WS BRIDGE (IMPORTANT)
     tokio::spawn({
         let state_clone = state.clone();
         let tx_clone = tx.clone();
 
         async move {
-            if let Err(e) = crate::core::ws_bridge::run(state_clone, tx_clone).await {
+            if let Err(e) = axon::core::ws_bridge::run(state_clone, tx_clone).await {
                 eprintln!("WS bridge error: {:?}", e);
             }
         }
     });
 
-    // 4️⃣ CLI RESPONSE LOGGER
+    // 4️⃣ ORCHESTRATOR + RAG RETRIEVER
+    tokio::spawn({
+        let state_clone = state.clone();
+        let tx_clone = tx.clone();
+        let ai_tx_clone = ai_tx.clone();
+
+        async move {
+            if let Err(e) = axon::orchestrator::event_loop::run(state_clone, tx_clone, ai_tx_clone).await {
+                eprintln!("Orchestrator error: {:?}", e);
+            }
+        }
+    });
+
+    tokio::spawn({
+        let state_clone = state.clone();
+        let tx_clone = tx.clone();
+
+        async move {
+            if let Err(e) = axon::rag::search::run(tx_clone, state_clone).await {
+                eprintln!("RAG retriever error: {:?}", e);
+            }
+        }
+    });
+
+    tokio::spawn({
+        let state_clone = state.clone();
+        let tx_clone = tx.clone();
+
+        async move {
+            if let Err(e) = axon::telegram::run(tx_clone, state_clone).await {
+                eprintln!("Telegram error: {:?}", e);
+            }
+        }
+    });
+
+    // 5️⃣ CLI RESPONSE LOGGER
     let mut rx_logger = tx.subscribe();
     tokio::spawn(async move {
         while let Ok(event) = rx_logger.recv().await {
-            if let AxonEvent::AiResponse { output, .. } = event {
-                println!("\n[QWEN]: {}\n", output);
+            if let AxonEvent::CommandReply { origin: Ingress::Cli, text, model, .. } = event {
+                println!("\n[{}]: {}\n", model.as_deref().unwrap_or("AXON"), text);
                 print!("> ");
                 let _ = std::io::stdout().flush();
             }
         }
     });
 
-    // 5️⃣ CLI INPUT HANDLER
-    let tx_shell = ai_tx.clone();
-    println!("Type something for Qwen and press Enter...");
+    // 6️⃣ CLI INPUT HANDLER
+    let tx_shell = tx.clone();
+    println!("Type a question or /help and press Enter...");
 
     tokio::spawn(async move {
-        let mut reader = BufReader::new(io::stdin()).lines();
         print!("> ");
         let _ = std::io::stdout().flush();
 
-        while let Ok(Some(line)) = reader.next_line().await {
-            let line = line.trim();
-
-            if !line.is_empty() {
-                let _ = tx_shell.send(AxonEvent::AiRequest {
-                    id: Uuid::new_v4(),
-                    prompt: line.to_string(),
-                    model: Some("qwen2.5:7b".to_string()),
-                    context: None,
-                }).await;
-            }
+        if let Err(e) = axon::core::cli::read_commands(BufReader::new(io::stdin()), &tx_shell).await {
+            eprintln!("CLI input error: {:?}", e);
         }
     });
 
-    // 6️⃣ Start AI runtime (blocking)
-    crate::ai::patch_tree::run(tx, state, ai_rx).await?;
+    // 7️⃣ Start AI runtime (blocking)
+    axon::ai::patch_tree::run(tx, state, agents, plans, repairs, ai_rx).await?;
 
     Ok(())
 }
\ No newline at end of file
@@ -1,7 +1,5 @@
 ﻿pub mod persistent_memory;
 pub mod vector_memory;
-pub mod tool_schema_detector;
-pub mod auto_tool_loop;
 
 
 
@@ -1,7 +1,16 @@
-﻿/// Classifies incoming text commands to route them correctly.
-/// Used by the Orchestrator Router to decide execution path.
+//! Classifies incoming text commands to route them correctly.
+//! Used by the Orchestrator Router to decide execution path.
 
-#[derive(Debug, Clone, PartialEq)]
+use anyhow::{Context, Result};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::ai::provider::LlmProvider;
+use crate::ai::structured::{generate_structured, DEFAULT_REPROMPTS};
+use crate::config::schema::{ClassifierConfig, ClassifierRule};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub enum CommandClass {
     Build,
     Status,
@@ -10,49 +19,248 @@ pub enum CommandClass {
     Unknown,
 }
 
-/// Classify raw user input into a command category.
-pub fn classify_command(input: &str) -> CommandClass {
-    if input.trim().is_empty() {
-        return CommandClass::Unknown;
+impl CommandClass {
+    /// Classes the model may choose from during fallback classification
+    pub const KNOWN: [CommandClass; 4] = [
+        CommandClass::Build,
+        CommandClass::Status,
+        CommandClass::RagSearch,
+        CommandClass::AiQuery,
+    ];
+
+    fn describe(&self) -> &'static str {
+        match self {
+            CommandClass::Build => "compile or build a project",
+            CommandClass::Status => "report worker health or engine status",
+            CommandClass::RagSearch => "search indexed project files and documents",
+            CommandClass::AiQuery => "question or task for the AI assistant",
+            CommandClass::Unknown => "none of the above",
+        }
+    }
+}
+
+/// Where a classification decision came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClassificationSource {
+    Rule(String),
+    Model(String),
+    Heuristic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub class: CommandClass,
+    pub confidence: f32,
+    pub reason: String,
+    pub source: ClassificationSource,
+}
+
+struct CompiledRule {
+    rule: ClassifierRule,
+    pattern: Option<Regex>,
+}
+
+impl CompiledRule {
+    fn matches(&self, normalized: &str) -> bool {
+        let prefix_hit = self.rule.prefixes.iter().any(|prefix| {
+            let prefix = prefix.to_lowercase();
+            normalized
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        });
+
+        prefix_hit || self.pattern.as_ref().is_some_and(|re| re.is_match(normalized))
+    }
+}
+
+/// Rule-based classifier with optional model fallback, built from `[classifier]`
+pub struct CommandClassifier {
+    rules: Vec<CompiledRule>,
+    min_confidence: f32,
+    llm_fallback: bool,
+    model: Option<String>,
+}
+
+impl CommandClassifier {
+    pub fn from_config(config: &ClassifierConfig) -> Result<Self> {
+        let mut rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                let pattern = rule
+                    .pattern
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .with_context(|| format!("Invalid pattern in classifier rule '{}'", rule.name))?;
+
+                Ok(CompiledRule { rule: rule.clone(), pattern })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Highest priority first; stable sort keeps config order within a priority
+        rules.sort_by_key(|r| std::cmp::Reverse(r.rule.priority));
+
+        Ok(Self {
+            rules,
+            min_confidence: config.min_confidence,
+            llm_fallback: config.llm_fallback,
+            model: config.model.clone(),
+        })
+    }
+
+    pub fn llm_fallback(&self) -> bool {
+        self.llm_fallback
+    }
+
+    /// Classify with rules only; falls back to a word-count heuristic
+    pub fn classify_rules(&self, input: &str) -> Classification {
+        if input.trim().is_empty() {
+            return Classification {
+                class: CommandClass::Unknown,
+                confidence: 1.0,
+                reason: "empty input".into(),
+                source: ClassificationSource::Heuristic,
+            };
+        }
+
+        if let Some(hit) = self.best_rule(input) {
+            if hit.confidence >= self.min_confidence {
+                return hit;
+            }
+        }
+
+        heuristic(input)
+    }
+
+    /// Whether `classify` would ask the model about `input`
+    pub fn needs_model(&self, input: &str) -> bool {
+        self.llm_fallback
+            && !input.trim().is_empty()
+            && self.best_rule(input).is_none_or(|b| b.confidence < self.min_confidence)
     }
 
-    let lower = input.to_lowercase();
-    let trimmed = lower.trim_start_matches('/').trim();
+    /// Classify with rules, asking the model when no rule is confident enough
+    pub async fn classify(
+        &self,
+        input: &str,
+        provider: Option<&dyn LlmProvider>,
+        default_model: &str,
+    ) -> Classification {
+        let best = self.best_rule(input);
 
-    // ---------- BUILD ----------
-    if trimmed.starts_with("build")
-        || trimmed.starts_with("cargo")
-        || trimmed.contains("compile")
-        || trimmed.contains("rebuild")
-    {
-        return CommandClass::Build;
+        if input.trim().is_empty() || best.as_ref().is_some_and(|b| b.confidence >= self.min_confidence) {
+            return self.classify_rules(input);
+        }
+
+        if let (true, Some(provider)) = (self.llm_fallback, provider) {
+            let model = self.model.as_deref().unwrap_or(default_model);
+
+            match classify_with_model(provider, model, input).await {
+                Ok(result) if result.confidence >= self.min_confidence => return result,
+                Ok(result) => {
+                    tracing::debug!("Model classification below threshold: {:?}", result);
+                    if best.as_ref().is_none_or(|b| result.confidence > b.confidence) {
+                        return result;
+                    }
+                }
+                Err(e) => tracing::warn!("Model classification failed: {}", e),
+            }
+        }
+
+        best.unwrap_or_else(|| heuristic(input))
     }
 
-    // ---------- STATUS ----------
-    if trimmed.starts_with("status")
-        || trimmed.starts_with("stat")
-        || trimmed.contains("worker")
-        || trimmed.contains("health")
-    {
-        return CommandClass::Status;
+    fn best_rule(&self, input: &str) -> Option<Classification> {
+        let normalized = normalize(input);
+
+        self.rules
+            .iter()
+            .filter(|r| r.matches(&normalized))
+            .fold(None::<&CompiledRule>, |best, r| match best {
+                Some(b) if b.rule.priority > r.rule.priority => Some(b),
+                Some(b) if b.rule.priority == r.rule.priority && b.rule.confidence >= r.rule.confidence => Some(b),
+                _ => Some(r),
+            })
+            .map(|r| Classification {
+                class: r.rule.class,
+                confidence: r.rule.confidence,
+                reason: format!("matched rule '{}'", r.rule.name),
+                source: ClassificationSource::Rule(r.rule.name.clone()),
+            })
     }
+}
 
-    // ---------- RAG SEARCH ----------
-    if trimmed.starts_with("search")
-        || trimmed.starts_with("find")
-        || trimmed.starts_with("rag")
-        || trimmed.contains("lookup")
-    {
-        return CommandClass::RagSearch;
+impl Default for CommandClassifier {
+    fn default() -> Self {
+        Self::from_config(&ClassifierConfig::default()).expect("default classifier rules are valid")
     }
+}
+
+fn normalize(input: &str) -> String {
+    input.trim().to_lowercase().trim_start_matches('/').trim().to_string()
+}
+
+/// Last resort when neither rules nor the model decided: natural sentences
+/// go to the AI, single words are unknown.
+fn heuristic(input: &str) -> Classification {
+    let normalized = normalize(input);
+    let sentence = normalized.split_whitespace().count() >= 3 || normalized.len() > 20;
 
-    // ---------- AI QUERY ----------
-    // Heuristic: questions or natural sentences
-    if trimmed.ends_with('?') || trimmed.contains(' ') || trimmed.len() > 20 {
-        return CommandClass::AiQuery;
+    Classification {
+        class: if sentence { CommandClass::AiQuery } else { CommandClass::Unknown },
+        confidence: 0.5,
+        reason: if sentence {
+            "no rule matched; looks like a natural-language request".into()
+        } else {
+            "no rule matched".into()
+        },
+        source: ClassificationSource::Heuristic,
     }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ModelVerdict {
+    class: CommandClass,
+    #[serde(default)]
+    confidence: f32,
+    #[serde(default)]
+    reason: String,
+}
+
+async fn classify_with_model(provider: &dyn LlmProvider, model: &str, input: &str) -> Result<Classification> {
+    let classes = CommandClass::KNOWN
+        .iter()
+        .map(|c| format!("- {:?}: {}", c, c.describe()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let prompt = format!(
+        "Classify the user command into exactly one class.\n\n\
+         Classes:\n{}\n- Unknown: {}\n\n\
+         Command:\n{}\n\n\
+         Respond strictly in JSON:\n\
+         {{\"class\":\"AiQuery\",\"confidence\":0.0,\"reason\":\"...\"}}",
+        classes,
+        CommandClass::Unknown.describe(),
+        input
+    );
+
+    let verdict: ModelVerdict = generate_structured(provider, &prompt, model, 128, DEFAULT_REPROMPTS)
+        .await
+        .context("Invalid model classification")?;
+
+    Ok(Classification {
+        class: verdict.class,
+        confidence: verdict.confidence.clamp(0.0, 1.0),
+        reason: verdict.reason,
+        source: ClassificationSource::Model(model.to_string()),
+    })
+}
 
-    CommandClass::Unknown
+/// Classify raw user input into a command category.
+pub fn classify_command(input: &str) -> CommandClass {
+    CommandClassifier::default().classify_rules(input).class
 }
 
 #[cfg(test)]
@@ -96,4 +304,45 @@ mod tests {
     fn test_unknown() {
         assert_eq!(classify_command("build"), CommandClass::Build);
     }
+
+    #[test]
+    fn test_keywords_inside_sentences_do_not_match() {
+        assert_eq!(
+            classify_command("explain why the worker pool deadlocks on shutdown"),
+            CommandClass::AiQuery
+        );
+        assert_eq!(classify_command("builder"), CommandClass::Unknown);
+    }
+
+    #[test]
+    fn test_config_rule_priority() {
+        let mut config = ClassifierConfig::default();
+        config.rules.push(ClassifierRule {
+            name: "deploy".into(),
+            class: CommandClass::Build,
+            pattern: Some(r"^(deploy|ship)\b".into()),
+            priority: 50,
+            ..Default::default()
+        });
+
+        let classifier = CommandClassifier::from_config(&config).unwrap();
+        let result = classifier.classify_rules("ship it to staging");
+
+        assert_eq!(result.class, CommandClass::Build);
+        assert_eq!(result.source, ClassificationSource::Rule("deploy".into()));
+    }
+
+    #[test]
+    fn test_invalid_pattern_rejected() {
+        let config = ClassifierConfig {
+            rules: vec![ClassifierRule {
+                name: "broken".into(),
+                pattern: Some("(".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert!(CommandClassifier::from_config(&config).is_err());
+    }
 }
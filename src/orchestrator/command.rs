@@ -0,0 +1,698 @@
+//! Slash-command grammar shared by the CLI, WebSocket and Telegram ingresses.
+//!
+//! `/build axon_core --release` is parsed against a `CommandSpec` into a
+//! typed `CommandRequest`; errors carry usage and "did you mean" hints.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ai::workspace::WorkspaceAction;
+use crate::orchestrator::classifier::CommandClass;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    /// Single word
+    Positional,
+    /// Consumes the remaining words
+    Rest,
+    /// `--name` with no value
+    Switch,
+    /// `--name <value>`
+    Option,
+    /// Every line after the command line, verbatim
+    Body,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+    pub help: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub summary: &'static str,
+    pub args: Vec<ArgSpec>,
+}
+
+impl CommandSpec {
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+
+        for arg in &self.args {
+            let part = match (arg.kind, arg.required) {
+                (ArgKind::Positional, true) => format!("<{}>", arg.name),
+                (ArgKind::Positional, false) => format!("[{}]", arg.name),
+                (ArgKind::Rest, true) => format!("<{}...>", arg.name),
+                (ArgKind::Rest, false) => format!("[{}...]", arg.name),
+                (ArgKind::Switch, _) => format!("[--{}]", arg.name),
+                (ArgKind::Option, _) => format!("[--{} <{}>]", arg.name, arg.name),
+                (ArgKind::Body, _) => format!("+ <{}> on the next lines", arg.name),
+            };
+            usage.push(' ');
+            usage.push_str(&part);
+        }
+
+        usage
+    }
+
+    fn flag(&self, name: &str) -> Option<&ArgSpec> {
+        self.args
+            .iter()
+            .find(|a| a.name == name && matches!(a.kind, ArgKind::Switch | ArgKind::Option))
+    }
+}
+
+fn arg(name: &'static str, kind: ArgKind, required: bool, help: &'static str) -> ArgSpec {
+    ArgSpec { name, kind, required, help }
+}
+
+/// Every command the engine understands
+pub fn command_specs() -> Vec<CommandSpec> {
+    vec![
+        CommandSpec {
+            name: "build",
+            aliases: &["b", "compile"],
+            summary: "run a cargo build",
+            args: vec![
+                arg("project", ArgKind::Positional, false, "project directory (default: .)"),
+                arg("release", ArgKind::Switch, false, "build with optimizations"),
+            ],
+        },
+        CommandSpec {
+            name: "repair",
+            aliases: &["fix"],
+            summary: "let the AI fix compile errors, reverting patches that make things worse",
+            args: vec![arg("project", ArgKind::Positional, false, "project directory (default: .)")],
+        },
+        CommandSpec {
+            name: "status",
+            aliases: &["stat", "health"],
+            summary: "worker and index status",
+            args: vec![],
+        },
+        CommandSpec {
+            name: "search",
+            aliases: &["find", "rag"],
+            summary: "search the RAG index",
+            args: vec![arg("query", ArgKind::Rest, true, "words to look for")],
+        },
+        CommandSpec {
+            name: "ask",
+            aliases: &["ai"],
+            summary: "ask the AI",
+            args: vec![
+                arg("prompt", ArgKind::Rest, true, "question or task"),
+                arg("model", ArgKind::Option, false, "override the routed model"),
+                arg("reflect", ArgKind::Switch, false, "self-check the answer and retry if weak"),
+            ],
+        },
+        CommandSpec {
+            name: "plan",
+            aliases: &[],
+            summary: "plan a goal and execute the steps",
+            args: vec![arg("goal", ArgKind::Rest, true, "what to accomplish")],
+        },
+        CommandSpec {
+            name: "feedback",
+            aliases: &["fb", "rate"],
+            summary: "rate the last AI answer",
+            args: vec![
+                arg("rating", ArgKind::Positional, true, "up or down"),
+                arg("comment", ArgKind::Rest, false, "what was good or wrong"),
+            ],
+        },
+        CommandSpec {
+            name: "approve",
+            aliases: &["yes"],
+            summary: "let a suspended tool call or patch run",
+            args: vec![
+                arg("id", ArgKind::Positional, true, "approval id"),
+                arg("actions", ArgKind::Rest, false, "patch action numbers to apply, e.g. 1,3 (default: all)"),
+            ],
+        },
+        CommandSpec {
+            name: "deny",
+            aliases: &["reject"],
+            summary: "reject a suspended tool call or patch",
+            args: vec![arg("id", ArgKind::Positional, true, "approval id")],
+        },
+        CommandSpec {
+            name: "edit",
+            aliases: &[],
+            summary: "replace the content of a patch action and review again",
+            args: vec![
+                arg("id", ArgKind::Positional, true, "patch review id"),
+                arg("action", ArgKind::Positional, true, "action number"),
+                arg("content", ArgKind::Body, true, "new content"),
+            ],
+        },
+        CommandSpec {
+            name: "changes",
+            aliases: &["pending"],
+            summary: "list AI commits waiting to be merged",
+            args: vec![],
+        },
+        CommandSpec {
+            name: "merge",
+            aliases: &[],
+            summary: "merge the AI branch into your checkout",
+            args: vec![],
+        },
+        CommandSpec {
+            name: "discard",
+            aliases: &[],
+            summary: "drop all unmerged AI changes",
+            args: vec![],
+        },
+        CommandSpec {
+            name: "help",
+            aliases: &["h", "?"],
+            summary: "list commands or show usage for one",
+            args: vec![arg("command", ArgKind::Positional, false, "command name")],
+        },
+    ]
+}
+
+pub fn find_spec(name: &str) -> Option<CommandSpec> {
+    let name = name.trim_start_matches('/').to_lowercase();
+    command_specs()
+        .into_iter()
+        .find(|s| s.name == name || s.aliases.contains(&name.as_str()))
+}
+
+/// A fully parsed and validated command
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandRequest {
+    Build { project: String, release: bool },
+    Repair { project: String },
+    Status,
+    Search { query: String },
+    Ask { prompt: String, model: Option<String>, reflect: bool },
+    Plan { goal: String },
+    Feedback { positive: bool, comment: Option<String> },
+    /// `actions` are 1-based patch action numbers
+    Approve { id: String, actions: Option<Vec<usize>> },
+    Deny { id: String },
+    Edit { id: String, action: usize, content: String },
+    Workspace { action: WorkspaceAction },
+    Help { command: Option<String> },
+}
+
+impl CommandRequest {
+    /// Shell command the build worker should run
+    pub fn build_command(release: bool) -> String {
+        if release { "cargo build --release".into() } else { "cargo build".into() }
+    }
+
+    /// Typed request for free text the classifier routed to `class`.
+    /// The command grammar is never applied here, only to `/` input, so
+    /// "merge" or "do you know..." cannot trigger a command. The whole
+    /// sentence stays the query or prompt; an apostrophe is just a letter.
+    pub fn from_class(class: CommandClass, text: &str) -> Option<CommandRequest> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+
+        match class {
+            CommandClass::Build => Some(CommandRequest::Build {
+                project: ".".into(),
+                release: words.contains(&"--release"),
+            }),
+            CommandClass::Status => Some(CommandRequest::Status),
+            CommandClass::RagSearch => {
+                // Drop the leading verb ("search", "find", ...)
+                let query = words.get(1..).unwrap_or_default().join(" ");
+                (!query.is_empty()).then_some(CommandRequest::Search { query })
+            }
+            CommandClass::AiQuery => Some(CommandRequest::Ask { prompt: text.trim().to_string(), model: None, reflect: false }),
+            CommandClass::Unknown => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Empty,
+    UnterminatedQuote,
+    UnknownCommand { name: String, suggestion: Option<String> },
+    UnknownFlag { command: String, flag: String, suggestion: Option<String> },
+    MissingValue { command: String, flag: String },
+    MissingArgument { command: String, arg: String },
+    UnexpectedArgument { command: String, value: String },
+    InvalidValue { command: String, arg: String, reason: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hint = |suggestion: &Option<String>| {
+            suggestion
+                .as_ref()
+                .map(|s| format!(" Did you mean {}?", s))
+                .unwrap_or_default()
+        };
+        let usage = |command: &str| {
+            find_spec(command)
+                .map(|s| format!("\nUsage: {}", s.usage()))
+                .unwrap_or_default()
+        };
+
+        match self {
+            ParseError::Empty => write!(f, "Empty command. Try /help"),
+            ParseError::UnterminatedQuote => write!(f, "Unterminated quote"),
+            ParseError::UnknownCommand { name, suggestion } => {
+                write!(f, "Unknown command /{}.{} Try /help", name, hint(suggestion))
+            }
+            ParseError::UnknownFlag { command, flag, suggestion } => {
+                write!(f, "Unknown flag --{} for /{}.{}{}", flag, command, hint(suggestion), usage(command))
+            }
+            ParseError::MissingValue { command, flag } => {
+                write!(f, "Flag --{} needs a value{}", flag, usage(command))
+            }
+            ParseError::MissingArgument { command, arg } => {
+                write!(f, "Missing <{}>{}", arg, usage(command))
+            }
+            ParseError::UnexpectedArgument { command, value } => {
+                write!(f, "Unexpected argument '{}'{}", value, usage(command))
+            }
+            ParseError::InvalidValue { command, arg, reason } => {
+                write!(f, "Invalid {}: {}{}", arg, reason, usage(command))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// True when the input should go through the grammar instead of the classifier
+pub fn is_slash_command(input: &str) -> bool {
+    input.trim_start().starts_with('/')
+}
+
+/// True for a one-line `/command` whose body comes on the following lines
+pub fn expects_body(input: &str) -> bool {
+    let name = input.split_whitespace().next().unwrap_or_default();
+
+    is_slash_command(input)
+        && !input.contains('\n')
+        && find_spec(name).is_some_and(|s| s.args.iter().any(|a| a.kind == ArgKind::Body))
+}
+
+/// Parse `/name args...` into a typed request
+pub fn parse(input: &str) -> Result<CommandRequest, ParseError> {
+    let input = input.trim_start();
+    let first = input.split_whitespace().next().ok_or(ParseError::Empty)?;
+    let name = first.trim_start_matches('/').to_lowercase();
+
+    if name.is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    let spec = find_spec(&name).ok_or_else(|| ParseError::UnknownCommand {
+        suggestion: suggest(&name, command_specs().iter().map(|s| s.name)).map(|s| format!("/{}", s)),
+        name,
+    })?;
+
+    if !spec.args.iter().any(|a| a.kind == ArgKind::Body) {
+        return parse_tokens(&spec, &tokenize(input)?[1..], None);
+    }
+
+    // Only the first line holds words; the rest is taken as is
+    let (head, body) = input.split_once('\n').unwrap_or((input, ""));
+    parse_tokens(&spec, &tokenize(head)?[1..], Some(body))
+}
+
+fn parse_tokens(spec: &CommandSpec, tokens: &[String], body: Option<&str>) -> Result<CommandRequest, ParseError> {
+    let command = spec.name.to_string();
+    let mut values: HashMap<&'static str, String> = HashMap::new();
+    let mut switches: Vec<&'static str> = Vec::new();
+    let mut positional: Vec<String> = Vec::new();
+
+    let mut iter = tokens.iter();
+    while let Some(token) = iter.next() {
+        let Some(flag) = token.strip_prefix("--") else {
+            positional.push(token.clone());
+            continue;
+        };
+
+        let (flag, inline) = match flag.split_once('=') {
+            Some((f, v)) => (f, Some(v.to_string())),
+            None => (flag, None),
+        };
+
+        let arg = spec.flag(flag).ok_or_else(|| ParseError::UnknownFlag {
+            command: command.clone(),
+            flag: flag.to_string(),
+            suggestion: suggest(
+                flag,
+                spec.args
+                    .iter()
+                    .filter(|a| matches!(a.kind, ArgKind::Switch | ArgKind::Option))
+                    .map(|a| a.name),
+            )
+            .map(|s| format!("--{}", s)),
+        })?;
+
+        match arg.kind {
+            ArgKind::Switch => switches.push(arg.name),
+            _ => {
+                let value = inline
+                    .or_else(|| iter.next().cloned())
+                    .ok_or_else(|| ParseError::MissingValue {
+                        command: command.clone(),
+                        flag: arg.name.to_string(),
+                    })?;
+                values.insert(arg.name, value);
+            }
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    for arg in &spec.args {
+        match arg.kind {
+            ArgKind::Positional => {
+                if let Some(value) = positional.next() {
+                    values.insert(arg.name, value);
+                }
+            }
+            ArgKind::Rest => {
+                let rest = positional.by_ref().collect::<Vec<_>>().join(" ");
+                if !rest.is_empty() {
+                    values.insert(arg.name, rest);
+                }
+            }
+            ArgKind::Body => {
+                if let Some(text) = body.filter(|t| !t.trim().is_empty()) {
+                    values.insert(arg.name, text.to_string());
+                }
+            }
+            _ => {}
+        }
+
+        if arg.required && !values.contains_key(arg.name) {
+            return Err(ParseError::MissingArgument {
+                command: command.clone(),
+                arg: arg.name.to_string(),
+            });
+        }
+    }
+
+    if let Some(extra) = positional.next() {
+        return Err(ParseError::UnexpectedArgument { command, value: extra });
+    }
+
+    build_request(spec, values, &switches)
+}
+
+/// Per-command validation and conversion into the typed request
+fn build_request(
+    spec: &CommandSpec,
+    mut values: HashMap<&'static str, String>,
+    switches: &[&'static str],
+) -> Result<CommandRequest, ParseError> {
+    let invalid = |arg: &str, reason: &str| ParseError::InvalidValue {
+        command: spec.name.to_string(),
+        arg: arg.to_string(),
+        reason: reason.to_string(),
+    };
+
+    match spec.name {
+        "build" => {
+            let project = values.remove("project").unwrap_or_else(|| ".".into());
+            if project.split(['/', '\\']).any(|part| part == "..") {
+                return Err(invalid("project", "must not leave the workspace"));
+            }
+            Ok(CommandRequest::Build { project, release: switches.contains(&"release") })
+        }
+        "repair" => {
+            let project = values.remove("project").unwrap_or_else(|| ".".into());
+            if project.split(['/', '\\']).any(|part| part == "..") {
+                return Err(invalid("project", "must not leave the workspace"));
+            }
+            Ok(CommandRequest::Repair { project })
+        }
+        "status" => Ok(CommandRequest::Status),
+        "search" => Ok(CommandRequest::Search { query: values.remove("query").unwrap_or_default() }),
+        "ask" => {
+            let model = values.remove("model");
+            if model.as_deref().is_some_and(|m| m.trim().is_empty()) {
+                return Err(invalid("model", "must not be empty"));
+            }
+            Ok(CommandRequest::Ask {
+                prompt: values.remove("prompt").unwrap_or_default(),
+                model,
+                reflect: switches.contains(&"reflect"),
+            })
+        }
+        "plan" => Ok(CommandRequest::Plan { goal: values.remove("goal").unwrap_or_default() }),
+        "approve" => {
+            let actions = match values.remove("actions") {
+                None => None,
+                Some(list) => Some(
+                    list.split([',', ' '])
+                        .filter(|n| !n.is_empty())
+                        .map(|n| n.parse::<usize>().ok().filter(|&n| n > 0))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid("actions", "use action numbers like 1,3"))?,
+                ),
+            };
+            Ok(CommandRequest::Approve { id: values.remove("id").unwrap_or_default(), actions })
+        }
+        "edit" => {
+            let action = values
+                .remove("action")
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|&n| n > 0)
+                .ok_or_else(|| invalid("action", "must be an action number"))?;
+            Ok(CommandRequest::Edit {
+                id: values.remove("id").unwrap_or_default(),
+                action,
+                content: values.remove("content").unwrap_or_default(),
+            })
+        }
+        "deny" => Ok(CommandRequest::Deny { id: values.remove("id").unwrap_or_default() }),
+        "changes" => Ok(CommandRequest::Workspace { action: WorkspaceAction::Changes }),
+        "merge" => Ok(CommandRequest::Workspace { action: WorkspaceAction::Merge }),
+        "discard" => Ok(CommandRequest::Workspace { action: WorkspaceAction::Discard }),
+        "feedback" => {
+            let positive = match values.remove("rating").unwrap_or_default().to_lowercase().as_str() {
+                "up" | "good" | "yes" | "+" | "+1" => true,
+                "down" | "bad" | "no" | "-" | "-1" => false,
+                _ => return Err(invalid("rating", "use up or down")),
+            };
+            Ok(CommandRequest::Feedback { positive, comment: values.remove("comment") })
+        }
+        "help" => {
+            let command = values.remove("command");
+            if let Some(name) = &command {
+                if find_spec(name).is_none() {
+                    return Err(ParseError::UnknownCommand {
+                        name: name.trim_start_matches('/').to_string(),
+                        suggestion: suggest(name, command_specs().iter().map(|s| s.name))
+                            .map(|s| format!("/{}", s)),
+                    });
+                }
+            }
+            Ok(CommandRequest::Help { command })
+        }
+        other => Err(ParseError::UnknownCommand { name: other.to_string(), suggestion: None }),
+    }
+}
+
+/// Split on whitespace, keeping "quoted strings" together
+fn tokenize(input: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut in_token = false;
+
+    for c in input.trim().chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(ParseError::UnterminatedQuote);
+    }
+    if in_token {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+/// Closest candidate within an edit distance of 2
+fn suggest<'a>(input: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+    let input = input.trim_start_matches('/').to_lowercase();
+
+    candidates
+        .map(|c| (levenshtein(&input, c), c))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c.to_string())
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            row[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        prev = row;
+    }
+
+    prev[b.len()]
+}
+
+/// `/help` output, generated from the specs
+pub fn help_text() -> String {
+    let specs = command_specs();
+    let width = specs.iter().map(|s| s.usage().len()).max().unwrap_or(0);
+
+    let mut text = String::from("Available commands:");
+    for spec in &specs {
+        text.push_str(&format!("\n  {:<width$}  {}", spec.usage(), spec.summary, width = width));
+    }
+    text.push_str("\nAnything without a leading / is classified automatically.");
+    text
+}
+
+/// `/help <command>` output
+pub fn command_help(spec: &CommandSpec) -> String {
+    let mut text = format!("/{} - {}\nUsage: {}", spec.name, spec.summary, spec.usage());
+
+    if !spec.aliases.is_empty() {
+        let aliases: Vec<String> = spec.aliases.iter().map(|a| format!("/{}", a)).collect();
+        text.push_str(&format!("\nAliases: {}", aliases.join(", ")));
+    }
+    for arg in &spec.args {
+        let name = match arg.kind {
+            ArgKind::Switch | ArgKind::Option => format!("--{}", arg.name),
+            _ => arg.name.to_string(),
+        };
+        text.push_str(&format!("\n  {:<10} {}", name, arg.help));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_build_with_flag() {
+        assert_eq!(
+            parse("/build axon_core --release"),
+            Ok(CommandRequest::Build { project: "axon_core".into(), release: true })
+        );
+        assert_eq!(
+            parse("/b"),
+            Ok(CommandRequest::Build { project: ".".into(), release: false })
+        );
+    }
+
+    #[test]
+    fn test_free_text_with_apostrophes() {
+        assert_eq!(
+            CommandRequest::from_class(CommandClass::AiQuery, "fix what's broken"),
+            Some(CommandRequest::Ask { prompt: "fix what's broken".into(), model: None, reflect: false })
+        );
+        assert_eq!(
+            CommandRequest::from_class(CommandClass::RagSearch, "find Bob's 'retry' notes"),
+            Some(CommandRequest::Search { query: "Bob's 'retry' notes".into() })
+        );
+    }
+
+    #[test]
+    fn test_free_text_never_runs_commands() {
+        let classes = [CommandClass::AiQuery, CommandClass::Unknown, CommandClass::Status, CommandClass::RagSearch, CommandClass::Build];
+
+        for text in ["do you know why the build fails", "merge", "discard", "yes ab12", "plan a trip"] {
+            for class in classes {
+                let request = CommandRequest::from_class(class, text);
+                assert!(
+                    !matches!(request, Some(CommandRequest::Plan { .. } | CommandRequest::Workspace { .. } | CommandRequest::Approve { .. })),
+                    "{:?} as {:?} became {:?}", text, class, request
+                );
+            }
+        }
+        assert!(find_spec("do").is_none());
+    }
+
+    #[test]
+    fn test_parse_rest_and_option() {
+        assert_eq!(
+            parse(r#"/ask --model qwen2.5:7b "why" does it fail"#),
+            Ok(CommandRequest::Ask { prompt: "why does it fail".into(), model: Some("qwen2.5:7b".into()), reflect: false })
+        );
+    }
+
+    #[test]
+    fn test_parse_patch_review_commands() {
+        assert_eq!(
+            parse("/approve ab12 1,3"),
+            Ok(CommandRequest::Approve { id: "ab12".into(), actions: Some(vec![1, 3]) })
+        );
+        assert_eq!(parse("/yes ab12"), Ok(CommandRequest::Approve { id: "ab12".into(), actions: None }));
+        assert!(matches!(parse("/approve ab12 0"), Err(ParseError::InvalidValue { .. })));
+
+        // The body keeps its lines and quotes
+        assert_eq!(
+            parse("/edit ab12 2\nlet s = \"it's\";\n    done();\n"),
+            Ok(CommandRequest::Edit { id: "ab12".into(), action: 2, content: "let s = \"it's\";\n    done();\n".into() })
+        );
+        assert!(matches!(parse("/edit ab12 2"), Err(ParseError::MissingArgument { .. })));
+
+        assert_eq!(parse("/merge"), Ok(CommandRequest::Workspace { action: WorkspaceAction::Merge }));
+        assert!(matches!(parse("/discard all"), Err(ParseError::UnexpectedArgument { .. })));
+    }
+
+    #[test]
+    fn test_typo_suggestions() {
+        let err = parse("/biuld").unwrap_err();
+        assert_eq!(err, ParseError::UnknownCommand { name: "biuld".into(), suggestion: Some("/build".into()) });
+
+        let err = parse("/build --relase").unwrap_err();
+        assert!(err.to_string().contains("Did you mean --release?"));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        assert!(matches!(parse("/search"), Err(ParseError::MissingArgument { .. })));
+        assert!(matches!(parse("/status now"), Err(ParseError::UnexpectedArgument { .. })));
+        assert!(matches!(parse("/build ../other"), Err(ParseError::InvalidValue { .. })));
+        assert!(matches!(parse("/repair ../other"), Err(ParseError::InvalidValue { .. })));
+        assert!(matches!(parse("/ask --model"), Err(ParseError::MissingValue { .. })));
+    }
+
+    #[test]
+    fn test_help_lists_every_command() {
+        let help = help_text();
+        for spec in command_specs() {
+            assert!(help.contains(&spec.usage()));
+        }
+    }
+}
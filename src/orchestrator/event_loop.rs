@@ -0,0 +1,676 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::ai::approval::{is_approver, telegram_admin};
+use crate::ai::memory::conversation_embeddings::JobStatus;
+use crate::ai::memory::eval_store::EvalStore;
+use crate::ai::model_router::AiTaskType;
+use crate::ai::patch_review::ReviewDecision;
+use crate::ai::provider::{LlmProvider, OllamaProvider};
+use crate::core::state::AppState;
+use crate::event::bus::{AiSender, EventSender};
+use crate::event::event::{AxonEvent, Ingress, WorkerHealth};
+use crate::orchestrator::classifier::{Classification, CommandClassifier};
+use crate::orchestrator::command::{self, CommandRequest};
+
+/// Consumes bus events, classifies user commands and routes the answers
+/// back to the ingress that asked.
+pub struct Orchestrator {
+    state: Arc<AppState>,
+    tx: EventSender,
+    ai_tx: AiSender,
+    classifier: Arc<CommandClassifier>,
+    /// Used for fallback classification when no rule is confident
+    provider: Option<Arc<dyn LlmProvider>>,
+    /// AI and RAG requests awaiting an answer
+    pending: HashMap<Uuid, Ingress>,
+    /// Builds awaiting `BuildFinished`, keyed by project
+    pending_builds: HashMap<String, Vec<(Uuid, Ingress)>>,
+    /// Most recent AI answer per ingress, the target of `/feedback`
+    last_answers: HashMap<Ingress, Uuid>,
+    evals: EvalStore,
+    /// Tool calls suspended until `/approve` or `/deny`
+    pending_approvals: HashSet<String>,
+    /// Patches awaiting review, with their number of actions
+    pending_reviews: HashMap<String, usize>,
+}
+
+impl Orchestrator {
+    pub fn new(state: Arc<AppState>, tx: EventSender, ai_tx: AiSender) -> Result<Self> {
+        let classifier = CommandClassifier::from_config(&state.config.classifier)?;
+
+        let provider = classifier.llm_fallback().then(|| {
+            Arc::new(OllamaProvider::from_config(&state.config.ai)) as Arc<dyn LlmProvider>
+        });
+
+        Ok(Self {
+            state,
+            tx,
+            ai_tx,
+            classifier: Arc::new(classifier),
+            provider,
+            pending: HashMap::new(),
+            pending_builds: HashMap::new(),
+            last_answers: HashMap::new(),
+            evals: EvalStore::new(),
+            pending_approvals: HashSet::new(),
+            pending_reviews: HashMap::new(),
+        })
+    }
+
+    pub async fn run(mut self) -> Result<()> {
+        let mut rx = self.tx.subscribe();
+
+        self.state.update_worker("orchestrator", WorkerHealth::Running).await;
+        info!("Orchestrator ACTIVE");
+
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    if let Err(e) = self.on_event(event).await {
+                        warn!("Orchestrator error: {}", e);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Orchestrator lagged, skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+
+        self.state.update_worker("orchestrator", WorkerHealth::Stopped).await;
+        Ok(())
+    }
+
+    async fn on_event(&mut self, event: AxonEvent) -> Result<()> {
+        match event {
+            AxonEvent::UserCommand { id, text, origin } => {
+                self.dispatch(id, text, origin).await
+            }
+
+            AxonEvent::TelegramCommand { text, chat_id, .. } => {
+                self.dispatch(Uuid::new_v4(), text, Ingress::Telegram { chat_id }).await
+            }
+
+            AxonEvent::CommandClassified { id, text, origin, class } => {
+                let request = CommandRequest::from_class(class, &text).unwrap_or(CommandRequest::Help { command: None });
+                self.execute(id, request, origin).await
+            }
+
+            AxonEvent::AiResponse { request_id, output, model, .. } => {
+                if let Some(origin) = self.pending.remove(&request_id) {
+                    self.last_answers.insert(origin.clone(), request_id);
+                    self.reply(request_id, origin, output, Some(model))?;
+                }
+                Ok(())
+            }
+
+            AxonEvent::RagSearchResult { request_id, query, results } => {
+                if let Some(origin) = self.pending.remove(&request_id) {
+                    let text = if results.is_empty() {
+                        format!("No results for \"{}\"", query)
+                    } else {
+                        format!("Results for \"{}\":\n{}", query, results.join("\n"))
+                    };
+                    self.reply(request_id, origin, text, None)?;
+                }
+                Ok(())
+            }
+
+            AxonEvent::JobProgress { job_id, step, total, description, status } => {
+                if status != JobStatus::Running {
+                    if let Some(origin) = self.pending.get(&job_id).cloned() {
+                        let text = format!("Step {}/{} {:?}: {}", step, total, status, description);
+                        self.reply(job_id, origin, text, None)?;
+                    }
+                }
+                Ok(())
+            }
+
+            AxonEvent::JobFinished { job_id, summary, .. } => {
+                if let Some(origin) = self.pending.remove(&job_id) {
+                    self.reply(job_id, origin, summary, None)?;
+                }
+                Ok(())
+            }
+
+            AxonEvent::ApprovalRequested { id, agent, tool, args, reason, timeout_seconds } => {
+                self.pending_approvals.insert(id.clone());
+
+                let description = format!(
+                    "Agent '{}' wants to call {} {}\nReason: {}\nReply /approve {} or /deny {} within {}s",
+                    agent, tool, args, reason, id, id, timeout_seconds
+                );
+                for origin in self.approvers() {
+                    let text = format!("Approval required [{}]: {}", id, description);
+                    self.reply(Uuid::new_v4(), origin, text, None)?;
+                }
+                Ok(())
+            }
+
+            AxonEvent::PatchReviewRequested { id, agent, preview, timeout_seconds } => {
+                self.pending_reviews.insert(id.clone(), preview.actions.len());
+
+                let instructions = format!(
+                    "Reply /approve {} [1,3] to apply all or some actions, /deny {}, \
+                     or /edit {} <n> with the new content on the next lines, within {}s",
+                    id, id, id, timeout_seconds
+                );
+                let heading = format!("Agent '{}' proposes a patch", agent);
+
+                for origin in self.approvers() {
+                    let text = format!("Patch review [{}]: {}\n{}\n\n{}", id, heading, preview.render(), instructions);
+                    self.reply(Uuid::new_v4(), origin, text, None)?;
+                }
+                Ok(())
+            }
+
+            AxonEvent::ApprovalResolved { id, outcome } => {
+                if self.pending_approvals.remove(&id) | self.pending_reviews.remove(&id).is_some() {
+                    let text = format!("Approval [{}]: {:?}", id, outcome);
+                    for origin in self.approvers() {
+                        self.reply(Uuid::new_v4(), origin, text.clone(), None)?;
+                    }
+                }
+                Ok(())
+            }
+
+            AxonEvent::BuildFinished { project, success, output, duration_ms, .. } => {
+                let waiting = self.pending_builds.remove(&project).unwrap_or_default();
+                let verdict = if success { "succeeded" } else { "FAILED" };
+
+                for (request_id, origin) in waiting {
+                    let text = format!(
+                        "Build of {} {} in {}\n{}",
+                        project,
+                        verdict,
+                        crate::util::time::format_duration_ms(duration_ms),
+                        output
+                    );
+                    self.reply(request_id, origin, text, None)?;
+                }
+                Ok(())
+            }
+
+            other => {
+                crate::orchestrator::handler::handle_event(
+                    other,
+                    self.state.clone(),
+                    self.ai_tx.clone(),
+                ).await
+            }
+        }
+    }
+
+    async fn dispatch(&mut self, id: Uuid, text: String, origin: Ingress) -> Result<()> {
+        let request = if command::is_slash_command(&text) {
+            match command::parse(&text) {
+                Ok(request) => request,
+                Err(e) => return self.reply(id, origin, e.to_string(), None),
+            }
+        } else if let (true, Some(provider)) = (self.classifier.needs_model(&text), self.provider.clone()) {
+            // The model can take seconds; the answer comes back as `CommandClassified`
+            let classifier = self.classifier.clone();
+            let model = self.state.config.ai.default_model.name.clone();
+            let tx = self.tx.clone();
+            tokio::spawn(async move {
+                let classification = classifier.classify(&text, Some(provider.as_ref()), &model).await;
+                log_classification(id, &classification);
+                let _ = tx.send(AxonEvent::CommandClassified { id, text, origin, class: classification.class });
+            });
+            return Ok(());
+        } else {
+            let classification = self.classifier.classify(&text, None, &self.state.config.ai.default_model.name).await;
+            log_classification(id, &classification);
+
+            CommandRequest::from_class(classification.class, &text)
+                .unwrap_or(CommandRequest::Help { command: None })
+        };
+
+        self.execute(id, request, origin).await
+    }
+
+    async fn execute(&mut self, id: Uuid, request: CommandRequest, origin: Ingress) -> Result<()> {
+        match request {
+            CommandRequest::Build { project, release } => {
+                let command = CommandRequest::build_command(release);
+
+                self.pending_builds
+                    .entry(project.clone())
+                    .or_default()
+                    .push((id, origin.clone()));
+
+                self.reply(id, origin, format!("Build started: {} ({})", project, command), None)?;
+
+                let state = self.state.clone();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = crate::workers::build_worker::run_build(project, command, state, tx).await {
+                        warn!("Build worker error: {}", e);
+                    }
+                });
+            }
+
+            CommandRequest::Repair { project } => {
+                self.pending_builds
+                    .entry(project.clone())
+                    .or_default()
+                    .push((id, origin.clone()));
+
+                self.reply(id, origin, format!("Repair started: {}", project), None)?;
+                self.ai_tx.send(AxonEvent::RepairRequested { id, project }).await?;
+            }
+
+            CommandRequest::Status => {
+                let report = status_report(&self.state).await;
+                self.reply(id, origin, report, None)?;
+            }
+
+            CommandRequest::Search { query } => {
+                self.pending.insert(id, origin);
+                self.tx.send(AxonEvent::RagSearch { query, request_id: id })?;
+            }
+
+            CommandRequest::Ask { prompt, model, reflect } => {
+                self.pending.insert(id, origin);
+                self.ai_tx.send(AxonEvent::AiRequest {
+                    id,
+                    task: Some(AiTaskType::infer(&prompt)),
+                    prompt,
+                    model,
+                    context: None,
+                    // Only an explicit --reflect overrides the agent setting
+                    reflect: reflect.then_some(true),
+                }).await?;
+            }
+
+            CommandRequest::Plan { goal } => {
+                self.pending.insert(id, origin.clone());
+                self.reply(id, origin, format!("Planning: {}", goal), None)?;
+                self.ai_tx.send(AxonEvent::PlanRequested { id, goal }).await?;
+            }
+
+            CommandRequest::Feedback { positive, comment } => {
+                let text = match self.last_answers.get(&origin) {
+                    None => "No AI answer to rate yet".to_string(),
+                    Some(&answer) => match self.evals.add_feedback(answer, positive, comment) {
+                        Ok(_) => "Feedback recorded, thanks".to_string(),
+                        Err(e) => format!("Feedback not recorded: {:#}", e),
+                    },
+                };
+                self.reply(id, origin, text, None)?;
+            }
+
+            CommandRequest::Approve { id: approval, actions } => self.decide(id, origin, approval, true, actions)?,
+            CommandRequest::Deny { id: approval } => self.decide(id, origin, approval, false, None)?,
+            CommandRequest::Edit { id: review, .. } if !is_approver(&self.state.config, &origin) => {
+                self.reply(id, origin, format!("Only an approver can edit {}", review), None)?;
+            }
+            CommandRequest::Edit { id: review, action, content } => {
+                let text = match self.pending_reviews.get(&review) {
+                    None => format!("No pending patch review {}", review),
+                    Some(&count) if action > count => format!("Patch {} has no action {}", review, action),
+                    Some(_) => {
+                        let decision = ReviewDecision::Edit { action: action - 1, content };
+                        self.tx.send(AxonEvent::PatchReviewDecided { id: review.clone(), decision, by: Some(origin.clone()) })?;
+                        format!("Action {} of {} edited, the updated diff follows", action, review)
+                    }
+                };
+                self.reply(id, origin, text, None)?;
+            }
+
+            // Answered by the workspace once git is done
+            CommandRequest::Workspace { action } => {
+                self.tx.send(AxonEvent::WorkspaceRequested { request_id: id, origin, action })?;
+            }
+
+            CommandRequest::Help { command } => {
+                let text = command
+                    .as_deref()
+                    .and_then(command::find_spec)
+                    .map(|spec| command::command_help(&spec))
+                    .unwrap_or_else(command::help_text);
+                self.reply(id, origin, text, None)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Relay an `/approve` or `/deny` to the patch reviewer or the
+    /// approval gate; `actions` are 1-based and only apply to patches
+    fn decide(&self, id: Uuid, origin: Ingress, approval: String, approved: bool, actions: Option<Vec<usize>>) -> Result<()> {
+        let text = if !is_approver(&self.state.config, &origin) {
+            format!("Only an approver can decide {}", approval)
+        } else if let Some(&count) = self.pending_reviews.get(&approval) {
+            match actions.iter().flatten().find(|&&n| n > count) {
+                Some(missing) => format!("Patch {} has no action {}", approval, missing),
+                None => {
+                    let decision = if approved {
+                        ReviewDecision::Approve { actions: actions.map(|a| a.iter().map(|n| n - 1).collect()) }
+                    } else {
+                        ReviewDecision::Reject
+                    };
+                    self.tx.send(AxonEvent::PatchReviewDecided { id: approval.clone(), decision, by: Some(origin.clone()) })?;
+                    format!("{} {}", if approved { "Approved" } else { "Denied" }, approval)
+                }
+            }
+        } else if !self.pending_approvals.contains(&approval) {
+            format!("No pending approval {}", approval)
+        } else if actions.is_some() {
+            format!("{} is a tool call; action numbers only apply to patches", approval)
+        } else if approved {
+            self.tx.send(AxonEvent::FixApproved { alert_id: approval.clone(), by: Some(origin.clone()) })?;
+            format!("Approved {}", approval)
+        } else {
+            self.tx.send(AxonEvent::FixDenied { alert_id: approval.clone(), by: Some(origin.clone()) })?;
+            format!("Denied {}", approval)
+        };
+        self.reply(id, origin, text, None)
+    }
+
+    /// Where approval requests are relayed as text: the CLI and the
+    /// Telegram admin chat; the dashboard gets them over WS
+    fn approvers(&self) -> Vec<Ingress> {
+        let mut approvers = vec![Ingress::Cli];
+        approvers.extend(telegram_admin(&self.state.config).map(|chat_id| Ingress::Telegram { chat_id }));
+        approvers
+    }
+
+    fn reply(&self, request_id: Uuid, origin: Ingress, text: String, model: Option<String>) -> Result<()> {
+        self.tx.send(AxonEvent::CommandReply {
+            request_id,
+            origin,
+            text,
+            model,
+        })?;
+        Ok(())
+    }
+}
+
+fn log_classification(id: Uuid, classification: &Classification) {
+    info!(
+        "Command [{}] classified as {:?} (confidence {:.2}, {:?}): {}",
+        id,
+        classification.class,
+        classification.confidence,
+        classification.source,
+        classification.reason
+    );
+}
+
+/// Human-readable summary of worker health and RAG index size
+pub async fn status_report(state: &AppState) -> String {
+    let workers = state.worker_status.read().await;
+    let indexed = *state.rag_indexed.read().await;
+
+    let mut names: Vec<&String> = workers.keys().collect();
+    names.sort();
+
+    let mut report = format!("AXON status\nRAG indexed: {}\nWorkers:", indexed);
+    if names.is_empty() {
+        report.push_str(" none registered");
+    }
+    for name in names {
+        report.push_str(&format!("\n  {}: {:?}", name, workers[name]));
+    }
+
+    report
+}
+
+/// Spawn-friendly entry point used by `main`
+pub async fn run(state: Arc<AppState>, tx: EventSender, ai_tx: AiSender) -> Result<()> {
+    Orchestrator::new(state, tx, ai_tx)?.run().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::AxonConfig;
+    use tokio::sync::{broadcast, mpsc};
+
+    fn setup() -> (Orchestrator, broadcast::Receiver<AxonEvent>, mpsc::Receiver<AxonEvent>) {
+        setup_with(AxonConfig::default())
+    }
+
+    fn setup_with(mut config: AxonConfig) -> (Orchestrator, broadcast::Receiver<AxonEvent>, mpsc::Receiver<AxonEvent>) {
+        config.classifier.llm_fallback = false;
+
+        let state = Arc::new(AppState::new(config));
+        let (tx, bus_rx) = broadcast::channel(16);
+        let (ai_tx, ai_rx) = mpsc::channel(16);
+        (Orchestrator::new(state, tx, ai_tx).unwrap(), bus_rx, ai_rx)
+    }
+
+    #[tokio::test]
+    async fn test_status_replies_to_origin() {
+        let (mut orch, mut bus_rx, _ai_rx) = setup();
+        let id = Uuid::new_v4();
+
+        orch.dispatch(id, "/status".into(), Ingress::Cli).await.unwrap();
+
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { request_id, origin, text, .. } => {
+                assert_eq!(request_id, id);
+                assert_eq!(origin, Ingress::Cli);
+                assert!(text.contains("RAG indexed"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ai_answer_routed_back() {
+        let (mut orch, mut bus_rx, mut ai_rx) = setup();
+        let id = Uuid::new_v4();
+        let origin = Ingress::Telegram { chat_id: 7 };
+
+        orch.dispatch(id, "Why is the linker failing?".into(), origin.clone()).await.unwrap();
+        assert!(matches!(ai_rx.recv().await, Some(AxonEvent::AiRequest { id: req, .. }) if req == id));
+
+        orch.on_event(AxonEvent::AiResponse {
+            request_id: id,
+            output: "missing symbol".into(),
+            model: "m".into(),
+            context: None,
+            response: "missing symbol".into(),
+            routing: None,
+            trace: None,
+            confidence: None,
+            attempts: None,
+        }).await.unwrap();
+
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { origin: to, text, .. } => {
+                assert_eq!(to, origin);
+                assert_eq!(text, "missing symbol");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_background_classification_is_executed() {
+        let (mut orch, _bus_rx, mut ai_rx) = setup();
+        let id = Uuid::new_v4();
+
+        orch.on_event(AxonEvent::CommandClassified {
+            id,
+            text: "what does the retry loop do".into(),
+            origin: Ingress::Cli,
+            class: crate::orchestrator::classifier::CommandClass::AiQuery,
+        }).await.unwrap();
+
+        match ai_rx.recv().await {
+            Some(AxonEvent::AiRequest { id: req, prompt, .. }) => {
+                assert_eq!(req, id);
+                assert_eq!(prompt, "what does the retry loop do");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_gets_help() {
+        let (mut orch, mut bus_rx, _ai_rx) = setup();
+
+        orch.dispatch(Uuid::new_v4(), "xyz".into(), Ingress::WebSocket).await.unwrap();
+
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { text, .. } => assert_eq!(text, command::help_text()),
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slash_typo_replies_with_suggestion() {
+        let (mut orch, mut bus_rx, _ai_rx) = setup();
+
+        orch.dispatch(Uuid::new_v4(), "/statsu".into(), Ingress::Cli).await.unwrap();
+
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { text, .. } => assert!(text.contains("Did you mean /status?")),
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approval_relayed_and_approved() {
+        let (mut orch, mut bus_rx, _ai_rx) = setup();
+
+        orch.dispatch(Uuid::new_v4(), "/approve abc".into(), Ingress::Cli).await.unwrap();
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { text, .. } => assert_eq!(text, "No pending approval abc"),
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        orch.on_event(AxonEvent::ApprovalRequested {
+            id: "abc".into(),
+            agent: "coder".into(),
+            tool: "write_file".into(),
+            args: serde_json::json!({"path": "src/main.rs"}),
+            reason: "writes src/main.rs".into(),
+            timeout_seconds: 60,
+        }).await.unwrap();
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { origin, text, .. } => {
+                assert_eq!(origin, Ingress::Cli);
+                assert!(text.starts_with("Approval required [abc]"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        orch.dispatch(Uuid::new_v4(), "/approve abc".into(), Ingress::Cli).await.unwrap();
+        assert!(matches!(bus_rx.recv().await.unwrap(), AxonEvent::FixApproved { alert_id, by: Some(Ingress::Cli) } if alert_id == "abc"));
+    }
+
+    #[tokio::test]
+    async fn test_telegram_admin_gets_and_decides_approvals() {
+        let mut config = AxonConfig::default();
+        config.telegram.bot_token = "123:abc".into();
+        config.telegram.admin_id = 42;
+        let (mut orch, mut bus_rx, _ai_rx) = setup_with(config);
+
+        orch.on_event(AxonEvent::ApprovalRequested {
+            id: "abc".into(),
+            agent: "coder".into(),
+            tool: "run_shell".into(),
+            args: serde_json::json!({"command": "rm -rf target"}),
+            reason: "deletes files".into(),
+            timeout_seconds: 60,
+        }).await.unwrap();
+        let mut relayed = Vec::new();
+        for _ in 0..2 {
+            match bus_rx.recv().await.unwrap() {
+                AxonEvent::CommandReply { origin, .. } => relayed.push(origin),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+        assert_eq!(relayed, [Ingress::Cli, Ingress::Telegram { chat_id: 42 }]);
+
+        orch.on_event(AxonEvent::TelegramCommand { text: "/approve abc".into(), chat_id: 7, command: None, args: vec![] }).await.unwrap();
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { text, .. } => assert_eq!(text, "Only an approver can decide abc"),
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        orch.on_event(AxonEvent::TelegramCommand { text: "/deny abc".into(), chat_id: 42, command: None, args: vec![] }).await.unwrap();
+        assert!(matches!(
+            bus_rx.recv().await.unwrap(),
+            AxonEvent::FixDenied { alert_id, by: Some(Ingress::Telegram { chat_id: 42 }) } if alert_id == "abc"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_patch_review_relayed_and_partially_approved() {
+        use crate::ai::multi_agent_router::{PatchAction, PatchType};
+        use crate::ai::patch_review::{FileDiff, PatchPreview};
+
+        let (mut orch, mut bus_rx, _ai_rx) = setup();
+
+        let action = |path: &str| PatchAction {
+            file_path: path.into(),
+            description: "rewrite".into(),
+            change_type: PatchType::ModifyFile,
+            content: Some("x".into()),
+            search: None,
+        };
+        let preview = PatchPreview {
+            node_id: "fix".into(),
+            description: "fix both".into(),
+            actions: vec![action("a.rs"), action("b.rs")],
+            files: vec![FileDiff {
+                file_path: "a.rs".into(),
+                actions: vec![0],
+                diff: "+x\n".into(),
+                error: None,
+            }],
+        };
+        orch.on_event(AxonEvent::PatchReviewRequested {
+            id: "p1".into(),
+            agent: "coder".into(),
+            preview,
+            timeout_seconds: 60,
+        }).await.unwrap();
+
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { origin, text, .. } => {
+                assert_eq!(origin, Ingress::Cli);
+                assert!(text.contains("2. ModifyFile b.rs"), "{}", text);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        // Only approvers decide
+        for origin in [Ingress::WebSocket, Ingress::Telegram { chat_id: 42 }] {
+            orch.dispatch(Uuid::new_v4(), "/approve p1".into(), origin.clone()).await.unwrap();
+            match bus_rx.recv().await.unwrap() {
+                AxonEvent::CommandReply { origin: to, text, .. } => {
+                    assert_eq!(to, origin);
+                    assert_eq!(text, "Only an approver can decide p1");
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        orch.dispatch(Uuid::new_v4(), "/approve p1 3".into(), Ingress::Cli).await.unwrap();
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::CommandReply { text, .. } => assert_eq!(text, "Patch p1 has no action 3"),
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        orch.dispatch(Uuid::new_v4(), "/approve p1 2".into(), Ingress::Cli).await.unwrap();
+        match bus_rx.recv().await.unwrap() {
+            AxonEvent::PatchReviewDecided { id, decision, .. } => {
+                assert_eq!(id, "p1");
+                assert_eq!(decision, ReviewDecision::Approve { actions: Some(vec![1]) });
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+}
@@ -1,5 +1,6 @@
-﻿use crate::core::state::AppState;
-use crate::event::bus::EventSender;
+﻿use crate::ai::model_router::AiTaskType;
+use crate::core::state::AppState;
+use crate::event::bus::AiSender;
 use crate::event::event::AxonEvent;
 use anyhow::Result;
 use std::sync::Arc;
@@ -7,19 +8,20 @@ use tracing::info;
 
 pub async fn handle_event(
     event: AxonEvent,
-    state: Arc<AppState>,
-    tx: EventSender,
+    _state: Arc<AppState>,
+    ai_tx: AiSender,
 ) -> Result<()> {
     match event {
-        AxonEvent::AiRequest { id, prompt, model, context } => {
+        AxonEvent::AiRequest { id, prompt, model, context, task, reflect } => {
             info!("Processing AI Request: {}", id);
             crate::orchestrator::router::handle_ai_request(
                 id,
                 prompt,
                 model,
                 context,
-                state,
-                tx,
+                task,
+                reflect,
+                ai_tx,
             ).await?;
         }
 
@@ -32,10 +34,11 @@ pub async fn handle_event(
                 crate::orchestrator::router::handle_ai_request(
                     req_id,
                     message,
-                    Some("deepseek-r1:8b".into()),
                     None,
-                    state,
-                    tx,
+                    None,
+                    Some(AiTaskType::Analysis),
+                    None,
+                    ai_tx,
                 ).await?;
             }
         }
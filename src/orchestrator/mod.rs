@@ -1,3 +1,5 @@
 ﻿pub mod classifier;
+pub mod command;
+pub mod event_loop;
 pub mod handler;
 pub mod router;
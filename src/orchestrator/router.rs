@@ -1,26 +1,29 @@
 ﻿use uuid::Uuid;
-use std::sync::Arc;
-use crate::event::bus::EventSender;
+use crate::event::bus::AiSender;
 use crate::event::event::{AxonEvent};
-use crate::core::state::AppState;
+use crate::ai::model_router::AiTaskType;
 
-/// Handler aliniat cu handler.rs (6 argumente)
+/// Handler aliniat cu handler.rs (7 argumente)
 pub async fn handle_ai_request(
     request_id: Uuid,
     prompt: String,
     model: Option<String>,
     context: Option<String>,
-    _state: Arc<AppState>,
-    tx: EventSender,
+    task: Option<AiTaskType>,
+    reflect: Option<bool>,
+    ai_tx: AiSender,
 ) -> anyhow::Result<()> {
     tracing::info!("AI request received: {}", request_id);
     
-    tx.send(AxonEvent::AiRequest {
+    // Model stays None unless the caller pinned one; the AI runtime routes by task
+    ai_tx.send(AxonEvent::AiRequest {
         id: request_id,
+        task: task.or_else(|| Some(AiTaskType::infer(&prompt))),
         prompt,
-        model: model.or(Some("deepseek-r1:8b".into())),
+        model,
         context,
-    })?;
+        reflect,
+    }).await?;
 
     Ok(())
 }
@@ -29,17 +32,19 @@ pub async fn handle_ai_request(
 pub async fn handle_file_detected(
     path: String,
     content: String,
-    tx: EventSender,
+    ai_tx: AiSender,
 ) -> anyhow::Result<()> {
     let req_id = Uuid::new_v4();
     tracing::info!("AXON ROUTER: Analiza automata pentru: {}", path);
 
-    tx.send(AxonEvent::AiRequest {
+    ai_tx.send(AxonEvent::AiRequest {
         id: req_id,
         prompt: format!("Analizeaza acest cod Rust:\nPath: {}\n\n{}", path, content),
-        model: Some("deepseek-r1:8b".into()),
+        model: None,
         context: None,
-    })?;
+        task: Some(AiTaskType::Coding),
+        reflect: None,
+    }).await?;
 
     Ok(())
 }
@@ -1,9 +1,14 @@
-﻿pub fn chunk_text(content: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+﻿/// Pieces of about `chunk_size` bytes, each repeating the last `overlap`
+/// bytes of the one before; cuts never split a character
+pub fn chunk_text(content: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
     let mut chunks = Vec::new();
     let mut start = 0;
 
     while start < content.len() {
-        let end = (start + chunk_size).min(content.len());
+        let mut end = (start + chunk_size.max(1)).min(content.len());
+        while !content.is_char_boundary(end) {
+            end += 1;
+        }
         let slice = &content[start..end];
         chunks.push(slice.to_string());
 
@@ -11,8 +16,28 @@
             break;
         }
 
-        start = end.saturating_sub(overlap);
+        let mut next = end.saturating_sub(overlap);
+        while !content.is_char_boundary(next) {
+            next -= 1;
+        }
+        start = if next > start { next } else { end };
     }
 
     chunks
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_overlap_and_keep_characters_whole() {
+        assert_eq!(chunk_text("abcdefgh", 4, 1), ["abcd", "defg", "gh"]);
+
+        let text = "așteptăm să apară fișierul";
+        let chunks = chunk_text(text, 5, 2);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| text.contains(c.as_str())));
+        assert!(chunks.last().unwrap().ends_with("erul"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Fills the vector store the retriever searches: the project's text files
+//! at startup and when they change, and every AI answer.
+
+use std::path::Path;
+
+use anyhow::Result;
+use tracing::{debug, info};
+
+use crate::core::state::AppState;
+use crate::rag::chunker::chunk_text;
+use crate::rag::embedder::embed;
+
+const CHUNK_SIZE: usize = 1200;
+const CHUNK_OVERLAP: usize = 200;
+/// Larger files are skipped
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+const EXTENSIONS: &[&str] = &["rs", "md", "toml", "txt", "js", "ts", "py", "json", "yaml", "yml"];
+const SKIP_DIRS: &[&str] = &["target", "node_modules"];
+
+/// Index `text` under `source`, replacing what was stored for it; the
+/// number of chunks stored
+pub async fn index_text(state: &AppState, source: &str, text: &str) -> Result<usize> {
+    let ai = &state.config.ai;
+    let endpoint = ai.ollama_endpoint.as_deref().unwrap_or_default();
+
+    let mut chunks = Vec::new();
+    for chunk in chunk_text(text, CHUNK_SIZE, CHUNK_OVERLAP) {
+        if chunk.trim().is_empty() {
+            continue;
+        }
+        let embedding = embed(endpoint, &ai.embed_model.name, &chunk).await?;
+        chunks.push((chunk, embedding));
+    }
+
+    let count = chunks.len();
+    let mut store = state.vector_store.write().await;
+    store.replace(source, chunks);
+    *state.rag_indexed.write().await = store.chunk_count() as u64;
+    Ok(count)
+}
+
+/// Index one file, named relative to `root`
+pub async fn index_file(state: &AppState, root: &Path, path: &Path) -> Result<usize> {
+    let text = tokio::fs::read_to_string(path).await?;
+    let name = path.strip_prefix(root).unwrap_or(path).to_string_lossy().into_owned();
+    index_text(state, &name, &text).await
+}
+
+/// Index the text files under `root`, skipping hidden and build
+/// directories; the number of files indexed
+pub async fn index_dir(state: &AppState, root: &Path) -> Result<usize> {
+    let mut dirs = vec![root.to_path_buf()];
+    let mut files = 0;
+
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)?.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+
+            if kind.is_dir() {
+                if !name.starts_with('.') && !SKIP_DIRS.contains(&name.as_str()) {
+                    dirs.push(path);
+                }
+                continue;
+            }
+
+            let indexable = kind.is_file()
+                && path.extension().and_then(|e| e.to_str()).is_some_and(|e| EXTENSIONS.contains(&e))
+                && entry.metadata().is_ok_and(|m| m.len() <= MAX_FILE_BYTES);
+            if !indexable {
+                continue;
+            }
+
+            match index_file(state, root, &path).await {
+                Ok(_) => files += 1,
+                Err(e) => debug!("Not indexed {}: {:#}", path.display(), e),
+            }
+        }
+    }
+
+    info!("RAG indexed {} file(s) under {}", files, root.display());
+    Ok(files)
+}
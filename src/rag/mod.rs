@@ -1,3 +1,5 @@
 ﻿pub mod chunker;
 pub mod embedder;
+pub mod indexer;
+pub mod search;
 pub mod vector_store;
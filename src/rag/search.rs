@@ -1,8 +1,123 @@
-﻿pub mod identity;
-pub mod persistence;
+use std::path::Path;
+use std::sync::Arc;
 
+use anyhow::Result;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
 
+use crate::core::state::AppState;
+use crate::event::bus::EventSender;
+use crate::event::event::{AxonEvent, WorkerHealth};
+use crate::rag::indexer::{index_dir, index_file, index_text};
 
+const TOP_K: usize = 5;
 
+/// Query the shared vector store and format hits for display
+pub async fn retrieve(state: &AppState, query: &str, top_k: usize) -> Vec<String> {
+    let store = state.vector_store.read().await;
 
+    store
+        .search(query, top_k)
+        .into_iter()
+        .map(|(file, chunk, score)| format!("[{} | {:.2}] {}", file, score, chunk.trim()))
+        .collect()
+}
 
+/// Retriever worker: indexes `filesystem.watch_path`, keeps changed files
+/// and AI answers indexed, and answers `RagSearch` events with
+/// `RagSearchResult`
+pub async fn run(tx: EventSender, state: Arc<AppState>) -> Result<()> {
+    let mut rx = tx.subscribe();
+
+    state.update_worker("rag_retriever", WorkerHealth::Running).await;
+    let root = Path::new(&state.config.filesystem.watch_path).to_path_buf();
+    if let Err(e) = index_dir(&state, &root).await {
+        warn!("RAG indexing of {} failed: {:#}", root.display(), e);
+    }
+    info!("RAG retriever ACTIVE");
+
+    loop {
+        match rx.recv().await {
+            Ok(AxonEvent::FileChanged { path }) => {
+                if let Err(e) = index_file(&state, &root, Path::new(&path)).await {
+                    warn!("RAG could not reindex {}: {:#}", path, e);
+                }
+            }
+            Ok(AxonEvent::AiResponse { request_id, output, .. }) => {
+                if let Err(e) = index_text(&state, &format!("conversation/{}", request_id), &output).await {
+                    warn!("RAG could not index answer {}: {:#}", request_id, e);
+                }
+            }
+            Ok(AxonEvent::RagSearch { query, request_id }) => {
+                let results = retrieve(&state, &query, TOP_K).await;
+
+                tx.send(AxonEvent::RagSearchResult {
+                    request_id,
+                    query,
+                    results,
+                })?;
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("RAG retriever lagged, skipped {} events", skipped);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+
+    state.update_worker("rag_retriever", WorkerHealth::Stopped).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::AxonConfig;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_documents_and_answers_are_retrieved() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.md"), "# Retries\nThe retry loop backs off exponentially.\n").unwrap();
+        std::fs::create_dir(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("target/build.txt"), "exponentially generated").unwrap();
+
+        let mut config = AxonConfig::default();
+        config.filesystem.watch_path = dir.path().to_string_lossy().into_owned();
+        let state = Arc::new(AppState::new(config));
+        let (tx, mut rx) = tokio::sync::broadcast::channel(16);
+        tokio::spawn(run(tx.clone(), state.clone()));
+        // Events sent before the worker subscribed would be missed
+        while !state.worker_status.read().await.contains_key("rag_retriever") {
+            tokio::task::yield_now().await;
+        }
+
+        let answer = Uuid::new_v4();
+        tx.send(AxonEvent::AiResponse {
+            request_id: answer,
+            output: "Run cargo check after every patch.".into(),
+            model: "m".into(),
+            context: None,
+            response: String::new(),
+            routing: None,
+            trace: None,
+            confidence: None,
+            attempts: None,
+        }).unwrap();
+
+        for (query, source) in [("backs off exponentially", "notes.md".to_string()), ("cargo check", format!("conversation/{}", answer))] {
+            let request_id = Uuid::new_v4();
+            tx.send(AxonEvent::RagSearch { query: query.into(), request_id }).unwrap();
+
+            let results = loop {
+                match rx.recv().await.unwrap() {
+                    AxonEvent::RagSearchResult { request_id: id, results, .. } if id == request_id => break results,
+                    _ => continue,
+                }
+            };
+            assert_eq!(results.len(), 1, "{:?}", results);
+            assert!(results[0].starts_with(&format!("[{} |", source)), "{:?}", results);
+        }
+        assert_eq!(*state.rag_indexed.read().await, 2);
+    }
+}
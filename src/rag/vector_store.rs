@@ -17,9 +17,55 @@ impl VectorStore {
         self.data.entry(file).or_default().push((chunk, embedding)); // Ã¢Å“â€¦ parantezÃ„Æ’ ÃƒÂ®nchisÃ„Æ’ corect
     }
 
+    /// Store `chunks` as everything known about `file`
+    pub fn replace(&mut self, file: &str, chunks: Vec<(String, Vec<f32>)>) {
+        if chunks.is_empty() {
+            self.data.remove(file);
+        } else {
+            self.data.insert(file.to_string(), chunks);
+        }
+    }
+
     pub fn save(&self, _path: &Path) -> Result<()> {
         Ok(())
     }
+
+    pub fn chunk_count(&self) -> usize {
+        self.data.values().map(Vec::len).sum()
+    }
+
+    /// Rank stored chunks against a query, best first.
+    ///
+    /// The embedder is still a stub, so ranking is lexical: the fraction of
+    /// query terms that appear in the chunk.
+    pub fn search(&self, query: &str, top_k: usize) -> Vec<(String, String, f32)> {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|t| t.to_lowercase())
+            .collect();
+
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<(String, String, f32)> = self.data
+            .iter()
+            .flat_map(|(file, chunks)| {
+                chunks.iter().map(move |(chunk, _)| (file, chunk))
+            })
+            .filter_map(|(file, chunk)| {
+                let lower = chunk.to_lowercase();
+                let matched = terms.iter().filter(|t| lower.contains(t.as_str())).count();
+                (matched > 0).then(|| {
+                    (file.clone(), chunk.clone(), matched as f32 / terms.len() as f32)
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.2.total_cmp(&a.2));
+        hits.truncate(top_k);
+        hits
+    }
 }
 
 impl Default for VectorStore { fn default() -> Self { Self::new() } }
@@ -1,9 +1,12 @@
-﻿use anyhow::Result;
+﻿use std::path::Path;
+
+use anyhow::Result;
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 use tracing::warn;
 
 use crate::config::schema::AxonConfig;
+use crate::shell::sandbox::{Sandbox, SandboxOutput};
 
 /// Runs a shell command with a configurable timeout
 pub async fn run_command(
@@ -11,14 +14,36 @@ pub async fn run_command(
     args: &[String],
     config: &AxonConfig,
 ) -> Result<(String, String, i32)> {
-    let timeout_secs = config.shell.timeout_seconds;
+    run_command_in(bin, args, None, config).await
+}
+
+/// Like `run_command`, from working directory `dir` when given
+pub async fn run_command_in(
+    bin: &str,
+    args: &[String],
+    dir: Option<&Path>,
+    config: &AxonConfig,
+) -> Result<(String, String, i32)> {
+    run_command_for(bin, args, dir, Duration::from_secs(config.shell.timeout_seconds)).await
+}
 
+/// Like `run_command_in`, allowing the command `limit` instead of
+/// `shell.timeout_seconds`
+async fn run_command_for(
+    bin: &str,
+    args: &[String],
+    dir: Option<&Path>,
+    limit: Duration,
+) -> Result<(String, String, i32)> {
     let mut cmd = Command::new(bin);
-    cmd.args(args);
+    cmd.args(args).kill_on_drop(true);
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
 
     let future = cmd.output();
 
-    let output = timeout(Duration::from_secs(timeout_secs), future).await;
+    let output = timeout(limit, future).await;
 
     match output {
         Ok(Ok(out)) => {
@@ -39,4 +64,79 @@ pub async fn run_command(
             Err(anyhow::anyhow!("Command timed out"))
         }
     }
-}
\ No newline at end of file
+}
+/// Whether `line` matches `shell.require_approval_for`
+pub fn needs_approval(line: &str, config: &AxonConfig) -> bool {
+    let mut parts = line.split_whitespace().map(String::from);
+    let bin = parts.next().unwrap_or_default();
+    let args: Vec<String> = parts.collect();
+
+    crate::shell::whitelist::requires_approval(&bin, &args, config)
+}
+
+/// Run a whitelisted command whose approval, if any, was already granted
+pub async fn run_allowed_in(line: &str, dir: Option<&Path>, config: &AxonConfig) -> Result<String> {
+    let mut parts = line.split_whitespace().map(String::from);
+    let bin = parts.next().ok_or_else(|| anyhow::anyhow!("Empty command"))?;
+    let args: Vec<String> = parts.collect();
+
+    if !crate::shell::whitelist::is_allowed(&bin, config) {
+        anyhow::bail!("Command not allowed: {}", bin);
+    }
+
+    let (stdout, stderr, code) = run_command_in(&bin, &args, dir, config).await?;
+    let output = format!("{}{}", stdout, stderr);
+
+    if code != 0 {
+        anyhow::bail!("{} exited with {}\n{}", bin, code, output);
+    }
+
+    Ok(output)
+}
+
+/// Run a whitelisted command from an AI tool inside `root`: sandboxed
+/// (`shell.sandbox`) unless the sandbox is disabled. Approval, if needed,
+/// must already be granted.
+pub async fn run_for_ai(line: &str, root: &Path, config: &AxonConfig) -> Result<String> {
+    let mut parts = line.split_whitespace().map(String::from);
+    let bin = parts.next().ok_or_else(|| anyhow::anyhow!("Empty command"))?;
+    let args: Vec<String> = parts.collect();
+
+    let limit = Duration::from_secs(config.shell.timeout_seconds);
+    let out = run_confined(&bin, &args, root, config, limit).await?;
+
+    let output = format!("{}{}", out.stdout, out.stderr);
+    match out.code {
+        Some(0) => Ok(output),
+        Some(code) => anyhow::bail!("{} exited with {}\n{}", bin, code, output),
+        None => anyhow::bail!("{} was killed (resource limit?)\n{}", bin, output),
+    }
+}
+
+/// Run whitelisted `bin` for an AI tool inside `root` like `run_for_ai`,
+/// allowing it `limit`. A non-zero exit is returned, not an error, for
+/// tools that report it themselves (e.g. a failed build).
+pub async fn run_confined(
+    bin: &str,
+    args: &[String],
+    root: &Path,
+    config: &AxonConfig,
+    limit: Duration,
+) -> Result<SandboxOutput> {
+    if !crate::shell::whitelist::is_allowed(bin, config) {
+        anyhow::bail!("Command not allowed: {}", bin);
+    }
+
+    if !config.shell.sandbox.enabled {
+        let (stdout, stderr, code) = run_command_for(bin, args, Some(root), limit).await?;
+        return Ok(SandboxOutput { stdout, stderr, code: Some(code), truncated: false, timed_out: false });
+    }
+
+    let sandbox = Sandbox::new(root, config.shell.sandbox.clone(), limit);
+    let out = sandbox.run(bin, args).await?;
+
+    if out.timed_out {
+        anyhow::bail!("{} timed out after {}s", bin, limit.as_secs());
+    }
+    Ok(out)
+}
@@ -1,2 +1,3 @@
 ﻿pub mod command;
+pub mod sandbox;
 pub mod whitelist;
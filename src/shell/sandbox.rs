@@ -0,0 +1,288 @@
+//! Confined execution for commands issued by AI tools.
+//!
+//! The command runs in the project root with a scrubbed environment, its
+//! own process group and, on Linux, rlimits on CPU, memory, processes and
+//! file size. Output is capped and the whole group is killed on timeout.
+//!
+//! Keeping the command inside the root is best-effort: arguments that look
+//! like paths outside it are refused, and so is inline code for an
+//! interpreter (`sh -c`, `python -c`), but a program can still open any
+//! path it computes itself. The whitelist is the real boundary.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+
+use crate::config::schema::SandboxConfig;
+use crate::util::path::confine;
+
+/// What a sandboxed command left behind
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// None when killed by a signal
+    pub code: Option<i32>,
+    /// Output beyond `max_output_bytes` was dropped
+    pub truncated: bool,
+    pub timed_out: bool,
+}
+
+pub struct Sandbox {
+    root: PathBuf,
+    config: SandboxConfig,
+    timeout: Duration,
+}
+
+impl Sandbox {
+    pub fn new(root: impl Into<PathBuf>, config: SandboxConfig, timeout: Duration) -> Self {
+        Self { root: root.into(), config, timeout }
+    }
+
+    /// Run `bin` with `args` from the sandbox root. Path arguments must stay
+    /// inside the root and interpreters get no inline code.
+    pub async fn run(&self, bin: &str, args: &[String]) -> Result<SandboxOutput> {
+        let root = self.root.canonicalize()
+            .with_context(|| format!("Sandbox root {} not found", self.root.display()))?;
+        check_inline_code(bin, args)?;
+        check_paths(&root, args)?;
+
+        let mut cmd = Command::new(bin);
+        cmd.args(args)
+            .current_dir(&root)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        for name in &self.config.env_allow {
+            if let Some(value) = std::env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        #[cfg(target_os = "linux")]
+        apply_rlimits(&mut cmd, &self.config);
+
+        let mut child = cmd.spawn().with_context(|| format!("Cannot start {}", bin))?;
+        let pid = child.id();
+
+        let stdout = child.stdout.take().context("stdout not captured")?;
+        let stderr = child.stderr.take().context("stderr not captured")?;
+        let max = self.config.max_output_bytes;
+
+        let finished = tokio::time::timeout(self.timeout, async {
+            let (out, err, status) = tokio::join!(read_capped(stdout, max), read_capped(stderr, max), child.wait());
+            (out, err, status)
+        }).await;
+
+        let ((stdout, out_cut), (stderr, err_cut), code, timed_out) = match finished {
+            Ok((out, err, status)) => (out?, err?, status?.code(), false),
+            Err(_) => {
+                tracing::warn!("Sandboxed {} timed out, killing its process group", bin);
+                kill_group(pid);
+                let _ = child.kill().await;
+                ((String::new(), false), (String::new(), false), None, true)
+            }
+        };
+
+        Ok(SandboxOutput { stdout, stderr, code, truncated: out_cut || err_cut, timed_out })
+    }
+}
+
+/// Interpreters and the flags that take code to run instead of a file
+const INLINE_CODE: &[(&[&str], &[&str])] = &[
+    (&["sh", "bash", "dash", "zsh", "ksh", "fish"], &["-c"]),
+    (&["python", "python3"], &["-c"]),
+    (&["perl", "ruby"], &["-e", "-E"]),
+    (&["node", "deno"], &["-e", "--eval", "-p", "--print"]),
+];
+
+/// Refuse code passed to an interpreter on the command line, which no
+/// path check can see into
+fn check_inline_code(bin: &str, args: &[String]) -> Result<()> {
+    let name = Path::new(bin).file_name().and_then(|n| n.to_str()).unwrap_or(bin);
+    let Some((_, flags)) = INLINE_CODE.iter().find(|(names, _)| names.contains(&name)) else {
+        return Ok(());
+    };
+
+    for arg in args {
+        // Short flags may be bundled (`sh -ec`)
+        let bundled = |flag: &&str| {
+            flag.len() == 2 && arg.len() > 1 && arg.starts_with('-') && !arg.starts_with("--") && arg[1..].contains(&flag[1..])
+        };
+        if flags.iter().any(|flag| arg == flag || arg.starts_with(&format!("{}=", flag)) || bundled(flag)) {
+            bail!("Inline code for {} is not allowed: {}", name, arg);
+        }
+    }
+    Ok(())
+}
+
+/// Refuse arguments that name paths outside `root`, including the values
+/// of `--flag=value` and of short flags written together (`-f/etc/passwd`)
+fn check_paths(root: &Path, args: &[String]) -> Result<()> {
+    for arg in args {
+        let mut values = vec![arg.as_str()];
+        if let Some((_, value)) = arg.split_once('=') {
+            values.push(value);
+        }
+        if arg.len() > 2 && arg.starts_with('-') && !arg.starts_with("--") {
+            values.extend(arg.get(2..));
+        }
+
+        for value in values {
+            let looks_like_path = value.starts_with('/') || value.starts_with('~') || value.split('/').any(|p| p == "..");
+            if !looks_like_path {
+                continue;
+            }
+            if value.starts_with('~') {
+                bail!("Path outside the project: {}", arg);
+            }
+            confine(root, value).with_context(|| format!("Path outside the project: {}", arg))?;
+        }
+    }
+    Ok(())
+}
+
+/// Read everything, keeping the first `max` bytes so the child never
+/// blocks on a full pipe
+async fn read_capped(mut reader: impl AsyncRead + Unpin, max: usize) -> Result<(String, bool)> {
+    let mut kept = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let mut truncated = false;
+
+    loop {
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        let room = max.saturating_sub(kept.len());
+        kept.extend_from_slice(&chunk[..n.min(room)]);
+        truncated |= n > room;
+    }
+
+    let mut text = String::from_utf8_lossy(&kept).into_owned();
+    if truncated {
+        text.push_str("\n... [output truncated]");
+    }
+    Ok((text, truncated))
+}
+
+#[cfg(target_os = "linux")]
+fn apply_rlimits(cmd: &mut Command, config: &SandboxConfig) {
+    let limits = [
+        (libc::RLIMIT_CPU, config.cpu_seconds),
+        (libc::RLIMIT_AS, config.memory_mb * 1024 * 1024),
+        (libc::RLIMIT_NPROC, config.max_processes),
+        (libc::RLIMIT_FSIZE, config.max_file_mb * 1024 * 1024),
+    ];
+
+    // SAFETY: only async-signal-safe setrlimit calls between fork and exec
+    unsafe {
+        cmd.pre_exec(move || {
+            for (resource, value) in limits {
+                let limit = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t };
+                if libc::setrlimit(resource, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn kill_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // SAFETY: plain syscall; the group id is the child's pid (`process_group(0)`)
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kill_group(_pid: Option<u32>) {}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn sandbox(dir: &Path, timeout_ms: u64) -> Sandbox {
+        let config = SandboxConfig { max_output_bytes: 1000, ..Default::default() };
+        Sandbox::new(dir, config, Duration::from_millis(timeout_ms))
+    }
+
+    /// `script` saved in `dir`, as the arguments that run it with `sh`
+    fn sh(dir: &Path, script: &str) -> Vec<String> {
+        std::fs::write(dir.join("script.sh"), script).unwrap();
+        vec!["script.sh".into()]
+    }
+
+    #[tokio::test]
+    async fn test_env_cwd_and_truncation() {
+        // The secret is only set for a copy of this test run as a child
+        // process, so no other test sees a changed environment
+        if std::env::var_os("AXON_SANDBOX_SECRET").is_none() {
+            let out = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "shell::sandbox::tests::test_env_cwd_and_truncation"])
+                .env("AXON_SANDBOX_SECRET", "hunter2")
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            assert!(out.status.success() && stdout.contains("1 passed"), "{}", stdout);
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = sh(dir.path(), "pwd; echo \"secret=$AXON_SANDBOX_SECRET\"; head -c 5000 /dev/zero | tr '\\0' x");
+        let out = sandbox(dir.path(), 5000).run("sh", &script).await.unwrap();
+
+        let mut lines = out.stdout.lines();
+        assert_eq!(Path::new(lines.next().unwrap()), dir.path().canonicalize().unwrap());
+        assert_eq!(lines.next().unwrap(), "secret=");
+        assert!(out.truncated);
+        assert_eq!(out.code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // The background sleeper would outlive a kill of `sh` alone
+        let script = sh(dir.path(), "sleep 30 & echo $! > pid; wait");
+        let out = sandbox(dir.path(), 300).run("sh", &script).await.unwrap();
+        assert!(out.timed_out);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let pid = std::fs::read_to_string(dir.path().join("pid")).unwrap();
+        let alive = Path::new(&format!("/proc/{}", pid.trim())).exists()
+            && !std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default().contains(") Z ");
+        assert!(!alive, "background process survived the timeout");
+    }
+
+    #[tokio::test]
+    async fn test_paths_outside_root_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let sandbox = sandbox(dir.path(), 1000);
+
+        for arg in ["/etc/passwd", "../x", "--file=/etc/hosts", "-f/etc/passwd", "-C/etc", "-I../include"] {
+            assert!(sandbox.run("cat", &[arg.to_string()]).await.is_err(), "{} was allowed", arg);
+        }
+        assert!(sandbox.run("ls", &["src/../.".to_string()]).await.is_ok());
+        assert!(sandbox.run("ls", &["-la".to_string()]).await.is_ok());
+
+        for (bin, args) in [("sh", vec!["-c", "cat x"]), ("/bin/bash", vec!["-ec", "ls"]), ("python3", vec!["-c", "print(1)"]), ("node", vec!["--eval=1"])] {
+            let args: Vec<String> = args.into_iter().map(String::from).collect();
+            assert!(sandbox.run(bin, &args).await.is_err(), "{} {:?} was allowed", bin, args);
+        }
+    }
+}
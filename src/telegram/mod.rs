@@ -1,8 +1,220 @@
-﻿use crate::core::state::AppState;
-use crate::event::bus::EventSender;
-use anyhow::Result;
+﻿//! Telegram ingress for the `telegram.admin_id` chat.
+//!
+//! Messages from the admin chat become `TelegramCommand` events; other
+//! chats are ignored. `CommandReply`s addressed to the chat are sent back,
+//! approval and patch review requests included (see `ai::approval`);
+//! replies too long for one message, such as large diffs, go as a document.
+
 use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::ai::approval::telegram_admin;
+use crate::core::state::AppState;
+use crate::event::bus::EventSender;
+use crate::event::event::{AxonEvent, Ingress, WorkerHealth};
+
+/// Seconds a `getUpdates` call waits for new messages
+const POLL_SECONDS: u64 = 30;
+
+/// Longest text Telegram accepts in one message
+pub const MESSAGE_LIMIT: usize = 4096;
+
+/// Bot API client
+#[derive(Clone)]
+pub struct TelegramApi {
+    client: Client,
+    base: String,
+}
+
+impl TelegramApi {
+    pub fn new(token: &str) -> Self {
+        Self::with_base(format!("https://api.telegram.org/bot{}", token.trim()))
+    }
+
+    /// Client for another Bot API server, e.g. a local one
+    pub fn with_base(base: impl Into<String>) -> Self {
+        Self { client: Client::new(), base: base.into() }
+    }
+
+    async fn call(&self, method: &str, request: reqwest::RequestBuilder) -> Result<Value> {
+        let reply: Value = request
+            .send()
+            .await
+            .with_context(|| format!("Telegram {} failed", method))?
+            .json()
+            .await
+            .with_context(|| format!("Invalid Telegram {} response", method))?;
+
+        if reply["ok"].as_bool() != Some(true) {
+            bail!("Telegram {} failed: {}", method, reply["description"].as_str().unwrap_or("no description"));
+        }
+        Ok(reply["result"].clone())
+    }
+
+    pub async fn send_message(&self, chat_id: i64, text: &str) -> Result<()> {
+        let request = self
+            .client
+            .post(format!("{}/sendMessage", self.base))
+            .json(&json!({ "chat_id": chat_id, "text": text }));
+
+        self.call("sendMessage", request).await.map(drop)
+    }
+
+    /// Send `content` as a file named `file_name`
+    pub async fn send_document(&self, chat_id: i64, file_name: &str, content: String, caption: &str) -> Result<()> {
+        let file = reqwest::multipart::Part::text(content).file_name(file_name.to_string());
+        let form = reqwest::multipart::Form::new()
+            .text("chat_id", chat_id.to_string())
+            .text("caption", caption.to_string())
+            .part("document", file);
+        let request = self.client.post(format!("{}/sendDocument", self.base)).multipart(form);
+
+        self.call("sendDocument", request).await.map(drop)
+    }
+
+    /// Updates after `offset`, waiting up to `POLL_SECONDS` for one
+    pub async fn updates(&self, offset: i64) -> Result<Vec<Value>> {
+        let request = self
+            .client
+            .post(format!("{}/getUpdates", self.base))
+            .timeout(Duration::from_secs(POLL_SECONDS + 10))
+            .json(&json!({ "offset": offset, "timeout": POLL_SECONDS, "allowed_updates": ["message"] }));
+
+        Ok(self.call("getUpdates", request).await?.as_array().cloned().unwrap_or_default())
+    }
+
+    /// Send `text`; text too long for one message goes as a document whose
+    /// caption is the first line
+    pub async fn deliver(&self, chat_id: i64, file_name: &str, text: &str) -> Result<()> {
+        match split_for_delivery(text) {
+            None => self.send_message(chat_id, text).await,
+            Some(caption) => self.send_document(chat_id, file_name, text.to_string(), &caption).await,
+        }
+    }
+}
 
-pub async fn run(_tx: EventSender, _state: Arc<AppState>) -> Result<()> {
+/// Caption when `text` must go as a document, `None` when it fits a message
+pub fn split_for_delivery(text: &str) -> Option<String> {
+    if text.chars().count() <= MESSAGE_LIMIT {
+        return None;
+    }
+    // Captions are limited to 1024 characters
+    Some(text.lines().next().unwrap_or_default().chars().take(1000).collect())
+}
+
+/// `TelegramCommand` for a `message` update sent by `admin`, if any
+pub fn command_from_update(update: &Value, admin: i64) -> Option<AxonEvent> {
+    let message = &update["message"];
+    let chat_id = message["chat"]["id"].as_i64()?;
+    let text = message["text"].as_str()?.trim();
+
+    if chat_id != admin || text.is_empty() {
+        return None;
+    }
+
+    // "/approve@axon_bot ab12" -> "/approve ab12"
+    let mut words = text.split_whitespace();
+    let first = words.next()?;
+    let first = if first.starts_with('/') { first.split('@').next().unwrap_or(first) } else { first };
+    let text = match text[text.find(char::is_whitespace).unwrap_or(text.len())..].trim_start() {
+        "" => first.to_string(),
+        rest => format!("{} {}", first, rest),
+    };
+
+    Some(AxonEvent::TelegramCommand {
+        chat_id,
+        command: first.strip_prefix('/').map(str::to_string),
+        args: words.map(str::to_string).collect(),
+        text,
+    })
+}
+
+/// Poll the admin chat and deliver replies to it; idle without a bot
+/// token or admin id
+pub async fn run(tx: EventSender, state: Arc<AppState>) -> Result<()> {
+    let Some(admin) = telegram_admin(&state.config) else {
+        info!("Telegram off (set telegram.bot_token and telegram.admin_id)");
+        return Ok(());
+    };
+
+    let api = TelegramApi::new(&state.config.telegram.bot_token);
+    state.update_worker("telegram", WorkerHealth::Running).await;
+    info!("Telegram ACTIVE for chat {}", admin);
+
+    let delivery = tokio::spawn(deliver_replies(api.clone(), tx.subscribe()));
+    let mut offset = 0;
+
+    while !delivery.is_finished() {
+        match api.updates(offset).await {
+            Ok(updates) => {
+                for update in updates {
+                    offset = offset.max(update["update_id"].as_i64().unwrap_or(0) + 1);
+
+                    if let Some(command) = command_from_update(&update, admin) {
+                        tx.send(command)?;
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Telegram polling failed: {:#}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+
+    state.update_worker("telegram", WorkerHealth::Stopped).await;
     Ok(())
 }
+
+async fn deliver_replies(api: TelegramApi, mut rx: tokio::sync::broadcast::Receiver<AxonEvent>) {
+    loop {
+        match rx.recv().await {
+            Ok(AxonEvent::CommandReply { request_id, origin: Ingress::Telegram { chat_id }, text, .. }) => {
+                let file_name = format!("axon-{}.txt", request_id.simple());
+
+                if let Err(e) = api.deliver(chat_id, &file_name, &text).await {
+                    warn!("Telegram reply to {} not delivered: {:#}", chat_id, e);
+                }
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(skipped)) => warn!("Telegram delivery lagged, skipped {} events", skipped),
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_admin_messages_become_commands() {
+        let update = |chat: i64, text: &str| json!({ "update_id": 1, "message": { "chat": { "id": chat }, "text": text } });
+
+        match command_from_update(&update(42, "/approve@axon_bot ab12 1,3"), 42) {
+            Some(AxonEvent::TelegramCommand { text, chat_id, command, args }) => {
+                assert_eq!(text, "/approve ab12 1,3");
+                assert_eq!(chat_id, 42);
+                assert_eq!(command.as_deref(), Some("approve"));
+                assert_eq!(args, ["ab12", "1,3"]);
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(command_from_update(&update(7, "/approve ab12"), 42).is_none());
+        assert!(command_from_update(&json!({ "update_id": 2, "edited_message": {} }), 42).is_none());
+    }
+
+    #[test]
+    fn test_long_replies_go_as_documents() {
+        assert_eq!(split_for_delivery("short"), None);
+
+        let diff = format!("Patch review [ab12]: Agent 'coder' proposes a patch\n{}", "+ line\n".repeat(1000));
+        assert_eq!(split_for_delivery(&diff).as_deref(), Some("Patch review [ab12]: Agent 'coder' proposes a patch"));
+    }
+}
@@ -0,0 +1,271 @@
+//! Compiler messages from `cargo --message-format=json`.
+
+use std::collections::HashSet;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Lines shown above and below the code a diagnostic points at
+pub const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Span {
+    pub file: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: usize,
+    pub column_end: usize,
+}
+
+/// A replacement rustc proposes for `span`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+    /// `MachineApplicable`, `MaybeIncorrect`, `HasPlaceholders` or `Unspecified`
+    pub applicability: Option<String>,
+}
+
+impl Suggestion {
+    /// Safe to apply without looking
+    pub fn is_machine_applicable(&self) -> bool {
+        self.applicability.as_deref() == Some("MachineApplicable")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Diagnostic {
+    /// `error`, `warning`, ...
+    pub level: String,
+    /// e.g. `E0308`
+    pub code: Option<String>,
+    pub message: String,
+    /// Primary span; `None` for crate-level messages
+    pub span: Option<Span>,
+    pub label: Option<String>,
+    pub suggestions: Vec<Suggestion>,
+    /// rustc's own human-readable text
+    pub rendered: String,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.level.starts_with("error")
+    }
+
+    /// Level, code, message and file without the position, which shifts
+    /// as code moves
+    pub fn key(&self) -> String {
+        let file = self.span.as_ref().map(|s| format!(" ({})", s.file)).unwrap_or_default();
+        format!("{}: {}{}", self.kind(), self.message, file)
+    }
+
+    /// `error[E0308] src/lib.rs:3:5: mismatched types (expected `u32`, found `&str`)`
+    pub fn headline(&self) -> String {
+        let mut line = self.kind();
+        if let Some(span) = &self.span {
+            line.push_str(&format!(" {}:{}:{}", span.file, span.line_start, span.column_start));
+        }
+        line.push_str(&format!(": {}", self.message));
+        if let Some(label) = self.label.as_deref().filter(|l| !l.is_empty()) {
+            line.push_str(&format!(" ({})", label));
+        }
+        line
+    }
+
+    fn kind(&self) -> String {
+        match &self.code {
+            Some(code) => format!("{}[{}]", self.level, code),
+            None => self.level.clone(),
+        }
+    }
+}
+
+/// Compiler messages in the JSON lines cargo wrote to stdout; other lines
+/// and repeats (the same message for several targets) are skipped
+pub fn parse(stdout: &str) -> Vec<Diagnostic> {
+    let mut seen = HashSet::new();
+
+    stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|m| m.reason == "compiler-message")
+        .filter_map(|m| m.message)
+        .filter(|m| !m.is_summary())
+        .map(Diagnostic::from)
+        .filter(|d| seen.insert(d.rendered.clone()))
+        .collect()
+}
+
+/// `diagnostics` per file in order of first appearance; crate-level ones
+/// under an empty name
+pub fn by_file(diagnostics: &[Diagnostic]) -> Vec<(String, Vec<&Diagnostic>)> {
+    let mut files: Vec<(String, Vec<&Diagnostic>)> = Vec::new();
+
+    for diagnostic in diagnostics {
+        let file = diagnostic.span.as_ref().map(|s| s.file.clone()).unwrap_or_default();
+        match files.iter_mut().find(|(f, _)| *f == file) {
+            Some((_, list)) => list.push(diagnostic),
+            None => files.push((file, vec![diagnostic])),
+        }
+    }
+
+    files
+}
+
+/// The lines of `text` covered by `ranges` (1-based, inclusive) plus
+/// `context` around them, numbered; separate regions are split by `...`
+pub fn excerpt(text: &str, ranges: &[(usize, usize)], context: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut regions: Vec<(usize, usize)> = ranges
+        .iter()
+        .filter(|(start, _)| *start >= 1 && *start <= lines.len())
+        .map(|&(start, end)| (start.saturating_sub(context).max(1), (end.max(start) + context).min(lines.len())))
+        .collect();
+    regions.sort();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in regions {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let width = merged.last().map_or(1, |(_, end)| end.to_string().len());
+    let mut out = String::new();
+    for (i, (start, end)) in merged.iter().enumerate() {
+        if i > 0 {
+            out.push_str("...\n");
+        }
+        for n in *start..=*end {
+            out.push_str(&format!("{:>width$} | {}\n", n, lines[n - 1], width = width));
+        }
+    }
+    out
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<RustcMessage>,
+}
+
+#[derive(Deserialize)]
+struct RustcMessage {
+    message: String,
+    code: Option<RustcCode>,
+    level: String,
+    #[serde(default)]
+    spans: Vec<RustcSpan>,
+    #[serde(default)]
+    children: Vec<RustcMessage>,
+    rendered: Option<String>,
+}
+
+impl RustcMessage {
+    /// rustc's closing lines: "aborting due to ...", "N warnings emitted",
+    /// "For more information about this error, ..."
+    fn is_summary(&self) -> bool {
+        self.level == "failure-note"
+            || self.spans.is_empty() && (self.message.starts_with("aborting due to") || self.message.ends_with(" emitted"))
+    }
+}
+
+#[derive(Deserialize)]
+struct RustcCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    is_primary: bool,
+    label: Option<String>,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<String>,
+}
+
+impl RustcSpan {
+    fn span(&self) -> Span {
+        Span {
+            file: self.file_name.clone(),
+            line_start: self.line_start,
+            line_end: self.line_end,
+            column_start: self.column_start,
+            column_end: self.column_end,
+        }
+    }
+}
+
+impl From<RustcMessage> for Diagnostic {
+    fn from(message: RustcMessage) -> Self {
+        let primary = message.spans.iter().find(|s| s.is_primary).or(message.spans.first());
+
+        let mut suggestions = Vec::new();
+        for (text, spans) in std::iter::once((&message.message, &message.spans))
+            .chain(message.children.iter().map(|c| (&c.message, &c.spans)))
+        {
+            for span in spans {
+                if let Some(replacement) = &span.suggested_replacement {
+                    suggestions.push(Suggestion {
+                        message: text.clone(),
+                        span: span.span(),
+                        replacement: replacement.clone(),
+                        applicability: span.suggestion_applicability.clone(),
+                    });
+                }
+            }
+        }
+
+        Diagnostic {
+            level: message.level.clone(),
+            code: message.code.as_ref().map(|c| c.code.clone()),
+            span: primary.map(RustcSpan::span),
+            label: primary.and_then(|s| s.label.clone()),
+            suggestions,
+            rendered: message.rendered.clone().unwrap_or_else(|| message.message.clone()),
+            message: message.message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_group_and_excerpt() {
+        let stdout = concat!(
+            r#"{"reason":"compiler-artifact","package_id":"dep 0.1.0"}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"message":"cannot find value `cout` in this scope","code":{"code":"E0425","explanation":null},"level":"error","spans":[{"file_name":"src/lib.rs","byte_start":40,"byte_end":44,"line_start":3,"line_end":3,"column_start":5,"column_end":9,"is_primary":true,"text":[],"label":"not found in this scope","suggested_replacement":null,"suggestion_applicability":null}],"children":[{"message":"a local variable with a similar name exists","code":null,"level":"help","spans":[{"file_name":"src/lib.rs","byte_start":40,"byte_end":44,"line_start":3,"line_end":3,"column_start":5,"column_end":9,"is_primary":true,"text":[],"label":null,"suggested_replacement":"count","suggestion_applicability":"MachineApplicable"}],"children":[],"rendered":null}],"rendered":"error[E0425]: cannot find value `cout` in this scope\n"}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/main.rs","byte_start":1,"byte_end":2,"line_start":9,"line_end":9,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null}],"children":[],"rendered":"warning: unused variable: `x`\n"}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"message":"aborting due to 1 previous error","code":null,"level":"error","spans":[],"children":[],"rendered":"error: aborting due to 1 previous error\n"}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"message":"For more information about this error, try `rustc --explain E0425`.","code":null,"level":"failure-note","spans":[],"children":[],"rendered":"For more information about this error, try `rustc --explain E0425`.\n"}}"#, "\n",
+            r#"{"reason":"build-finished","success":false}"#, "\n",
+        );
+
+        let diagnostics = parse(stdout);
+        assert_eq!(diagnostics.len(), 2);
+
+        let error = &diagnostics[0];
+        assert!(error.is_error());
+        assert_eq!(error.headline(), "error[E0425] src/lib.rs:3:5: cannot find value `cout` in this scope (not found in this scope)");
+        assert_eq!(error.key(), "error[E0425]: cannot find value `cout` in this scope (src/lib.rs)");
+        assert_eq!(error.suggestions.len(), 1);
+        assert!(error.suggestions[0].is_machine_applicable());
+        assert_eq!(error.suggestions[0].replacement, "count");
+
+        let files = by_file(&diagnostics);
+        assert_eq!(files.iter().map(|(f, d)| (f.as_str(), d.len())).collect::<Vec<_>>(), vec![("src/lib.rs", 1), ("src/main.rs", 1)]);
+
+        let text = (1..=20).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+        assert_eq!(excerpt(&text, &[(3, 3), (5, 5)], 1), "2 | line 2\n3 | line 3\n4 | line 4\n5 | line 5\n6 | line 6\n");
+        assert_eq!(excerpt(&text, &[(2, 2), (10, 10)], 0), " 2 | line 2\n...\n10 | line 10\n");
+    }
+}
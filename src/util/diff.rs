@@ -0,0 +1,156 @@
+/// Lines kept around each change in a hunk
+const CONTEXT: usize = 3;
+
+/// Above this many line pairs the changed middle is shown as one block
+/// instead of being aligned line by line
+const MAX_ALIGN: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Same,
+    Removed,
+    Added,
+}
+
+/// Unified diff of `old` against `new` as `git diff` would show it for
+/// `path`; `None` marks a file that is created or deleted. Empty when
+/// nothing changed.
+pub fn unified_diff(path: &str, old: Option<&str>, new: Option<&str>) -> String {
+    let before: Vec<&str> = old.map(|t| t.lines().collect()).unwrap_or_default();
+    let after: Vec<&str> = new.map(|t| t.lines().collect()).unwrap_or_default();
+
+    let ops = align(&before, &after);
+    if old == new || ops.iter().all(|(op, _)| *op == Op::Same) && old.is_some() == new.is_some() {
+        return String::new();
+    }
+
+    let from = if old.is_some() { format!("a/{}", path) } else { "/dev/null".to_string() };
+    let to = if new.is_some() { format!("b/{}", path) } else { "/dev/null".to_string() };
+    let mut out = format!("--- {}\n+++ {}\n", from, to);
+
+    for hunk in hunks(&ops) {
+        let (mut old_start, mut new_start) = (1, 1);
+        for (op, _) in &ops[..hunk.start] {
+            if *op != Op::Added { old_start += 1; }
+            if *op != Op::Removed { new_start += 1; }
+        }
+
+        let lines = &ops[hunk.clone()];
+        let old_len = lines.iter().filter(|(op, _)| *op != Op::Added).count();
+        let new_len = lines.iter().filter(|(op, _)| *op != Op::Removed).count();
+
+        // An empty side starts before its first line, like `git diff`
+        let old_start = if old_len == 0 { old_start - 1 } else { old_start };
+        let new_start = if new_len == 0 { new_start - 1 } else { new_start };
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_len, new_start, new_len));
+
+        for (op, line) in lines {
+            let sign = match op {
+                Op::Same => ' ',
+                Op::Removed => '-',
+                Op::Added => '+',
+            };
+            out.push(sign);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Edit script from `before` to `after` via the longest common subsequence
+fn align<'a>(before: &[&'a str], after: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let prefix = before.iter().zip(after).take_while(|(a, b)| a == b).count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old = &before[prefix..before.len() - suffix];
+    let new = &after[prefix..after.len() - suffix];
+
+    let mut ops: Vec<(Op, &str)> = before[..prefix].iter().map(|l| (Op::Same, *l)).collect();
+
+    if old.len().saturating_mul(new.len()) > MAX_ALIGN {
+        ops.extend(old.iter().map(|l| (Op::Removed, *l)));
+        ops.extend(new.iter().map(|l| (Op::Added, *l)));
+    } else {
+        // lcs[i][j]: common lines of old[i..] and new[j..]
+        let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                ops.push((Op::Same, old[i]));
+                i += 1;
+                j += 1;
+            } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push((Op::Removed, old[i]));
+                i += 1;
+            } else {
+                ops.push((Op::Added, new[j]));
+                j += 1;
+            }
+        }
+    }
+
+    ops.extend(before[before.len() - suffix..].iter().map(|l| (Op::Same, *l)));
+    ops
+}
+
+/// Ranges of `ops` to print: each change with its context, merging
+/// changes whose context overlaps
+fn hunks(ops: &[(Op, &str)]) -> Vec<std::ops::Range<usize>> {
+    let mut hunks: Vec<std::ops::Range<usize>> = Vec::new();
+
+    for (i, (op, _)) in ops.iter().enumerate() {
+        if *op == Op::Same {
+            continue;
+        }
+
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + 1 + CONTEXT).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+
+        assert_eq!(
+            unified_diff("src/x.rs", Some(old), Some(new)),
+            "--- a/src/x.rs\n+++ b/src/x.rs\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+
+        assert_eq!(
+            unified_diff("new.rs", None, Some("x\n")),
+            "--- /dev/null\n+++ b/new.rs\n@@ -0,0 +1,1 @@\n+x\n"
+        );
+        assert_eq!(unified_diff("same.rs", Some(old), Some(old)), "");
+    }
+}
@@ -1,2 +1,5 @@
-﻿pub mod logging;
+﻿pub mod diagnostics;
+pub mod diff;
+pub mod logging;
+pub mod path;
 pub mod time;
@@ -0,0 +1,42 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+/// Resolve `path` inside `root` without touching the filesystem.
+/// Absolute paths and `..` that would climb above `root` are refused.
+pub fn confine(root: &Path, path: &str) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !relative.pop() {
+                    bail!("Path escapes the project root: {}", path);
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                bail!("Absolute paths are not allowed: {}", path);
+            }
+        }
+    }
+
+    Ok(root.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confine() {
+        let root = Path::new("/srv/project");
+
+        assert_eq!(confine(root, "src/./main.rs").unwrap(), root.join("src/main.rs"));
+        assert_eq!(confine(root, "src/../Cargo.toml").unwrap(), root.join("Cargo.toml"));
+        assert!(confine(root, "../secrets").is_err());
+        assert!(confine(root, "src/../../etc").is_err());
+        assert!(confine(root, "/etc/passwd").is_err());
+    }
+}
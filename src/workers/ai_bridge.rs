@@ -1,5 +1,7 @@
-﻿use crate::ai::model_router;
-use std::path::Path;
+﻿use crate::ai::model_router::{self, AiTaskType};
+use crate::event::bus::AiSender;
+use crate::event::event::AxonEvent;
+use uuid::Uuid;
 
 pub async fn route_and_query(prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
     model_router::route_and_query(prompt).await
@@ -7,16 +9,19 @@ pub async fn route_and_query(prompt: &str) -> Result<String, Box<dyn std::error:
 
 pub async fn suggest_fix(code: &str, instruction: &str) -> Result<String, Box<dyn std::error::Error>> {
     let prompt = format!("Code:\n{}\n\nInstruction: {}", code, instruction);
-    model_router::route_and_query(&prompt).await
+    model_router::query_for_task(AiTaskType::Coding, &prompt).await
 }
 
 pub async fn validate_fix(code: &str, fix: &str) -> Result<bool, Box<dyn std::error::Error>> {
     let prompt = format!("Code: {}\nFix: {}\nIs this valid? Reply ONLY with VALID or INVALID", code, fix);
-    let res = model_router::route_and_query(&prompt).await?;
+    let res = model_router::query_for_task(AiTaskType::Coding, &prompt).await?;
     Ok(res.contains("VALID"))
 }
 
-pub async fn apply_ai_patch(path: &Path, _errors: String) -> Result<(), Box<dyn std::error::Error>> {
-    tracing::info!("Applying patch to {:?}", path);
-    Ok(())
+/// Queue a repair of `project` in the AI runtime; the result arrives as `BuildFinished`
+pub async fn request_repair(ai_tx: &AiSender, project: &str) -> Result<Uuid, Box<dyn std::error::Error>> {
+    let id = Uuid::new_v4();
+    tracing::info!("Requesting repair [{}] of {}", id, project);
+    ai_tx.send(AxonEvent::RepairRequested { id, project: project.to_string() }).await?;
+    Ok(id)
 }
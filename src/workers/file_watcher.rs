@@ -19,7 +19,7 @@ pub async fn run(tx: EventSender, _state: Arc<AppState>, root: PathBuf) -> anyho
         if let Ok(event) = res {
             if event.kind.is_modify() || event.kind.is_create() {
                 for path in event.paths {
-                    if path.extension().map_or(false, |ext| ext == "rs" || ext == "toml") {
+                    if path.extension().is_some_and(|ext| ext == "rs" || ext == "toml") {
                         tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
                         if let Ok(content) = tokio::fs::read_to_string(&path).await {
                             if !content.is_empty() {
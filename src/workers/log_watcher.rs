@@ -1,10 +1,15 @@
 ﻿use std::fs::File;
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::Arc;
 use tokio::time::{sleep, Duration};
+
+use crate::ai::workspace::GitWorkspace;
+use crate::event::bus::AiSender;
 use crate::workers::universal_commander::{UniversalCommander, UniversalTask};
 
-pub async fn watch_logs_for_commands(log_path: &str) {
+pub async fn watch_logs_for_commands(log_path: &str, ai_tx: AiSender, workspace: Arc<GitWorkspace>) {
+    let commander = UniversalCommander::new(ai_tx, workspace);
     let path = Path::new(log_path);
     
     // Așteptăm să apară fișierul dacă nu există
@@ -26,7 +31,7 @@ pub async fn watch_logs_for_commands(log_path: &str) {
                     let json_part = line.split("COMMANDER_INPUT:").last().unwrap_or("").trim();
                     if let Ok(task) = serde_json::from_str::<UniversalTask>(json_part) {
                         println!(">>> [LOG_WATCHER] Comanda detectata! Delegare catre Commander...");
-                        let _ = UniversalCommander::dispatch(task).await;
+                        let _ = commander.dispatch(task).await;
                     } else {
                         eprintln!(">>> [ERROR] JSON invalid detectat in log: {}", json_part);
                     }
@@ -1,10 +1,16 @@
 ﻿use std::process::Command;
-use std::path::Path;
-use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+use crate::ai::multi_agent_router::{PatchAction, PatchNode, PatchType};
+use crate::ai::patch_executor::PatchExecutor;
+use crate::ai::workspace::GitWorkspace;
+use crate::event::bus::AiSender;
+use crate::util::diagnostics::{self, by_file, Diagnostic};
+use crate::util::path::confine;
 use crate::workers::ai_bridge;
- // We assume the bus is required for status logging
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UniversalTask {
@@ -13,12 +19,23 @@ pub struct UniversalTask {
     pub ai_instruction: Option<String>,
 }
 
-pub struct UniversalCommander;
+/// Runs `UniversalTask`s; build errors are handed to the AI runtime and
+/// generated code goes to the AI branch of `workspace`
+pub struct UniversalCommander {
+    ai_tx: AiSender,
+    workspace: Arc<GitWorkspace>,
+}
 
 impl UniversalCommander {
-    pub async fn dispatch(task: UniversalTask) -> Result<(), Box<dyn std::error::Error>> {
-        let root = Path::new(&task.target_path);
-        
+    pub fn new(ai_tx: AiSender, workspace: Arc<GitWorkspace>) -> Self {
+        Self { ai_tx, workspace }
+    }
+
+    /// Run the task's actions on its project in the AI workspace
+    pub async fn dispatch(&self, task: UniversalTask) -> Result<(), Box<dyn std::error::Error>> {
+        let project = self.project(&task.target_path)?;
+        let root = confine(self.workspace.root(), &project)?;
+
         if !root.exists() {
             return Err(format!("PATH_NOT_FOUND: {:?}", root).into());
         }
@@ -26,21 +43,21 @@ impl UniversalCommander {
         for action in &task.actions {
             match action.to_uppercase().as_str() {
                 // 1. EXECUTION: Build and error verification
-                "BUILD" => Self::execute_build(root).await?,
+                "BUILD" => self.execute_build(&root, &project).await?,
 
                 // 2. WRITE: AI generates and writes new code
                 "WRITE_CODE" => {
                     if let Some(instruction) = &task.ai_instruction {
-                        Self::ai_write_module(root, instruction).await?;
+                        self.ai_write_module(&project, instruction).await?;
                     }
                 },
 
                 // 3. REPAIR: Search for bugs and apply automatic patches
-                "AUTO_FIX" => Self::run_self_healing(root).await?,
+                "AUTO_FIX" => Self::run_self_healing(&root).await?,
 
                 // 4. FORMAT: Code cleanup
                 "FMT" => {
-                    let _ = Command::new("cargo").args(["fmt"]).current_dir(root).status();
+                    let _ = Command::new("cargo").args(["fmt"]).current_dir(&root).status();
                 },
 
                 _ => println!(">>> [WARN] Unknown action: {}", action),
@@ -49,27 +66,59 @@ impl UniversalCommander {
         Ok(())
     }
 
-    /// Executes 'cargo build' and captures errors to send them to AI
-    async fn execute_build(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    /// `target_path` relative to the repository; absolute paths must lie
+    /// inside it
+    fn project(&self, target_path: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let path = Path::new(target_path);
+        let relative = if path.is_absolute() {
+            let repo = self.workspace.repo().canonicalize()?;
+            let target = path.canonicalize().map_err(|_| format!("PATH_NOT_FOUND: {:?}", path))?;
+            target
+                .strip_prefix(&repo)
+                .map_err(|_| format!("PATH_OUTSIDE_REPO: {:?}", path))?
+                .to_path_buf()
+        } else {
+            PathBuf::from(target_path)
+        };
+
+        let relative = relative.to_string_lossy().into_owned();
+        let relative = if relative.is_empty() { ".".to_string() } else { relative };
+        confine(self.workspace.repo(), &relative)?;
+        Ok(relative)
+    }
+
+    /// Executes 'cargo build' in `root` and captures errors to send them to AI
+    async fn execute_build(&self, root: &Path, project: &str) -> Result<(), Box<dyn std::error::Error>> {
         println!(">>> [COMMANDER] Running build...");
         let output = Command::new("cargo")
-            .args(["build", "--message-format=short"])
+            .args(["build", "--message-format=json"])
             .current_dir(root)
             .output()?;
 
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
+            let errors: Vec<Diagnostic> = diagnostics::parse(&String::from_utf8_lossy(&output.stdout))
+                .into_iter()
+                .filter(Diagnostic::is_error)
+                .collect();
+
             println!(">>> [BUILD_FAILED] Errors detected. Triggering AI Repair...");
+            for (file, list) in by_file(&errors) {
+                println!("    {} ({} error(s))", if file.is_empty() { "<crate>" } else { &file }, list.len());
+                for diagnostic in list {
+                    println!("      {}", diagnostic.headline());
+                }
+            }
             // If the build fails, send errors to the auto-fix process
-            Self::handle_build_errors(root, &stderr).await?;
+            self.handle_build_errors(project, &errors).await?;
         } else {
             println!(">>> [SUCCESS] Build completed successfully.");
         }
         Ok(())
     }
 
-    /// AI writes a new module based on instructions
-    async fn ai_write_module(root: &Path, instruction: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// AI writes a new module based on instructions, committed to the AI
+    /// branch; the user's checkout only gets it on merge
+    async fn ai_write_module(&self, project: &str, instruction: &str) -> Result<(), Box<dyn std::error::Error>> {
         println!(">>> [AI_WRITE] Task: {}", instruction);
         
         // Ask AI for the raw generated code
@@ -77,23 +126,41 @@ impl UniversalCommander {
         
         // Validate the generated code before writing it
         if ai_bridge::validate_fix("/* New Module */", &generated_code).await? {
-            let file_path = root.join("src/ai_generated.rs"); // Example destination
-            fs::write(&file_path, generated_code)?;
-            println!(">>> [SUCCESS] Code written to {:?}", file_path);
+            let executor = PatchExecutor::new(self.workspace.root());
+            let file_path = Path::new(project).join("src/ai_generated.rs").to_string_lossy().into_owned(); // Example destination
+            let change_type = if executor.current(&file_path)?.is_some() { PatchType::ModifyFile } else { PatchType::CreateFile };
+
+            let mut node = PatchNode::new(Uuid::new_v4().simple().to_string(), format!("Write module: {}", instruction));
+            node.actions.push(PatchAction {
+                file_path: file_path.clone(),
+                description: "Generated module".into(),
+                change_type,
+                content: Some(generated_code),
+                search: None,
+            });
+
+            let outcome = executor.apply(&node);
+            if !outcome.report.success {
+                return Err(outcome.report.summary().into());
+            }
+
+            match self.workspace.commit(&node, &outcome.report).await? {
+                Some(hash) => println!(">>> [SUCCESS] {} committed as {} on {}", file_path, hash, self.workspace.branch()),
+                None => println!(">>> [SUCCESS] Code written to {}", file_path),
+            }
         }
         Ok(())
     }
 
-    /// Self-Healing: Takes compilation errors and automatically modifies files
-    async fn handle_build_errors(root: &Path, errors: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // Error-to-file mapping logic
-        // At this stage, the AI Bridge receives the error and decides where to apply the patch
-        println!(">>> [HEALER] Analyzing build log...");
-        
-        // Example: apply patch to main file if errors occur
-        let main_path = root.join("src/main.rs").to_str().unwrap().to_string();
-        ai_bridge::apply_ai_patch(std::path::Path::new(&main_path), errors.to_string()).await?;
-        
+    /// Self-Healing: the repair loop patches, re-checks and reverts until
+    /// the project compiles or it runs out of attempts, then reports with
+    /// `BuildFinished`
+    async fn handle_build_errors(&self, project: &str, errors: &[Diagnostic]) -> Result<(), Box<dyn std::error::Error>> {
+        println!(">>> [HEALER] {} error(s), starting repair loop...", errors.len());
+
+        let id = ai_bridge::request_repair(&self.ai_tx, project).await?;
+        println!(">>> [HEALER] Repair [{}] queued for {}", id, project);
+
         Ok(())
     }
 
@@ -105,3 +172,24 @@ impl UniversalCommander {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::AxonConfig;
+
+    #[test]
+    fn test_project_is_relative_to_the_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("app")).unwrap();
+        let (ai_tx, _ai_rx) = tokio::sync::mpsc::channel(1);
+        let commander = UniversalCommander::new(ai_tx, Arc::new(GitWorkspace::direct(dir.path(), &AxonConfig::default())));
+
+        let absolute = dir.path().join("app").to_string_lossy().into_owned();
+        assert_eq!(commander.project(&absolute).unwrap(), "app");
+        assert_eq!(commander.project(&dir.path().to_string_lossy()).unwrap(), ".");
+        assert_eq!(commander.project("app").unwrap(), "app");
+        assert!(commander.project("../elsewhere").is_err());
+        assert!(commander.project(&std::env::temp_dir().to_string_lossy()).is_err());
+    }
+}